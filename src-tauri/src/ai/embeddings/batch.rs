@@ -0,0 +1,109 @@
+use super::{EmbedError, Embedder};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// Tuning knobs for bulk embedding.
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    /// Texts sent per request.
+    pub batch_size: usize,
+    /// Requests allowed in flight at the same time.
+    pub concurrency: usize,
+    /// Retries per request after the first attempt.
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled after every failure.
+    pub initial_backoff: Duration,
+    /// Upper bound for a single retry delay.
+    pub max_backoff: Duration,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: 32,
+            concurrency: 4,
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Run `op`, retrying on rate limits, 5xx and transport errors with
+/// exponential backoff. A server-provided `Retry-After` takes precedence.
+pub async fn with_retry<T, F, Fut>(options: &BatchOptions, mut op: F) -> Result<T, EmbedError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, EmbedError>>,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(e) if e.is_retryable() && attempt < options.max_retries => {
+                let backoff = options
+                    .initial_backoff
+                    .saturating_mul(1u32 << attempt.min(16))
+                    .min(options.max_backoff);
+                let delay = match &e {
+                    EmbedError::Status { retry_after: Some(after), .. } => (*after).min(options.max_backoff),
+                    _ => backoff,
+                };
+                attempt += 1;
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Embed a single query string (search, chat) with retries.
+pub async fn embed_query(embedder: &dyn Embedder, text: &str, options: &BatchOptions) -> Result<Vec<f32>, EmbedError> {
+    with_retry(options, || embedder.embed(text)).await
+}
+
+/// Embed every text, `batch_size` at a time with at most `concurrency`
+/// requests in flight. Output order matches input order.
+///
+/// The first batch that still fails after its retries aborts the others.
+pub async fn embed_all(
+    embedder: Arc<dyn Embedder>,
+    texts: &[String],
+    options: &BatchOptions,
+) -> Result<Vec<Vec<f32>>, EmbedError> {
+    if texts.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let batch_size = options.batch_size.max(1);
+    let semaphore = Arc::new(Semaphore::new(options.concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+
+    for (index, batch) in texts.chunks(batch_size).enumerate() {
+        let batch = batch.to_vec();
+        let embedder = embedder.clone();
+        let semaphore = semaphore.clone();
+        let options = options.clone();
+
+        tasks.spawn(async move {
+            let _permit = semaphore
+                .acquire_owned()
+                .await
+                .map_err(|e| EmbedError::Other(e.to_string()))?;
+            let embeddings = with_retry(&options, || embedder.embed_batch(&batch)).await?;
+            Ok::<_, EmbedError>((index, embeddings))
+        });
+    }
+
+    let mut batches: Vec<Option<Vec<Vec<f32>>>> = vec![None; tasks.len()];
+    while let Some(joined) = tasks.join_next().await {
+        // Returning early drops the JoinSet, which aborts the remaining tasks
+        let (index, embeddings) = joined.map_err(|e| EmbedError::Other(e.to_string()))??;
+        batches[index] = Some(embeddings);
+    }
+
+    Ok(batches.into_iter().flatten().flatten().collect())
+}
@@ -0,0 +1,148 @@
+use super::{check_dimension, check_status, EmbedError, Embedder};
+use crate::ai::http::shared_client;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+pub const DEFAULT_MODEL: &str = "embedding-001";
+pub const DEFAULT_DIMENSION: usize = 768;
+
+/// Embeddings from the Google Gemini API.
+pub struct GeminiEmbedder {
+    client: Client,
+    base_url: String,
+    api_key: String,
+    model: String,
+    dimension: usize,
+}
+
+#[derive(Serialize)]
+struct EmbedContentRequest<'a> {
+    model: String,
+    content: GeminiContent<'a>,
+}
+
+#[derive(Serialize)]
+struct BatchEmbedRequest<'a> {
+    requests: Vec<EmbedContentRequest<'a>>,
+}
+
+#[derive(Serialize)]
+struct GeminiContent<'a> {
+    parts: Vec<GeminiPart<'a>>,
+}
+
+#[derive(Serialize)]
+struct GeminiPart<'a> {
+    text: &'a str,
+}
+
+#[derive(Deserialize)]
+struct EmbedContentResponse {
+    embedding: GeminiEmbedding,
+}
+
+#[derive(Deserialize)]
+struct BatchEmbedResponse {
+    embeddings: Vec<GeminiEmbedding>,
+}
+
+#[derive(Deserialize)]
+struct GeminiEmbedding {
+    values: Vec<f32>,
+}
+
+impl GeminiEmbedder {
+    pub fn new(api_key: String) -> Self {
+        Self::with_model(DEFAULT_BASE_URL, api_key, DEFAULT_MODEL, DEFAULT_DIMENSION)
+    }
+
+    pub fn with_model(base_url: &str, api_key: String, model: &str, dimension: usize) -> Self {
+        Self {
+            client: shared_client(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            model: model.to_string(),
+            dimension,
+        }
+    }
+
+    fn request<'a>(&self, text: &'a str) -> EmbedContentRequest<'a> {
+        // Gemini expects "content" object structure
+        EmbedContentRequest {
+            model: format!("models/{}", self.model),
+            content: GeminiContent {
+                parts: vec![GeminiPart { text }],
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl Embedder for GeminiEmbedder {
+    fn model_id(&self) -> &str {
+        &self.model
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, EmbedError> {
+        let url = format!(
+            "{}/models/{}:embedContent?key={}",
+            self.base_url, self.model, self.api_key
+        );
+
+        let res = self
+            .client
+            .post(&url)
+            .json(&self.request(text))
+            .send()
+            .await?;
+        let res = check_status(res, "Gemini").await?;
+
+        let response: EmbedContentResponse = res.json().await?;
+        check_dimension(&self.model, self.dimension, &response.embedding.values)?;
+        Ok(response.embedding.values)
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let url = format!(
+            "{}/models/{}:batchEmbedContents?key={}",
+            self.base_url, self.model, self.api_key
+        );
+        let request = BatchEmbedRequest {
+            requests: texts.iter().map(|t| self.request(t)).collect(),
+        };
+
+        let res = self
+            .client
+            .post(&url)
+            .json(&request)
+            .send()
+            .await?;
+        let res = check_status(res, "Gemini").await?;
+
+        let response: BatchEmbedResponse = res.json().await?;
+        if response.embeddings.len() != texts.len() {
+            return Err(EmbedError::Other(format!(
+                "Gemini returned {} embeddings for {} inputs",
+                response.embeddings.len(),
+                texts.len()
+            )));
+        }
+
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for embedding in response.embeddings {
+            check_dimension(&self.model, self.dimension, &embedding.values)?;
+            embeddings.push(embedding.values);
+        }
+        Ok(embeddings)
+    }
+}
@@ -0,0 +1,161 @@
+use crate::settings::AppSettings;
+use async_trait::async_trait;
+use reqwest::header::RETRY_AFTER;
+use reqwest::Response;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub mod batch;
+pub mod gemini;
+pub mod hash;
+pub mod ollama;
+pub mod openai;
+
+pub use batch::{embed_all, embed_query, BatchOptions};
+pub use gemini::GeminiEmbedder;
+pub use hash::HashEmbedder;
+pub use ollama::OllamaEmbedder;
+pub use openai::OpenAiEmbedder;
+
+/// Failure of an embedding request.
+#[derive(Debug, Clone)]
+pub enum EmbedError {
+    /// The server answered with a non-success status.
+    Status {
+        status: u16,
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    /// The request did not complete (connection refused, reset, timeout).
+    Transport(String),
+    /// Malformed responses, dimension mismatches, bad configuration.
+    Other(String),
+}
+
+impl EmbedError {
+    /// Whether the same request may succeed if sent again later.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            EmbedError::Status { status, .. } => *status == 429 || *status >= 500,
+            EmbedError::Transport(_) => true,
+            EmbedError::Other(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for EmbedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmbedError::Status { message, .. } => write!(f, "{}", message),
+            EmbedError::Transport(e) => write!(f, "Embedding request failed: {}", e),
+            EmbedError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for EmbedError {}
+
+impl From<reqwest::Error> for EmbedError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            EmbedError::Other(e.to_string())
+        } else {
+            EmbedError::Transport(e.to_string())
+        }
+    }
+}
+
+impl From<EmbedError> for String {
+    fn from(e: EmbedError) -> Self {
+        e.to_string()
+    }
+}
+
+/// A backend that turns text into fixed-size embedding vectors.
+///
+/// Implementations must always return vectors of length `dimension()`,
+/// since that value decides which vector index the results are stored in.
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    /// Identifier of the model producing the vectors (e.g. `nomic-embed-text`).
+    fn model_id(&self) -> &str;
+
+    /// Length of every vector returned by this embedder.
+    fn dimension(&self) -> usize;
+
+    /// Embed a single piece of text.
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, EmbedError>;
+
+    /// Embed several texts, preserving input order.
+    /// Backends with a native batch endpoint should override this.
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for text in texts {
+            embeddings.push(self.embed(text).await?);
+        }
+        Ok(embeddings)
+    }
+}
+
+/// Build the embedder selected in the application settings.
+pub fn from_settings(settings: &AppSettings) -> Result<Arc<dyn Embedder>, String> {
+    let model = settings.embedding_model.as_str();
+    let dimension = settings.embedding_dimension;
+
+    match settings.embedding_provider.as_str() {
+        "ollama" => Ok(Arc::new(OllamaEmbedder::new(&settings.ollama_url, model, dimension))),
+        "gemini" => {
+            let api_key = settings
+                .gemini_api_key
+                .clone()
+                .filter(|k| !k.trim().is_empty())
+                .ok_or("API key required for Gemini")?;
+            Ok(Arc::new(GeminiEmbedder::with_model(&settings.gemini_base_url, api_key, model, dimension)))
+        }
+        "openai" => Ok(Arc::new(OpenAiEmbedder::new(
+            &settings.openai_base_url,
+            settings.openai_api_key.clone(),
+            model,
+            dimension,
+        ))),
+        "hash" => Ok(Arc::new(HashEmbedder::new(dimension))),
+        other => Err(format!("Unknown embedding provider: {}", other)),
+    }
+}
+
+/// Turn a non-success response into an `EmbedError`, keeping the body text
+/// and any `Retry-After` hint (in seconds) for the retry loop.
+pub(crate) async fn check_status(res: Response, api: &str) -> Result<Response, EmbedError> {
+    let status = res.status();
+    if status.is_success() {
+        return Ok(res);
+    }
+
+    let retry_after = res
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(Duration::from_secs);
+    let body = res.text().await.unwrap_or_default();
+
+    Err(EmbedError::Status {
+        status: status.as_u16(),
+        message: format!("{} API error: {} {}", api, status, body).trim_end().to_string(),
+        retry_after,
+    })
+}
+
+/// Fail if a backend returned a vector of the wrong size.
+pub(crate) fn check_dimension(model: &str, expected: usize, embedding: &[f32]) -> Result<(), EmbedError> {
+    if embedding.len() != expected {
+        return Err(EmbedError::Other(format!(
+            "Embedding model {} returned {} dimensions, expected {}",
+            model,
+            embedding.len(),
+            expected
+        )));
+    }
+    Ok(())
+}
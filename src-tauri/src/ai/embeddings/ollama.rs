@@ -0,0 +1,119 @@
+use super::{check_dimension, check_status, EmbedError, Embedder};
+use crate::ai::http::shared_client;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_BASE_URL: &str = "http://localhost:11434";
+pub const DEFAULT_MODEL: &str = "nomic-embed-text";
+pub const DEFAULT_DIMENSION: usize = 768;
+
+/// Embeddings served by a local Ollama instance.
+pub struct OllamaEmbedder {
+    client: Client,
+    base_url: String,
+    model: String,
+    dimension: usize,
+}
+
+#[derive(Serialize)]
+struct EmbedRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct EmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+#[derive(Serialize)]
+struct EmbeddingsRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingsResponse {
+    embedding: Vec<f32>,
+}
+
+impl OllamaEmbedder {
+    pub fn new(base_url: &str, model: &str, dimension: usize) -> Self {
+        Self {
+            client: shared_client(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            dimension,
+        }
+    }
+}
+
+impl Default for OllamaEmbedder {
+    fn default() -> Self {
+        Self::new(DEFAULT_BASE_URL, DEFAULT_MODEL, DEFAULT_DIMENSION)
+    }
+}
+
+#[async_trait]
+impl Embedder for OllamaEmbedder {
+    fn model_id(&self) -> &str {
+        &self.model
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, EmbedError> {
+        let request = EmbeddingsRequest {
+            model: &self.model,
+            prompt: text,
+        };
+
+        let res = self
+            .client
+            .post(format!("{}/api/embeddings", self.base_url))
+            .json(&request)
+            .send()
+            .await?;
+        let res = check_status(res, "Ollama").await?;
+
+        let response: EmbeddingsResponse = res.json().await?;
+        check_dimension(&self.model, self.dimension, &response.embedding)?;
+        Ok(response.embedding)
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // `/api/embed` accepts a list of inputs in one request
+        let request = EmbedRequest {
+            model: &self.model,
+            input: texts,
+        };
+
+        let res = self
+            .client
+            .post(format!("{}/api/embed", self.base_url))
+            .json(&request)
+            .send()
+            .await?;
+        let res = check_status(res, "Ollama").await?;
+
+        let response: EmbedResponse = res.json().await?;
+        if response.embeddings.len() != texts.len() {
+            return Err(EmbedError::Other(format!(
+                "Ollama returned {} embeddings for {} inputs",
+                response.embeddings.len(),
+                texts.len()
+            )));
+        }
+        for embedding in &response.embeddings {
+            check_dimension(&self.model, self.dimension, embedding)?;
+        }
+        Ok(response.embeddings)
+    }
+}
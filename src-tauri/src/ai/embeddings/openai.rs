@@ -0,0 +1,106 @@
+use super::{check_dimension, check_status, EmbedError, Embedder};
+use crate::ai::http::shared_client;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+pub const DEFAULT_MODEL: &str = "text-embedding-3-small";
+pub const DEFAULT_DIMENSION: usize = 1536;
+
+/// Embeddings from any server implementing the OpenAI `/embeddings` endpoint
+/// (OpenAI itself, llama.cpp server, vLLM, LM Studio, ...).
+pub struct OpenAiEmbedder {
+    client: Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+    dimension: usize,
+}
+
+#[derive(Serialize)]
+struct EmbeddingsRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct EmbeddingsResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+impl OpenAiEmbedder {
+    pub fn new(base_url: &str, api_key: Option<String>, model: &str, dimension: usize) -> Self {
+        Self {
+            client: shared_client(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.filter(|k| !k.is_empty()),
+            model: model.to_string(),
+            dimension,
+        }
+    }
+}
+
+#[async_trait]
+impl Embedder for OpenAiEmbedder {
+    fn model_id(&self) -> &str {
+        &self.model
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, EmbedError> {
+        let mut embeddings = self.embed_batch(&[text.to_string()]).await?;
+        embeddings
+            .pop()
+            .ok_or_else(|| EmbedError::Other("OpenAI API returned no embedding".to_string()))
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, EmbedError> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let request = EmbeddingsRequest {
+            model: &self.model,
+            input: texts,
+        };
+
+        let mut builder = self
+            .client
+            .post(format!("{}/embeddings", self.base_url))
+            .json(&request);
+        if let Some(key) = &self.api_key {
+            builder = builder.bearer_auth(key);
+        }
+
+        let res = builder.send().await?;
+        let res = check_status(res, "OpenAI").await?;
+
+        let mut response: EmbeddingsResponse = res.json().await?;
+        if response.data.len() != texts.len() {
+            return Err(EmbedError::Other(format!(
+                "OpenAI API returned {} embeddings for {} inputs",
+                response.data.len(),
+                texts.len()
+            )));
+        }
+
+        // The spec does not guarantee ordering, `index` does
+        response.data.sort_by_key(|d| d.index);
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for data in response.data {
+            check_dimension(&self.model, self.dimension, &data.embedding)?;
+            embeddings.push(data.embedding);
+        }
+        Ok(embeddings)
+    }
+}
@@ -0,0 +1,22 @@
+use reqwest::Client;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+static CLIENT: OnceLock<Client> = OnceLock::new();
+
+/// Process-wide HTTP client for model APIs.
+///
+/// `reqwest::Client` keeps a connection pool internally, so reusing one
+/// instance avoids a new TCP/TLS handshake for every embedding or chat call.
+/// Cloning is cheap (it is an `Arc` inside).
+pub fn shared_client() -> Client {
+    CLIENT
+        .get_or_init(|| {
+            Client::builder()
+                .connect_timeout(Duration::from_secs(10))
+                .pool_idle_timeout(Duration::from_secs(90))
+                .build()
+                .expect("failed to build HTTP client")
+        })
+        .clone()
+}
@@ -0,0 +1,133 @@
+use super::{read_lines, sse_data, ChatMessage, LlmProvider};
+use crate::ai::http::shared_client;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+/// Chat models from the Google Gemini API.
+pub struct GeminiLlm {
+    client: Client,
+    base_url: String,
+    api_key: String,
+    model: String,
+}
+
+impl GeminiLlm {
+    pub fn new(base_url: &str, api_key: String, model: &str) -> Self {
+        Self {
+            client: shared_client(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            model: model.to_string(),
+        }
+    }
+
+    /// Gemini has no "system" role: system messages become the
+    /// `systemInstruction` and "assistant" turns are called "model".
+    fn body(messages: &[ChatMessage]) -> Value {
+        let system: Vec<Value> = messages
+            .iter()
+            .filter(|m| m.role == "system")
+            .map(|m| json!({ "text": m.content }))
+            .collect();
+        let contents: Vec<Value> = messages
+            .iter()
+            .filter(|m| m.role != "system")
+            .map(|m| {
+                let role = if m.role == "assistant" { "model" } else { "user" };
+                json!({ "role": role, "parts": [{ "text": m.content }] })
+            })
+            .collect();
+
+        let mut body = json!({ "contents": contents });
+        if !system.is_empty() {
+            body["systemInstruction"] = json!({ "parts": system });
+        }
+        body
+    }
+}
+
+/// Concatenated text parts of the first candidate.
+fn candidate_text(json: &Value) -> Option<String> {
+    let parts = json["candidates"][0]["content"]["parts"].as_array()?;
+    Some(parts.iter().filter_map(|p| p["text"].as_str()).collect())
+}
+
+async fn check_status(resp: reqwest::Response) -> Result<reqwest::Response, String> {
+    if resp.status().is_success() {
+        return Ok(resp);
+    }
+    let error_text = resp.text().await.unwrap_or_default();
+    Err(format!("Gemini API error: {}", error_text))
+}
+
+#[async_trait]
+impl LlmProvider for GeminiLlm {
+    fn model_id(&self) -> &str {
+        &self.model
+    }
+
+    async fn chat(&self, messages: &[ChatMessage]) -> Result<String, String> {
+        let url = format!(
+            "{}/models/{}:generateContent?key={}",
+            self.base_url, self.model, self.api_key
+        );
+
+        let resp = self
+            .client
+            .post(&url)
+            .json(&Self::body(messages))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to call Gemini API: {}", e))?;
+        let resp = check_status(resp).await?;
+
+        let json: Value = resp.json().await.map_err(|e| e.to_string())?;
+        candidate_text(&json).ok_or_else(|| "Invalid response format from Gemini API".to_string())
+    }
+
+    /// `streamGenerateContent?alt=sse` sends one `data: {...}` response
+    /// object per fragment.
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+    ) -> Result<String, String> {
+        let url = format!(
+            "{}/models/{}:streamGenerateContent?alt=sse&key={}",
+            self.base_url, self.model, self.api_key
+        );
+
+        let resp = self
+            .client
+            .post(&url)
+            .json(&Self::body(messages))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to call Gemini API: {}", e))?;
+        let resp = check_status(resp).await?;
+
+        let mut content = String::new();
+        read_lines(resp, |line| {
+            let Some(data) = sse_data(line) else {
+                return Ok(false);
+            };
+
+            let json: Value = serde_json::from_str(data)
+                .map_err(|e| format!("Invalid stream chunk from Gemini API: {}", e))?;
+            if let Some(error) = json["error"]["message"].as_str() {
+                return Err(format!("Gemini API error: {}", error));
+            }
+            if let Some(delta) = candidate_text(&json) {
+                if !delta.is_empty() {
+                    content.push_str(&delta);
+                    on_delta(&delta);
+                }
+            }
+            Ok(false)
+        })
+        .await?;
+
+        Ok(content)
+    }
+}
@@ -0,0 +1,83 @@
+use super::{ChatMessage, LlmProvider};
+use async_trait::async_trait;
+use std::sync::Mutex;
+
+/// Offline provider for tests and UI work without a model server.
+///
+/// Answers with a fixed reply, or echoes the last user message when none
+/// is set, streams it word by word, and records every conversation it
+/// received so callers can inspect the prompts that were built.
+#[derive(Default)]
+pub struct MockLlm {
+    reply: Option<String>,
+    error: Option<String>,
+    calls: Mutex<Vec<Vec<ChatMessage>>>,
+}
+
+impl MockLlm {
+    pub fn with_reply(reply: &str) -> Self {
+        Self {
+            reply: Some(reply.to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// A provider whose every request fails with `error`, like an
+    /// unreachable model server.
+    pub fn failing(error: &str) -> Self {
+        Self {
+            error: Some(error.to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// Conversations received so far, oldest first.
+    pub fn calls(&self) -> Vec<Vec<ChatMessage>> {
+        self.calls.lock().map(|c| c.clone()).unwrap_or_default()
+    }
+
+    fn answer(&self, messages: &[ChatMessage]) -> Result<String, String> {
+        if let Ok(mut calls) = self.calls.lock() {
+            calls.push(messages.to_vec());
+        }
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        }
+
+        Ok(match &self.reply {
+            Some(reply) => reply.clone(),
+            None => {
+                let question = messages
+                    .iter()
+                    .rev()
+                    .find(|m| m.role == "user")
+                    .map(|m| m.content.as_str())
+                    .unwrap_or_default();
+                format!("Mock answer to: {}", question)
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl LlmProvider for MockLlm {
+    fn model_id(&self) -> &str {
+        "mock"
+    }
+
+    async fn chat(&self, messages: &[ChatMessage]) -> Result<String, String> {
+        self.answer(messages)
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+    ) -> Result<String, String> {
+        let content = self.answer(messages)?;
+        for word in content.split_inclusive(' ') {
+            on_delta(word);
+        }
+        Ok(content)
+    }
+}
@@ -0,0 +1,103 @@
+use crate::settings::AppSettings;
+use async_trait::async_trait;
+use reqwest::Response;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+pub mod gemini;
+pub mod mock;
+pub mod ollama;
+pub mod openai;
+
+pub use gemini::GeminiLlm;
+pub use mock::MockLlm;
+pub use ollama::OllamaLlm;
+pub use openai::OpenAiLlm;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+/// A chat model backend.
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    /// Model answering the requests (e.g. `ministral-3:8b`).
+    fn model_id(&self) -> &str;
+
+    /// Generate a full answer for the conversation.
+    async fn chat(&self, messages: &[ChatMessage]) -> Result<String, String>;
+
+    /// Generate an answer, calling `on_delta` with each fragment as it is
+    /// produced, and return the complete text. Backends without streaming
+    /// support deliver the whole answer as a single fragment.
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+    ) -> Result<String, String> {
+        let content = self.chat(messages).await?;
+        on_delta(&content);
+        Ok(content)
+    }
+
+    /// Single-turn completion of `prompt`.
+    async fn complete(&self, prompt: &str) -> Result<String, String> {
+        let messages = [ChatMessage {
+            role: "user".to_string(),
+            content: prompt.to_string(),
+        }];
+        self.chat(&messages).await
+    }
+}
+
+/// Build the chat model selected in the application settings.
+pub fn from_settings(settings: &AppSettings) -> Result<Arc<dyn LlmProvider>, String> {
+    let model = settings.llm_model.as_str();
+
+    match settings.llm_provider.as_str() {
+        "ollama" => Ok(Arc::new(OllamaLlm::new(&settings.ollama_url, model))),
+        "openai" => Ok(Arc::new(OpenAiLlm::new(
+            &settings.openai_base_url,
+            settings.openai_api_key.clone(),
+            model,
+        ))),
+        "gemini" => {
+            let api_key = settings
+                .gemini_api_key
+                .clone()
+                .filter(|k| !k.trim().is_empty())
+                .ok_or("API key required for Gemini")?;
+            Ok(Arc::new(GeminiLlm::new(&settings.gemini_base_url, api_key, model)))
+        }
+        "mock" => Ok(Arc::new(MockLlm::default())),
+        other => Err(format!("Unknown LLM provider: {}", other)),
+    }
+}
+
+/// Feed each line of a streamed response body to `on_line` until it
+/// returns `Ok(true)` or the body ends. Network chunks may split or merge
+/// lines, so bytes are buffered up to the next newline.
+pub(crate) async fn read_lines<F>(mut resp: Response, mut on_line: F) -> Result<(), String>
+where
+    F: FnMut(&str) -> Result<bool, String>,
+{
+    let mut buffer: Vec<u8> = Vec::new();
+    while let Some(bytes) = resp.chunk().await.map_err(|e| e.to_string())? {
+        buffer.extend_from_slice(&bytes);
+        while let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=pos).collect();
+            if on_line(String::from_utf8_lossy(&line).trim())? {
+                return Ok(());
+            }
+        }
+    }
+    on_line(String::from_utf8_lossy(&buffer).trim())?;
+    Ok(())
+}
+
+/// Payload of a server-sent events `data:` line, if `line` is one.
+pub(crate) fn sse_data(line: &str) -> Option<&str> {
+    line.strip_prefix("data:").map(str::trim)
+}
@@ -0,0 +1,138 @@
+use super::{read_lines, ChatMessage, LlmProvider};
+use crate::ai::http::shared_client;
+use async_trait::async_trait;
+use reqwest::Client;
+
+/// Chat models served by a local Ollama instance.
+pub struct OllamaLlm {
+    client: Client,
+    base_url: String,
+    model: String,
+}
+
+impl OllamaLlm {
+    pub fn new(base_url: &str, model: &str) -> Self {
+        Self {
+            client: shared_client(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OllamaLlm {
+    fn model_id(&self) -> &str {
+        &self.model
+    }
+
+    async fn complete(&self, prompt: &str) -> Result<String, String> {
+        let ollama_url = format!("{}/api/generate", self.base_url);
+
+        let body = serde_json::json!({
+            "model": self.model,
+            "prompt": prompt,
+            "stream": false
+        });
+
+        match self.client.post(&ollama_url)
+            .json(&body)
+            .send()
+            .await {
+                Ok(resp) => {
+                    if resp.status().is_success() {
+                        let json: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+                        if let Some(response_text) = json["response"].as_str() {
+                            Ok(response_text.to_string())
+                        } else {
+                            Err("Invalid response format from Ollama".to_string())
+                        }
+                    } else {
+                         Err(format!("Ollama API error: {}", resp.status()))
+                    }
+                },
+                Err(e) => Err(format!("Failed to call Ollama: {}", e))
+            }
+    }
+
+    async fn chat(&self, messages: &[ChatMessage]) -> Result<String, String> {
+        // Basic chat implementation for Ollama /api/chat
+        let ollama_url = format!("{}/api/chat", self.base_url);
+
+        let body = serde_json::json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": false
+        });
+
+        match self.client.post(&ollama_url)
+            .json(&body)
+            .send()
+            .await {
+                Ok(resp) => {
+                    if resp.status().is_success() {
+                        let json: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+                        // Deeply nested: message -> content
+                        if let Some(content) = json["message"]["content"].as_str() {
+                            Ok(content.to_string())
+                        } else {
+                            Err("Invalid response format from Ollama".to_string())
+                        }
+                    } else {
+                         Err(format!("Ollama API error: {}", resp.status()))
+                    }
+                },
+                Err(e) => Err(format!("Failed to call Ollama: {}", e))
+            }
+    }
+
+    /// Ollama streams NDJSON: one `{"message": {"content": ...}, "done": bool}`
+    /// object per line.
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+    ) -> Result<String, String> {
+        let ollama_url = format!("{}/api/chat", self.base_url);
+
+        let body = serde_json::json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": true
+        });
+
+        let resp = self.client
+            .post(&ollama_url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to call Ollama: {}", e))?;
+        if !resp.status().is_success() {
+            return Err(format!("Ollama API error: {}", resp.status()));
+        }
+
+        let mut content = String::new();
+        read_lines(resp, |line| {
+            if line.is_empty() {
+                return Ok(false);
+            }
+
+            let json: serde_json::Value = serde_json::from_str(line)
+                .map_err(|e| format!("Invalid stream chunk from Ollama: {}", e))?;
+            if let Some(error) = json["error"].as_str() {
+                return Err(format!("Ollama API error: {}", error));
+            }
+            if let Some(delta) = json["message"]["content"].as_str() {
+                if !delta.is_empty() {
+                    content.push_str(delta);
+                    on_delta(delta);
+                }
+            }
+
+            Ok(json["done"].as_bool().unwrap_or(false))
+        })
+        .await?;
+
+        Ok(content)
+    }
+}
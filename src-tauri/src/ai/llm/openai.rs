@@ -0,0 +1,113 @@
+use super::{read_lines, sse_data, ChatMessage, LlmProvider};
+use crate::ai::http::shared_client;
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder};
+
+/// Chat models behind an OpenAI-compatible `/chat/completions` endpoint
+/// (OpenAI, llama.cpp server, vLLM, LM Studio, ...).
+pub struct OpenAiLlm {
+    client: Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+impl OpenAiLlm {
+    pub fn new(base_url: &str, api_key: Option<String>, model: &str) -> Self {
+        Self {
+            client: shared_client(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.filter(|k| !k.is_empty()),
+            model: model.to_string(),
+        }
+    }
+
+    fn request(&self, messages: &[ChatMessage], stream: bool) -> RequestBuilder {
+        let body = serde_json::json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": stream
+        });
+
+        let builder = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(&body);
+        match &self.api_key {
+            Some(key) => builder.bearer_auth(key),
+            None => builder,
+        }
+    }
+}
+
+async fn check_status(resp: reqwest::Response) -> Result<reqwest::Response, String> {
+    if resp.status().is_success() {
+        return Ok(resp);
+    }
+    let status = resp.status();
+    let error_text = resp.text().await.unwrap_or_default();
+    Err(format!("OpenAI API error: {} {}", status, error_text).trim_end().to_string())
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiLlm {
+    fn model_id(&self) -> &str {
+        &self.model
+    }
+
+    async fn chat(&self, messages: &[ChatMessage]) -> Result<String, String> {
+        let resp = self
+            .request(messages, false)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to call OpenAI API: {}", e))?;
+        let resp = check_status(resp).await?;
+
+        let json: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+        json["choices"][0]["message"]["content"]
+            .as_str()
+            .map(|content| content.to_string())
+            .ok_or_else(|| "Invalid response format from OpenAI API".to_string())
+    }
+
+    /// Streams arrive as server-sent events, one `data: {...}` chunk per
+    /// delta and a final `data: [DONE]`.
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+    ) -> Result<String, String> {
+        let resp = self
+            .request(messages, true)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to call OpenAI API: {}", e))?;
+        let resp = check_status(resp).await?;
+
+        let mut content = String::new();
+        read_lines(resp, |line| {
+            let Some(data) = sse_data(line) else {
+                return Ok(false);
+            };
+            if data == "[DONE]" {
+                return Ok(true);
+            }
+
+            let json: serde_json::Value = serde_json::from_str(data)
+                .map_err(|e| format!("Invalid stream chunk from OpenAI API: {}", e))?;
+            if let Some(error) = json["error"]["message"].as_str() {
+                return Err(format!("OpenAI API error: {}", error));
+            }
+            if let Some(delta) = json["choices"][0]["delta"]["content"].as_str() {
+                if !delta.is_empty() {
+                    content.push_str(delta);
+                    on_delta(delta);
+                }
+            }
+            Ok(false)
+        })
+        .await?;
+
+        Ok(content)
+    }
+}
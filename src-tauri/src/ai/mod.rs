@@ -1,2 +1,4 @@
 pub mod embeddings;
+pub mod http;
 pub mod llm;
+pub mod rag;
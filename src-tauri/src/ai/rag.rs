@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+
+/// A retrieved passage given to the model as numbered context.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Source {
+    /// 1-based number the model cites it by, as in `[1]`.
+    pub number: usize,
+    pub node_id: String,
+    pub title: String,
+    /// Headings the chunk sits under, outermost first.
+    #[serde(default)]
+    pub section: Vec<String>,
+    /// Pages the chunk covers, for PDFs.
+    #[serde(default)]
+    pub page_start: Option<u32>,
+    #[serde(default)]
+    pub page_end: Option<u32>,
+    pub chunk: String,
+    pub distance: f32,
+}
+
+/// System prompt listing `sources` as `[n]` blocks and asking the model to
+/// cite them.
+pub fn system_prompt(sources: &[Source]) -> String {
+    let context = sources
+        .iter()
+        .map(|s| {
+            let section = if s.section.is_empty() {
+                String::new()
+            } else {
+                format!("\nSection: {}", s.section.join(" > "))
+            };
+            let pages = match (s.page_start, s.page_end) {
+                (Some(start), Some(end)) if end > start => format!("\nPages: {}-{}", start, end),
+                (Some(page), _) => format!("\nPage: {}", page),
+                _ => String::new(),
+            };
+            format!("[{}] Title: {}{}{}\n{}", s.number, s.title, section, pages, s.chunk.trim())
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n---\n\n");
+
+    format!(
+        "You are a helpful research assistant. Answer the user's question based ONLY on the following numbered context blocks:\n\n{}\n\n\
+         Cite the blocks that support each statement with their number in square brackets, like [1] or [2][3]. \
+         Only cite numbers listed above. If the answer is not in the context, say so.",
+        context
+    )
+}
+
+/// Citation numbers found in `text`, in order of first appearance.
+///
+/// Accepts `[1]`, `[1][2]` and `[1, 2]`; other bracketed text (including
+/// Markdown links) is ignored.
+pub fn parse_citations(text: &str) -> Vec<usize> {
+    let mut numbers: Vec<usize> = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('[') {
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find(']') else { break };
+        let inner = &rest[..end];
+
+        let parsed: Option<Vec<usize>> = inner
+            .split(',')
+            .map(|n| n.trim().parse::<usize>().ok())
+            .collect();
+        let is_link = rest[end + 1..].starts_with('(');
+        if let (Some(parsed), false) = (parsed, is_link) {
+            for n in parsed {
+                if !numbers.contains(&n) {
+                    numbers.push(n);
+                }
+            }
+            rest = &rest[end + 1..];
+        }
+    }
+
+    numbers
+}
+
+/// Sources cited in `answer`, in citation order. Numbers that don't match a
+/// source are dropped.
+pub fn cited_sources(answer: &str, sources: &[Source]) -> Vec<Source> {
+    parse_citations(answer)
+        .into_iter()
+        .filter_map(|n| sources.iter().find(|s| s.number == n).cloned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(number: usize) -> Source {
+        Source {
+            number,
+            node_id: format!("node-{}", number),
+            title: format!("Title {}", number),
+            section: Vec::new(),
+            page_start: None,
+            page_end: None,
+            chunk: String::new(),
+            distance: 0.0,
+        }
+    }
+
+    #[test]
+    fn parses_single_adjacent_and_listed_citations() {
+        assert_eq!(parse_citations("Rust is fast [1]."), vec![1]);
+        assert_eq!(parse_citations("Both agree [2][3]."), vec![2, 3]);
+        assert_eq!(parse_citations("See [4, 1] and [ 5 ,6 ]."), vec![4, 1, 5, 6]);
+    }
+
+    #[test]
+    fn keeps_first_appearance_order_without_duplicates() {
+        assert_eq!(parse_citations("[3] then [1], again [3] and [1, 2]"), vec![3, 1, 2]);
+    }
+
+    #[test]
+    fn ignores_links_and_other_brackets() {
+        assert_eq!(parse_citations("A [link](https://example.com) and [1](x)"), Vec::<usize>::new());
+        assert_eq!(parse_citations("[note] [] [1a] [2, b]"), Vec::<usize>::new());
+        assert_eq!(parse_citations("an [unclosed bracket and [7]"), vec![7]);
+        assert_eq!(parse_citations("no citations here"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn cited_sources_drop_unknown_numbers() {
+        let sources = vec![source(1), source(2)];
+        let cited = cited_sources("Per [2] and [9], also [1].", &sources);
+        let numbers: Vec<_> = cited.iter().map(|s| s.number).collect();
+        assert_eq!(numbers, vec![2, 1]);
+    }
+}
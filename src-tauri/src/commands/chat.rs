@@ -1,62 +1,237 @@
-use tauri::command;
+use tauri::{command, AppHandle, Emitter, Manager, State};
 use crate::db::DbState;
-use crate::ai::embeddings::{embed_text, EmbeddingProvider};
-use crate::ai::llm::{chat as llm_chat, ChatMessage};
-use rusqlite::params;
-use serde_json::json;
+use crate::ai::embeddings::{self, embed_query, BatchOptions};
+use crate::db::{chunks, sessions, vec};
+use crate::settings::{self, AppSettings};
+use crate::ai::llm::{self, ChatMessage, LlmProvider};
+use crate::ai::rag::{self, Source};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Mutex, PoisonError};
+use tokio::sync::watch;
+use uuid::Uuid;
 
+pub const CHAT_START_EVENT: &str = "chat-start";
+pub const CHAT_DELTA_EVENT: &str = "chat-delta";
+pub const CHAT_DONE_EVENT: &str = "chat-done";
+pub const CHAT_ERROR_EVENT: &str = "chat-error";
+
+/// One question to answer. With a `session_id` the history comes from,
+/// and the turn is saved to, that session.
 #[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ChatRequest {
     pub message: String,
-    pub history: Vec<ChatMessage>,
+    pub history: Option<Vec<ChatMessage>>,
+    pub session_id: Option<String>,
 }
 
-#[command]
-pub async fn chat(
-    state: tauri::State<'_, DbState>,
+/// Cancel signals of in-flight streamed generations, by request id.
+#[derive(Default)]
+pub struct ChatStreams(Mutex<HashMap<String, watch::Sender<bool>>>);
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatStartEvent {
+    pub request_id: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatDeltaEvent {
+    pub request_id: String,
+    pub delta: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatDoneEvent {
+    pub request_id: String,
+    pub content: String,
+    pub sources: Vec<Source>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatErrorEvent {
+    pub request_id: String,
+    pub error: String,
+    pub cancelled: bool,
+}
+
+/// An event of a streamed generation.
+enum StreamEvent {
+    Start(ChatStartEvent),
+    Delta(ChatDeltaEvent),
+    Done(ChatDoneEvent),
+    Error(ChatErrorEvent),
+}
+
+impl StreamEvent {
+    fn emit(self, app: &AppHandle) {
+        let _ = match self {
+            StreamEvent::Start(event) => app.emit(CHAT_START_EVENT, event),
+            StreamEvent::Delta(event) => app.emit(CHAT_DELTA_EVENT, event),
+            StreamEvent::Done(event) => app.emit(CHAT_DONE_EVENT, event),
+            StreamEvent::Error(event) => app.emit(CHAT_ERROR_EVENT, event),
+        };
+    }
+}
+
+/// Number of passages given to the model.
+const CONTEXT_BLOCKS: usize = 5;
+
+/// Answer with the sources it cited.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatResponse {
+    pub content: String,
+    pub sources: Vec<Source>,
+}
+
+/// Stored messages sent back to the model as history.
+const SESSION_HISTORY: usize = 10;
+
+/// History for this turn: the latest messages of a stored session, or
+/// the history sent by the caller for an unsaved chat.
+async fn load_history(
+    state: &DbState,
+    session_id: Option<String>,
+    history: Option<Vec<ChatMessage>>,
+) -> Result<Vec<ChatMessage>, String> {
+    let Some(session_id) = session_id else {
+        return Ok(history.unwrap_or_default());
+    };
+
+    state.run(move |conn| {
+        sessions::get(conn, &session_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Chat session {} not found", session_id))?;
+        let stored = sessions::messages(conn, &session_id).map_err(|e| e.to_string())?;
+
+        let start = stored.len().saturating_sub(SESSION_HISTORY);
+        Ok(stored[start..]
+            .iter()
+            .map(|m| ChatMessage { role: m.role.clone(), content: m.content.clone() })
+            .collect())
+    })
+    .await
+}
+
+/// Record the question and its answer in their session, if the chat is
+/// stored. Both are written together, so a failed or cancelled generation
+/// leaves no unanswered question behind.
+async fn finish_turn(
+    state: &DbState,
+    session_id: Option<String>,
+    message: String,
+    model: &str,
+    response: &ChatResponse,
+) -> Result<(), String> {
+    let Some(session_id) = session_id else {
+        return Ok(());
+    };
+    let model = model.to_string();
+    let response = response.clone();
+
+    state.run(move |conn| {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let session = sessions::get(&tx, &session_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Chat session {} not found", session_id))?;
+        let stored = sessions::messages(&tx, &session_id).map_err(|e| e.to_string())?;
+
+        // Name new sessions after their first question
+        if stored.is_empty() && session.title == sessions::DEFAULT_TITLE {
+            sessions::rename(&tx, &session_id, &sessions::title_from(&message)).map_err(|e| e.to_string())?;
+        }
+        sessions::add_message(&tx, &session_id, "user", &message, None, &[]).map_err(|e| e.to_string())?;
+        sessions::add_message(&tx, &session_id, "assistant", &response.content, Some(&model), &response.sources)
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    })
+    .await
+}
+
+/// Passages nearest to `embedding` in the index of `model_id`, numbered
+/// for citation.
+fn retrieve_sources(
+    conn: &Connection,
+    model_id: &str,
+    dimension: usize,
+    embedding: &[f32],
+) -> Result<Vec<Source>, String> {
+    // Over-fetch since vectors predating the chunk table fall back to
+    // whole documents, which can repeat
+    let index = vec::require_index(conn, model_id, dimension)?;
+    let hits = vec::search(conn, &index, embedding, CONTEXT_BLOCKS * 2).map_err(|e| e.to_string())?;
+
+    let mut title_stmt = conn.prepare("SELECT title FROM nodes WHERE id = ?1 AND deleted_at IS NULL")
+        .map_err(|e| e.to_string())?;
+    let mut content_stmt = conn.prepare("SELECT content FROM nodes_fts WHERE id = ?1")
+        .map_err(|e| e.to_string())?;
+
+    let mut sources: Vec<Source> = Vec::new();
+    for hit in hits {
+        if sources.len() == CONTEXT_BLOCKS {
+            break;
+        }
+        let Ok(title) = title_stmt.query_row(params![hit.node_id], |row| row.get::<_, String>(0)) else {
+            continue;
+        };
+        let (chunk, section, pages) = match chunks::get(conn, hit.rowid, &hit.node_id).map_err(|e| e.to_string())? {
+            Some(chunk) => (chunk.text, chunk.heading_path, (chunk.page_start, chunk.page_end)),
+            None => match content_stmt.query_row(params![hit.node_id], |row| row.get::<_, String>(0)) {
+                Ok(content) => (content, Vec::new(), (None, None)),
+                Err(_) => continue,
+            },
+        };
+        if sources.iter().any(|s| s.node_id == hit.node_id && s.chunk == chunk) {
+            continue;
+        }
+        sources.push(Source {
+            number: sources.len() + 1,
+            node_id: hit.node_id,
+            title,
+            section,
+            page_start: pages.0,
+            page_end: pages.1,
+            chunk,
+            distance: hit.distance,
+        });
+    }
+
+    Ok(sources)
+}
+
+/// Retrieve numbered context for `message` and assemble the full prompt.
+async fn build_messages(
+    state: &DbState,
+    settings: &AppSettings,
     message: String,
     history: Vec<ChatMessage>,
-) -> Result<String, String> {
+) -> Result<(Vec<ChatMessage>, Vec<Source>), String> {
     // 1. Retrieve Context via Vector Search
-    let provider = EmbeddingProvider::Ollama { model: "nomic-embed-text".to_string() };
-    let embedding = embed_text(&message, &provider).await?;
-    let embedding_json = serde_json::to_string(&embedding).map_err(|e| e.to_string())?;
-
-    let context = {
-        let conn = state.get_connection().map_err(|e| e.to_string())?;
-        
-        // Search for top 3 relevant chunks
-        let mut stmt = conn.prepare(
-            "SELECT n.title, n.content_path, fts.content, v.distance
-             FROM nodes_vec v
-             JOIN nodes n ON v.node_id = n.id
-             JOIN nodes_fts fts ON n.id = fts.id
-             WHERE v.embedding MATCH ?
-             ORDER BY v.distance
-             LIMIT 3"
-        ).map_err(|e| e.to_string())?;
-
-        let context_rows = stmt.query_map(params![embedding_json], |row| {
-            let title: String = row.get(0)?;
-            let content: String = row.get(2)?; 
-            Ok(format!("Title: {}\nContent: {}\n", title, content))
-        }).map_err(|e| e.to_string())?;
-
-        let mut context_parts = Vec::new();
-        for row in context_rows {
-            if let Ok(c) = row {
-                context_parts.push(c);
-            }
-        }
+    let embedder = embeddings::from_settings(settings)?;
+    let embedding = embed_query(embedder.as_ref(), &message, &BatchOptions::default()).await?;
+
+    let model_id = embedder.model_id().to_string();
+    let dimension = embedder.dimension();
+    let sources = state
+        .run(move |conn| retrieve_sources(conn, &model_id, dimension, &embedding))
+        .await?;
 
-        context_parts.join("\n---\n")
-    }; // conn is dropped here
+    let final_messages = prompt_messages(&sources, history, message);
+    Ok((final_messages, sources))
+}
 
+/// The conversation sent to the model: a system prompt with the numbered
+/// `sources`, the earlier turns, then `message`.
+fn prompt_messages(sources: &[Source], history: Vec<ChatMessage>, message: String) -> Vec<ChatMessage> {
     // 2. Construct System Prompt
-    let system_prompt = format!(
-        "You are a helpful research assistant. Answer the user's question based ONLY on the following context:\n\n{}\n\nIf the answer is not in the context, say so.",
-        context
-    );
+    let system_prompt = rag::system_prompt(sources);
 
     // 3. Prepare Messages
     let mut final_messages = Vec::new();
@@ -69,7 +244,327 @@ pub async fn chat(
         role: "user".to_string(),
         content: message,
     });
+    final_messages
+}
+
+/// Answer `request` with `provider` and record the turn in its session.
+/// With `on_delta` the answer is streamed, each fragment passed to it as
+/// it is generated. If `stop` completes first, generation is abandoned
+/// with nothing saved and `None` is returned.
+async fn answer(
+    state: &DbState,
+    settings: &AppSettings,
+    provider: &dyn LlmProvider,
+    request: ChatRequest,
+    on_delta: Option<&mut (dyn for<'d> FnMut(&'d str) + Send)>,
+    stop: impl Future<Output = ()>,
+) -> Result<Option<ChatResponse>, String> {
+    let ChatRequest { message, history, session_id } = request;
+    let history = load_history(state, session_id.clone(), history).await?;
+
+    let generate = async {
+        let (final_messages, sources) = build_messages(state, settings, message.clone(), history).await?;
+
+        // 4. Call LLM
+        let content = match on_delta {
+            Some(on_delta) => provider.chat_stream(&final_messages, on_delta).await?,
+            None => provider.chat(&final_messages).await?,
+        };
+
+        // 5. Link citations back to their nodes
+        let sources = rag::cited_sources(&content, &sources);
+        Ok::<_, String>(ChatResponse { content, sources })
+    };
+    let response = tokio::select! {
+        biased;
+        _ = stop => return Ok(None),
+        response = generate => response?,
+    };
+
+    // A complete answer is kept even if a stop arrives while saving it
+    finish_turn(state, session_id, message, provider.model_id(), &response).await?;
+    Ok(Some(response))
+}
+
+#[command]
+pub async fn chat(
+    state: State<'_, DbState>,
+    message: String,
+    history: Option<Vec<ChatMessage>>,
+    session_id: Option<String>,
+) -> Result<ChatResponse, String> {
+    let settings = state.run(|conn| settings::load(conn).map_err(|e| e.to_string())).await?;
+
+    let provider = llm::from_settings(&settings)?;
+    let request = ChatRequest { message, history, session_id };
+    answer(&state, &settings, provider.as_ref(), request, None, std::future::pending())
+        .await?
+        .ok_or_else(|| "Generation cancelled".to_string())
+}
+
+/// Start a streamed chat answer and return its request id right away.
+///
+/// The answer arrives as `chat-start`, `chat-delta`* and then either
+/// `chat-done` or `chat-error` events, all carrying the request id. The
+/// frontend may pass its own `request_id` to subscribe before invoking.
+/// With a `session_id` the history comes from, and the turn is saved to,
+/// that session.
+#[command]
+pub async fn chat_stream(
+    app: AppHandle,
+    streams: State<'_, ChatStreams>,
+    message: String,
+    history: Option<Vec<ChatMessage>>,
+    session_id: Option<String>,
+    request_id: Option<String>,
+) -> Result<String, String> {
+    let request_id = request_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    // Keep the map locked while spawning so the task cannot finish (and
+    // unregister itself) before it has been registered
+    let mut running = streams.0.lock().map_err(|e| e.to_string())?;
+    if running.contains_key(&request_id) {
+        return Err(format!("Chat request {} is already running", request_id));
+    }
+
+    let (cancel_sender, cancel) = watch::channel(false);
+    let request = ChatRequest { message, history, session_id };
+    tauri::async_runtime::spawn(run_chat_stream(app, request_id.clone(), request, cancel));
+    running.insert(request_id.clone(), cancel_sender);
+
+    Ok(request_id)
+}
+
+async fn run_chat_stream(app: AppHandle, request_id: String, request: ChatRequest, cancel: watch::Receiver<bool>) {
+    let emit = |event: StreamEvent| event.emit(&app);
+    let state = app.state::<DbState>();
+    let outcome = stream_answer(&state, &request_id, request, cancel, &emit).await;
+
+    // Unregister before the final event, so a cancel arriving after it
+    // reports that the generation had already finished
+    app.state::<ChatStreams>()
+        .0
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .remove(&request_id);
+    emit(outcome);
+}
+
+/// Generate a streamed answer, passing the start event and each delta to
+/// `emit`, and return the final event: done, a failure, or a cancelled
+/// error once `cancel` is set.
+async fn stream_answer(
+    state: &DbState,
+    request_id: &str,
+    request: ChatRequest,
+    cancel: watch::Receiver<bool>,
+    emit: &(dyn Fn(StreamEvent) + Sync),
+) -> StreamEvent {
+    emit(StreamEvent::Start(ChatStartEvent { request_id: request_id.to_string() }));
+
+    let result = async {
+        let settings = state.run(|conn| settings::load(conn).map_err(|e| e.to_string())).await?;
+        let provider = llm::from_settings(&settings)?;
+
+        let mut on_delta = |delta: &str| {
+            emit(StreamEvent::Delta(ChatDeltaEvent {
+                request_id: request_id.to_string(),
+                delta: delta.to_string(),
+            }))
+        };
+        answer(state, &settings, provider.as_ref(), request, Some(&mut on_delta), cancelled(cancel)).await
+    }
+    .await;
+
+    let request_id = request_id.to_string();
+    match result {
+        Ok(Some(ChatResponse { content, sources })) => StreamEvent::Done(ChatDoneEvent { request_id, content, sources }),
+        Ok(None) => StreamEvent::Error(ChatErrorEvent {
+            request_id,
+            error: "Generation cancelled".to_string(),
+            cancelled: true,
+        }),
+        Err(error) => StreamEvent::Error(ChatErrorEvent { request_id, error, cancelled: false }),
+    }
+}
+
+/// Completes once `cancel` is set.
+async fn cancelled(mut cancel: watch::Receiver<bool>) {
+    // The sender is only dropped unset when nobody can cancel any more
+    if cancel.wait_for(|cancelled| *cancelled).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Cancel a streamed generation, which then ends with a cancelled
+/// `chat-error`. Returns false if it already finished.
+#[command]
+pub fn cancel_chat(streams: State<'_, ChatStreams>, request_id: String) -> Result<bool, String> {
+    let sender = streams.0.lock().map_err(|e| e.to_string())?.remove(&request_id);
+
+    match sender {
+        Some(sender) => {
+            sender.send_replace(true);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::embeddings::{Embedder, HashEmbedder};
+    use crate::ai::llm::{LlmProvider, MockLlm};
+    use crate::db::testing::{add_document, open_db, open_state, TestState};
+    use std::future::pending;
+
+    #[tokio::test]
+    async fn answers_from_retrieved_sources_and_links_citations() {
+        let mut conn = open_db();
+        let embedder = HashEmbedder::default();
+        let rust = add_document(
+            &mut conn,
+            &embedder,
+            "Rust ownership",
+            "Every Rust value has a single owner; the value is dropped when its owner goes out of scope.",
+        );
+        add_document(
+            &mut conn,
+            &embedder,
+            "Sourdough",
+            "Sourdough bread rises with a starter of flour and water.",
+        );
+
+        let question = "When is a Rust value dropped?";
+        let embedding = embedder.embed_sync(question);
+        let sources = retrieve_sources(&conn, embedder.model_id(), embedder.dimension(), &embedding).unwrap();
+        assert_eq!(sources[0].node_id, rust);
+        assert_eq!(sources[0].number, 1);
+
+        let history = vec![ChatMessage { role: "user".to_string(), content: "Hi".to_string() }];
+        let messages = prompt_messages(&sources, history, question.to_string());
+        let llm = MockLlm::with_reply("When its owner goes out of scope [1]. See also [9].");
+        let answer = llm.chat(&messages).await.unwrap();
+
+        // The model saw the numbered passage, the history and the question
+        let calls = llm.calls();
+        assert_eq!(calls.len(), 1);
+        let sent = &calls[0];
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[0].role, "system");
+        assert!(sent[0].content.contains("[1] Title: Rust ownership"));
+        assert!(sent[0].content.contains("dropped when its owner goes out of scope"));
+        assert_eq!(sent[1].content, "Hi");
+        assert_eq!(sent[2].content, question);
+
+        // Only citations of real sources come back
+        let cited = rag::cited_sources(&answer, &sources);
+        assert_eq!(cited.len(), 1);
+        assert_eq!(cited[0].node_id, rust);
+    }
+
+    fn ask(question: &str, session_id: &str) -> ChatRequest {
+        ChatRequest {
+            message: question.to_string(),
+            history: None,
+            session_id: Some(session_id.to_string()),
+        }
+    }
+
+    /// Run `stream_answer` and collect every event it emits, the final
+    /// one last.
+    async fn collect_stream(state: &DbState, request: ChatRequest, cancel: watch::Receiver<bool>) -> Vec<StreamEvent> {
+        let events = Mutex::new(Vec::new());
+        let outcome = stream_answer(state, "request-1", request, cancel, &|event| events.lock().unwrap().push(event)).await;
+        let mut events = events.into_inner().unwrap();
+        events.push(outcome);
+        events
+    }
+
+    /// A stored session in `state`, with one indexed document to retrieve.
+    fn session_with_library(state: &TestState) -> String {
+        let mut conn = state.get_connection().unwrap();
+        add_document(
+            &mut conn,
+            &HashEmbedder::default(),
+            "Rust ownership",
+            "Every Rust value has a single owner; the value is dropped when its owner goes out of scope.",
+        );
+        sessions::create(&conn, sessions::DEFAULT_TITLE).unwrap().id
+    }
+
+    #[tokio::test]
+    async fn failed_answers_leave_the_session_untouched() {
+        let state = open_state();
+        let session_id = session_with_library(&state);
+        let settings = state.run(|conn| settings::load(conn).map_err(|e| e.to_string())).await.unwrap();
+        let question = "When is a Rust value dropped?";
+
+        let down = MockLlm::failing("connection refused");
+        let result = answer(&state, &settings, &down, ask(question, &session_id), None, pending()).await;
+        assert_eq!(result.err().as_deref(), Some("connection refused"));
+
+        let conn = state.get_connection().unwrap();
+        assert!(sessions::messages(&conn, &session_id).unwrap().is_empty());
+        assert_eq!(sessions::get(&conn, &session_id).unwrap().unwrap().title, sessions::DEFAULT_TITLE);
+        drop(conn);
+
+        // Asking again sends no trace of the failed turn and stores the pair
+        let llm = MockLlm::default();
+        answer(&state, &settings, &llm, ask(question, &session_id), None, pending()).await.unwrap();
+        assert_eq!(llm.calls()[0].len(), 2);
+
+        let conn = state.get_connection().unwrap();
+        let stored = sessions::messages(&conn, &session_id).unwrap();
+        let roles: Vec<&str> = stored.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["user", "assistant"]);
+        assert_eq!(stored[0].content, question);
+        assert_eq!(sessions::get(&conn, &session_id).unwrap().unwrap().title, question);
+    }
+
+    #[tokio::test]
+    async fn streamed_answers_arrive_in_pieces_and_are_saved() {
+        let state = open_state();
+        let session_id = session_with_library(&state);
+        let (_cancel_sender, cancel) = watch::channel(false);
+
+        let events = collect_stream(&state, ask("When is a Rust value dropped?", &session_id), cancel).await;
+        assert!(matches!(&events[0], StreamEvent::Start(e) if e.request_id == "request-1"));
+
+        let deltas: Vec<&str> = events
+            .iter()
+            .filter_map(|e| match e {
+                StreamEvent::Delta(delta) => Some(delta.delta.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert!(deltas.len() > 1);
+        let Some(StreamEvent::Done(done)) = events.last() else {
+            panic!("the stream should end with chat-done");
+        };
+        assert_eq!(done.content, "Mock answer to: When is a Rust value dropped?");
+        assert_eq!(deltas.concat(), done.content);
+        assert_eq!(events.len(), deltas.len() + 2);
+
+        let conn = state.get_connection().unwrap();
+        let stored = sessions::messages(&conn, &session_id).unwrap();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[1].content, done.content);
+    }
+
+    #[tokio::test]
+    async fn cancelled_streams_end_once_and_save_nothing() {
+        let state = open_state();
+        let session_id = session_with_library(&state);
+        let (cancel_sender, cancel) = watch::channel(false);
+        cancel_sender.send_replace(true);
+
+        let events = collect_stream(&state, ask("When is a Rust value dropped?", &session_id), cancel).await;
+        assert_eq!(events.len(), 2);
+        assert!(matches!(&events[0], StreamEvent::Start(_)));
+        assert!(matches!(&events[1], StreamEvent::Error(e) if e.cancelled));
 
-    // 4. Call LLM
-    llm_chat(final_messages, Some("ministral-3:8b".to_string())).await
+        let conn = state.get_connection().unwrap();
+        assert!(sessions::messages(&conn, &session_id).unwrap().is_empty());
+    }
 }
@@ -0,0 +1,604 @@
+use tauri::{command, AppHandle, Emitter, Manager, State};
+use crate::ai::embeddings;
+use crate::commands::ingest::ingest_fetched;
+use crate::db::DbState;
+use crate::scraper::fetcher::{self, Body, FetchOptions, Fetched};
+use crate::scraper::{canonical, files, parser};
+use crate::settings::{self, AppSettings};
+use async_trait::async_trait;
+use globset::GlobSet;
+use reqwest::Url;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+use tokio::sync::watch;
+use uuid::Uuid;
+
+pub const CRAWL_PROGRESS_EVENT: &str = "crawl-progress";
+
+pub const DEFAULT_MAX_DEPTH: usize = 2;
+pub const DEFAULT_MAX_PAGES: usize = 50;
+/// Upper bound for `CrawlOptions::max_pages`.
+const PAGE_LIMIT: usize = 1000;
+
+/// Links to these are never followed; they can't be ingested.
+const SKIP_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "svg", "webp", "ico", "bmp", "css", "js", "mjs", "map", "zip",
+    "gz", "tgz", "tar", "rar", "7z", "exe", "dmg", "msi", "deb", "rpm", "mp3", "mp4", "webm",
+    "mov", "avi", "woff", "woff2", "ttf", "otf", "eot",
+];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CrawlOptions {
+    /// Link hops from the seed; 0 ingests only the seed.
+    pub max_depth: usize,
+    /// Pages fetched at most, including ones that fail.
+    pub max_pages: usize,
+    /// Globs over full URLs, like `https://docs.example.com/guide/*`.
+    /// Links matching one are followed; without any, links on the seed's
+    /// host are.
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl Default for CrawlOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_pages: DEFAULT_MAX_PAGES,
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Control {
+    Run,
+    Pause,
+    Cancel,
+}
+
+/// Control channels of running crawls, by job id.
+#[derive(Default)]
+pub struct CrawlJobs(Mutex<HashMap<String, watch::Sender<Control>>>);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrawlProgress {
+    pub job_id: String,
+    /// "running", "paused", "done", "cancelled" or "error"
+    pub status: String,
+    pub seed_url: String,
+    /// Pages fetched so far, ingested or failed
+    pub processed: usize,
+    pub ingested: usize,
+    /// Pages that could not be fetched or ingested, listed in `failed`
+    pub failed_count: usize,
+    /// Pages found but not fetched yet
+    pub queued: usize,
+    pub current_url: Option<String>,
+    pub failed: Vec<String>,
+    pub message: Option<String>,
+}
+
+/// Where a crawl gets its pages from.
+#[async_trait]
+trait PageSource: Send + Sync {
+    async fn fetch(&self, url: &Url) -> Result<Fetched, String>;
+}
+
+/// Pages fetched from the web, obeying robots.txt and rate limits.
+struct Web;
+
+#[async_trait]
+impl PageSource for Web {
+    async fn fetch(&self, url: &Url) -> Result<Fetched, String> {
+        fetcher::fetch(url.as_str(), &FetchOptions::default()).await.map_err(|e| e.to_string())
+    }
+}
+
+/// Which links a crawl follows.
+struct LinkFilter {
+    host: Option<String>,
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl LinkFilter {
+    fn allows(&self, url: &Url) -> bool {
+        let extension = url.path().rsplit_once('.').map(|(_, ext)| ext.to_lowercase());
+        if extension.is_some_and(|ext| SKIP_EXTENSIONS.contains(&ext.as_str())) {
+            return false;
+        }
+        if self.exclude.as_ref().is_some_and(|set| set.is_match(url.as_str())) {
+            return false;
+        }
+        match &self.include {
+            Some(set) => set.is_match(url.as_str()),
+            None => url.host_str().map(str::to_string) == self.host,
+        }
+    }
+}
+
+/// Crawl a site from `url`, ingesting every page as a source node and
+/// linking pages with `links_to` edges.
+///
+/// Returns a job id immediately; progress is reported through
+/// `crawl-progress` events. Pages are fetched one at a time, obeying
+/// robots.txt and the per-host rate limits.
+#[command]
+pub async fn start_crawl(
+    app: AppHandle,
+    jobs: State<'_, CrawlJobs>,
+    url: String,
+    options: Option<CrawlOptions>,
+) -> Result<String, String> {
+    let seed = Url::parse(url.trim()).map_err(|_| format!("Invalid URL: {}", url))?;
+    if !matches!(seed.scheme(), "http" | "https") {
+        return Err(format!("Invalid URL: {}", url));
+    }
+    let mut options = options.unwrap_or_default();
+    options.max_pages = options.max_pages.clamp(1, PAGE_LIMIT);
+    let filter = LinkFilter {
+        host: seed.host_str().map(str::to_string),
+        include: files::glob_set(&options.include)?,
+        exclude: files::glob_set(&options.exclude)?,
+    };
+
+    let settings = app
+        .state::<DbState>()
+        .run(|conn| settings::load(conn).map_err(|e| e.to_string()))
+        .await?;
+    // Fail now rather than on every page
+    embeddings::from_settings(&settings)?;
+
+    let job_id = Uuid::new_v4().to_string();
+    let (sender, control) = watch::channel(Control::Run);
+
+    // Keep the map locked while spawning so the job cannot finish (and
+    // unregister itself) before it has been registered
+    let mut running = jobs.0.lock().map_err(|e| e.to_string())?;
+    running.insert(job_id.clone(), sender);
+    let job = job_id.clone();
+    tauri::async_runtime::spawn(async move {
+        run_crawl(&app, &job, seed, &options, &filter, &settings, control).await;
+        if let Ok(mut running) = app.state::<CrawlJobs>().0.lock() {
+            running.remove(&job);
+        }
+    });
+
+    Ok(job_id)
+}
+
+/// Pause a crawl after the page it is on.
+#[command]
+pub fn pause_crawl(jobs: State<'_, CrawlJobs>, job_id: String) -> Result<(), String> {
+    send_control(&jobs, &job_id, Control::Pause)
+}
+
+#[command]
+pub fn resume_crawl(jobs: State<'_, CrawlJobs>, job_id: String) -> Result<(), String> {
+    send_control(&jobs, &job_id, Control::Run)
+}
+
+/// Stop a crawl after the page it is on. Pages ingested so far are kept.
+#[command]
+pub fn cancel_crawl(jobs: State<'_, CrawlJobs>, job_id: String) -> Result<(), String> {
+    send_control(&jobs, &job_id, Control::Cancel)
+}
+
+fn send_control(jobs: &CrawlJobs, job_id: &str, control: Control) -> Result<(), String> {
+    let running = jobs.0.lock().map_err(|e| e.to_string())?;
+    let sender = running
+        .get(job_id)
+        .ok_or_else(|| format!("Crawl {} is not running", job_id))?;
+    sender.send_replace(control);
+    Ok(())
+}
+
+async fn run_crawl(
+    app: &AppHandle,
+    job_id: &str,
+    seed: Url,
+    options: &CrawlOptions,
+    filter: &LinkFilter,
+    settings: &AppSettings,
+    mut control: watch::Receiver<Control>,
+) {
+    let mut progress = CrawlProgress {
+        job_id: job_id.to_string(),
+        status: "running".to_string(),
+        seed_url: seed.to_string(),
+        processed: 0,
+        ingested: 0,
+        failed_count: 0,
+        queued: 1,
+        current_url: None,
+        failed: Vec::new(),
+        message: None,
+    };
+    let emit = |progress: &CrawlProgress| {
+        let _ = app.emit(CRAWL_PROGRESS_EVENT, progress.clone());
+    };
+    emit(&progress);
+
+    let crawler = Crawler {
+        state: &app.state::<DbState>(),
+        pages: &Web,
+        options,
+        filter,
+        settings,
+        emit: &emit,
+    };
+    let result = crawler.crawl(seed, &mut control, &mut progress).await;
+    progress.current_url = None;
+    match result {
+        Ok(true) => progress.status = "done".to_string(),
+        Ok(false) => progress.status = "cancelled".to_string(),
+        Err(e) => {
+            progress.status = "error".to_string();
+            progress.message = Some(e);
+        }
+    }
+    emit(&progress);
+}
+
+/// One crawl's settings and where its pages and progress go.
+struct Crawler<'a> {
+    state: &'a DbState,
+    pages: &'a dyn PageSource,
+    options: &'a CrawlOptions,
+    filter: &'a LinkFilter,
+    settings: &'a AppSettings,
+    emit: &'a (dyn Fn(&CrawlProgress) + Sync),
+}
+
+impl Crawler<'_> {
+    /// Breadth-first crawl. Returns false if it was cancelled.
+    async fn crawl(
+        &self,
+        seed: Url,
+        control: &mut watch::Receiver<Control>,
+        progress: &mut CrawlProgress,
+    ) -> Result<bool, String> {
+        // URLs are keyed by their canonical form, so links differing only in
+        // fragment, tracking parameters or trailing slash are one page
+        let mut seen = HashSet::from([page_key(&seed)]);
+        let mut queue = VecDeque::from([(seed, 0)]);
+        // Node of each ingested page, by requested and final URL
+        let mut nodes: HashMap<String, String> = HashMap::new();
+        // Ingested pages linking to each URL not ingested yet
+        let mut pending: HashMap<String, Vec<String>> = HashMap::new();
+
+        while progress.processed < self.options.max_pages {
+            let Some((url, depth)) = queue.pop_front() else {
+                break;
+            };
+            if !self.wait_while_paused(control, progress).await {
+                progress.queued = queue.len() + 1;
+                return Ok(false);
+            }
+            progress.current_url = Some(url.to_string());
+            progress.queued = queue.len();
+            (self.emit)(progress);
+
+            let fetched = self.pages.fetch(&url).await;
+            progress.processed += 1;
+            let fetched = match fetched {
+                Ok(fetched) => fetched,
+                Err(e) => {
+                    fail(progress, &url, e);
+                    continue;
+                }
+            };
+
+            let final_key = page_key(&fetched.final_url);
+            seen.insert(final_key.clone());
+            let links = match &fetched.body {
+                Body::Html(html) => parser::extract_links(html, &fetched.final_url),
+                _ => Vec::new(),
+            };
+
+            let node_id = match ingest_fetched(self.state, self.settings, url.as_str(), fetched).await {
+                Ok(node) => node.id,
+                Err(e) => {
+                    fail(progress, &url, e);
+                    continue;
+                }
+            };
+            progress.ingested += 1;
+
+            // Pages ingested earlier that link here, then pages this one links to
+            let mut edges: Vec<(String, String)> = Vec::new();
+            for key in [page_key(&url), final_key] {
+                for source in pending.remove(&key).unwrap_or_default() {
+                    edges.push((source, node_id.clone()));
+                }
+                nodes.insert(key, node_id.clone());
+            }
+            for link in links {
+                let key = page_key(&link);
+                if let Some(target) = nodes.get(&key) {
+                    edges.push((node_id.clone(), target.clone()));
+                } else if self.filter.allows(&link) {
+                    pending.entry(key.clone()).or_default().push(node_id.clone());
+                    if depth < self.options.max_depth && seen.insert(key) {
+                        queue.push_back((link, depth + 1));
+                    }
+                }
+            }
+            edges.retain(|(source, target)| source != target);
+            if !edges.is_empty() {
+                // The page is ingested either way; only its links are lost
+                let linked = self.state.run(move |conn| link_pages(conn, &edges).map_err(|e| e.to_string())).await;
+                if let Err(e) = linked {
+                    progress.message = Some(format!("Links of {}: {}", url, e));
+                }
+            }
+        }
+
+        progress.queued = queue.len();
+        Ok(true)
+    }
+
+    /// Wait while the crawl is paused. Returns false once it is cancelled.
+    async fn wait_while_paused(&self, control: &mut watch::Receiver<Control>, progress: &mut CrawlProgress) -> bool {
+        loop {
+            let current = *control.borrow_and_update();
+            match current {
+                Control::Run => {
+                    if progress.status == "paused" {
+                        progress.status = "running".to_string();
+                        (self.emit)(progress);
+                    }
+                    return true;
+                }
+                Control::Cancel => return false,
+                Control::Pause => {
+                    if progress.status != "paused" {
+                        progress.status = "paused".to_string();
+                        progress.current_url = None;
+                        (self.emit)(progress);
+                    }
+                    // The sender lives until the job ends, so this only fails then
+                    if control.changed().await.is_err() {
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn page_key(url: &Url) -> String {
+    canonical::normalize(url).to_string()
+}
+
+fn fail(progress: &mut CrawlProgress, url: &Url, error: String) {
+    progress.failed_count += 1;
+    progress.failed.push(url.to_string());
+    progress.message = Some(format!("{}: {}", url, error));
+}
+
+/// Add `links_to` edges between crawled pages, skipping existing ones.
+fn link_pages(conn: &mut rusqlite::Connection, edges: &[(String, String)]) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    let now = chrono::Utc::now().to_rfc3339();
+    for (source, target) in edges {
+        tx.execute(
+            "INSERT INTO edges (id, source, target, label, created_at)
+             SELECT ?1, ?2, ?3, 'links_to', ?4
+             WHERE NOT EXISTS (SELECT 1 FROM edges WHERE source = ?2 AND target = ?3 AND label = 'links_to')",
+            params![Uuid::new_v4().to_string(), source, target, now],
+        )?;
+    }
+    tx.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing::open_state;
+    use crate::scraper::fetcher::Validators;
+    use std::time::Duration;
+
+    const SITE: &str = "https://site.test";
+
+    /// Pages served from memory, by path: where a request for it ends up
+    /// and the paths it links to. Records every fetch.
+    #[derive(Default)]
+    struct StubSite {
+        pages: HashMap<&'static str, (&'static str, Vec<&'static str>)>,
+        fetched: Mutex<Vec<String>>,
+    }
+
+    impl StubSite {
+        fn page(mut self, path: &'static str, links: &[&'static str]) -> Self {
+            self.pages.insert(path, (path, links.to_vec()));
+            self
+        }
+
+        fn redirect(mut self, from: &'static str, to: &'static str) -> Self {
+            let links = self.pages[to].1.clone();
+            self.pages.insert(from, (to, links));
+            self
+        }
+
+        fn fetched(&self) -> Vec<String> {
+            self.fetched.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl PageSource for StubSite {
+        async fn fetch(&self, url: &Url) -> Result<Fetched, String> {
+            self.fetched.lock().unwrap().push(url.path().to_string());
+            let (target, links) = self.pages.get(url.path()).ok_or("404 Not Found")?;
+            let links: String = links.iter().map(|l| format!("See <a href=\"{0}\">{0}</a>. ", l)).collect();
+            let html = format!(
+                "<html><head><title>Page {0}</title></head><body><article><h1>Page {0}</h1>\
+                 <p>Notes kept at {0} on the stub site, long enough to be its main text.</p><p>{1}</p>\
+                 </article></body></html>",
+                target, links
+            );
+            Ok(Fetched {
+                final_url: Url::parse(SITE).unwrap().join(target).unwrap(),
+                content_type: "text/html".to_string(),
+                validators: Validators::default(),
+                body: Body::Html(html),
+            })
+        }
+    }
+
+    fn new_progress() -> CrawlProgress {
+        CrawlProgress {
+            job_id: "job".to_string(),
+            status: "running".to_string(),
+            seed_url: SITE.to_string(),
+            processed: 0,
+            ingested: 0,
+            failed_count: 0,
+            queued: 1,
+            current_url: None,
+            failed: Vec::new(),
+            message: None,
+        }
+    }
+
+    /// Crawl `site` from `/a`, returning the outcome, the final progress
+    /// and every progress event.
+    async fn crawl_site(
+        state: &DbState,
+        site: &StubSite,
+        options: CrawlOptions,
+        mut control: watch::Receiver<Control>,
+    ) -> (Result<bool, String>, CrawlProgress, Vec<CrawlProgress>) {
+        let settings = state.run(|conn| settings::load(conn).map_err(|e| e.to_string())).await.unwrap();
+        let filter = LinkFilter { host: Some("site.test".to_string()), include: None, exclude: None };
+        let events = Mutex::new(Vec::new());
+        let emit = |progress: &CrawlProgress| events.lock().unwrap().push(progress.clone());
+        let crawler = Crawler { state, pages: site, options: &options, filter: &filter, settings: &settings, emit: &emit };
+
+        let mut progress = new_progress();
+        let result = crawler.crawl(Url::parse(SITE).unwrap().join("/a").unwrap(), &mut control, &mut progress).await;
+        (result, progress, events.into_inner().unwrap())
+    }
+
+    fn running() -> watch::Receiver<Control> {
+        watch::channel(Control::Run).1
+    }
+
+    /// `links_to` edges between stored pages, by title.
+    fn links(state: &DbState) -> Vec<(String, String)> {
+        let conn = state.get_connection().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT s.title, t.title FROM edges e
+                 JOIN nodes s ON s.id = e.source JOIN nodes t ON t.id = e.target
+                 WHERE e.label = 'links_to' ORDER BY s.title, t.title",
+            )
+            .unwrap();
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        rows.collect::<Result<_, _>>().unwrap()
+    }
+
+    fn link(from: &str, to: &str) -> (String, String) {
+        (format!("Page {}", from), format!("Page {}", to))
+    }
+
+    #[tokio::test]
+    async fn crawls_stop_at_the_depth_and_page_limits() {
+        let site = StubSite::default()
+            .page("/a", &["/b"])
+            .page("/b", &["/c"])
+            .page("/c", &["/d"])
+            .page("/d", &[]);
+
+        let state = open_state();
+        let options = CrawlOptions { max_depth: 2, ..CrawlOptions::default() };
+        let (result, progress, _) = crawl_site(&state, &site, options, running()).await;
+        assert_eq!(result, Ok(true));
+        assert_eq!(site.fetched(), ["/a", "/b", "/c"]);
+        assert_eq!(progress.ingested, 3);
+
+        let site = StubSite { fetched: Mutex::default(), ..site };
+        let state = open_state();
+        let options = CrawlOptions { max_depth: 10, max_pages: 2, ..CrawlOptions::default() };
+        let (_, progress, _) = crawl_site(&state, &site, options, running()).await;
+        assert_eq!(site.fetched(), ["/a", "/b"]);
+        assert_eq!(progress.processed, 2);
+        assert_eq!(progress.queued, 1);
+    }
+
+    #[tokio::test]
+    async fn failures_are_counted_apart_from_ingested_pages() {
+        let site = StubSite::default().page("/a", &["/b", "/missing"]).page("/b", &[]);
+
+        let state = open_state();
+        let (_, progress, _) = crawl_site(&state, &site, CrawlOptions::default(), running()).await;
+        assert_eq!(progress.processed, 3);
+        assert_eq!(progress.ingested, 2);
+        assert_eq!(progress.failed_count, 1);
+        assert_eq!(progress.failed, [format!("{}/missing", SITE)]);
+    }
+
+    #[tokio::test]
+    async fn urls_of_one_page_are_fetched_once() {
+        let site = StubSite::default()
+            .page("/a", &["/b", "/b#usage", "/b/?utm_source=feed", "/old"])
+            .page("/b", &[])
+            .page("/c", &["/a"])
+            .redirect("/old", "/c");
+
+        let state = open_state();
+        let (_, progress, _) = crawl_site(&state, &site, CrawlOptions::default(), running()).await;
+        assert_eq!(site.fetched(), ["/a", "/b", "/old"]);
+        assert_eq!(progress.ingested, 3);
+    }
+
+    #[tokio::test]
+    async fn links_between_crawled_pages_become_edges() {
+        // /b links back to /a, which was stored first; /a's link to /b is
+        // only resolved once /b is stored, as is the link to /c through
+        // its old address
+        let site = StubSite::default()
+            .page("/a", &["/b", "/old"])
+            .page("/b", &["/a"])
+            .page("/c", &["/b"])
+            .redirect("/old", "/c");
+
+        let state = open_state();
+        crawl_site(&state, &site, CrawlOptions::default(), running()).await.0.unwrap();
+        assert_eq!(links(&state), [link("/a", "/b"), link("/a", "/c"), link("/b", "/a"), link("/c", "/b")]);
+    }
+
+    #[tokio::test]
+    async fn paused_crawls_wait_and_cancelled_ones_stop() {
+        let site = StubSite::default().page("/a", &["/b"]).page("/b", &[]);
+
+        let state = open_state();
+        let (sender, control) = watch::channel(Control::Pause);
+        let resume = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert!(site.fetched().is_empty());
+            sender.send_replace(Control::Run);
+        };
+        let ((result, progress, events), ()) =
+            tokio::join!(crawl_site(&state, &site, CrawlOptions::default(), control), resume);
+        assert_eq!(result, Ok(true));
+        assert_eq!(progress.ingested, 2);
+        let statuses: Vec<&str> = events.iter().map(|e| e.status.as_str()).collect();
+        assert_eq!(statuses[..2], ["paused", "running"]);
+
+        let site = StubSite { fetched: Mutex::default(), ..site };
+        let state = open_state();
+        let (_, control) = watch::channel(Control::Cancel);
+        let (result, progress, _) = crawl_site(&state, &site, CrawlOptions::default(), control).await;
+        assert_eq!(result, Ok(false));
+        assert!(site.fetched().is_empty());
+        assert_eq!(progress.queued, 1);
+    }
+}
@@ -63,7 +63,7 @@ pub fn get_graph_data(state: State<'_, DbState>) -> Result<GraphPayload, String>
     let conn = state.get_connection().map_err(|e| e.to_string())?;
     
     // Fetch Nodes
-    let mut stmt_nodes = conn.prepare("SELECT id, type, title, content_path, metadata, created_at, updated_at FROM nodes").map_err(|e| e.to_string())?;
+    let mut stmt_nodes = conn.prepare("SELECT id, node_type, title, content_path, metadata, created_at, updated_at, index_status FROM nodes WHERE deleted_at IS NULL").map_err(|e| e.to_string())?;
     let nodes_iter = stmt_nodes.query_map([], |row| {
         let metadata_str: Option<String> = row.get(4)?;
         let metadata = metadata_str.and_then(|s| serde_json::from_str(&s).ok());
@@ -76,13 +76,19 @@ pub fn get_graph_data(state: State<'_, DbState>) -> Result<GraphPayload, String>
             metadata,
             created_at: row.get(5)?,
             updated_at: row.get(6)?,
+            index_status: row.get(7)?,
         })
     }).map_err(|e| e.to_string())?;
 
     let nodes: Vec<Node> = nodes_iter.filter_map(Result::ok).collect();
 
-    // Fetch Edges
-    let mut stmt_edges = conn.prepare("SELECT id, source, target, label, created_at FROM edges").map_err(|e| e.to_string())?;
+    // Fetch Edges, except those touching trashed nodes
+    let mut stmt_edges = conn.prepare(
+        "SELECT e.id, e.source, e.target, e.label, e.created_at
+         FROM edges e
+         JOIN nodes s ON s.id = e.source AND s.deleted_at IS NULL
+         JOIN nodes t ON t.id = e.target AND t.deleted_at IS NULL"
+    ).map_err(|e| e.to_string())?;
     let edges_iter = stmt_edges.query_map([], |row| {
         Ok(Edge {
             id: row.get(0)?,
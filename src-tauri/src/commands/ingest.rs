@@ -1,131 +1,684 @@
-use tauri::{command, State};
-use crate::ai::embeddings::{embed_text, EmbeddingProvider};
-use crate::db::DbState;
-use crate::models::Node;
-use crate::fs_manager::Workspace;
-use reqwest::Client;
-use scraper::{Html, Selector};
+use tauri::{command, AppHandle, Emitter, State};
+use crate::ai::embeddings::{self, BatchOptions, Embedder};
+use crate::db::{vec, DbState};
+use crate::models::{index_status, Node};
+use crate::db::chunks::ChunkSpan;
+use crate::fs_manager::{PublishedArtifact, StagedArtifact, Workspace};
+use crate::settings::{self, AppSettings};
+use crate::scraper::fetcher::{self, Body, FetchOptions, Fetched};
+use crate::scraper::files::{self, FileDocument, FileKind};
+use crate::scraper::{canonical, chunker, parser};
+use reqwest::Url;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
-use serde_json::json;
+use serde_json::{json, Value};
 use chrono::Utc;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-// Simple chunking function (split by paragraphs or fixed size)
-fn chunk_text(text: &str, chunk_size: usize) -> Vec<String> {
-    let mut chunks = Vec::new();
-    let mut current_chunk = String::new();
+pub const FOLDER_INGEST_EVENT: &str = "folder-ingest-progress";
 
-    for paragraph in text.split("\n\n") {
-        if current_chunk.len() + paragraph.len() > chunk_size {
-            chunks.push(current_chunk.clone());
-            current_chunk.clear();
-        }
-        current_chunk.push_str(paragraph);
-        current_chunk.push_str("\n\n");
-    }
-    if !current_chunk.is_empty() {
-        chunks.push(current_chunk);
-    }
-    chunks
+/// Earlier texts kept per source when it is updated in place.
+pub const MAX_KEPT_VERSIONS: usize = 5;
+
+/// Outcome of importing one file.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileIngestResult {
+    pub path: String,
+    /// "imported", "duplicate" or "failed"
+    pub status: String,
+    /// The new node, or the existing one for a duplicate.
+    pub node_id: Option<String>,
+    pub error: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderIngestProgress {
+    pub processed: usize,
+    pub total: usize,
+    pub result: FileIngestResult,
+}
+
+/// Fetch a URL and store it as a source node. HTML pages keep their main
+/// article, PDFs their text and the original file, and plain text and
+/// JSON are stored as is. `ignore_robots` fetches a page the site's
+/// robots.txt disallows.
 #[command]
 pub async fn ingest_url(
     state: State<'_, DbState>,
     url: String,
-    provider: String, // "ollama" or "gemini"
-    api_key: Option<String>,
+    ignore_robots: Option<bool>,
 ) -> Result<Node, String> {
-    let client = Client::new();
-    let resp = client.get(&url).send().await.map_err(|e| e.to_string())?;
-    let html_content = resp.text().await.map_err(|e| e.to_string())?;
-
-    let (title, text_content) = {
-        let document = Html::parse_document(&html_content);
-        
-        // Extract title
-        let title_selector = Selector::parse("title").unwrap();
-        let title = document.select(&title_selector).next().map(|e| e.text().collect::<String>()).unwrap_or("Untitled".to_string());
-
-        // Extract body text
-        let p_selector = Selector::parse("p").unwrap(); 
-        let mut text_content = String::new();
-        for element in document.select(&p_selector) {
-            text_content.push_str(&element.text().collect::<Vec<_>>().join(" "));
-            text_content.push_str("\n\n");
-        }
-        (title, text_content)
+    let settings = state.run(|conn| settings::load(conn).map_err(|e| e.to_string())).await?;
+
+    let options = FetchOptions {
+        ignore_robots: ignore_robots.unwrap_or(false),
+        ..FetchOptions::default()
     };
+    let fetched = fetcher::fetch(&url, &options).await.map_err(|e| e.to_string())?;
+    ingest_fetched(&state, &settings, &url, fetched).await
+}
 
-    // Determine embedding provider
-    let embedding_provider = match provider.as_str() {
-        "ollama" => EmbeddingProvider::Ollama {
-            model: "nomic-embed-text".to_string(), // Default embedding model
-        },
-        "gemini" => EmbeddingProvider::Gemini {
-            api_key: api_key.ok_or("API key required for Gemini")?.to_string(),
-        },
-        _ => return Err("Invalid provider".to_string()),
+/// A fetched page converted to Markdown, ready to store.
+pub(crate) struct FetchedDocument {
+    pub document: FileDocument,
+    /// The response body, kept as the original for PDFs.
+    pub bytes: Vec<u8>,
+    pub canonical_url: String,
+    /// SHA-256 of the extracted text.
+    pub content_hash: String,
+    /// Where and when it was fetched, for the node's metadata.
+    pub metadata: Value,
+}
+
+/// Convert a fetched page and store it as a source node. `url` is the
+/// address it was requested by.
+///
+/// A page already stored under the same canonical URL keeps its node,
+/// updated if the text changed. The same text under another URL is a
+/// source of its own, as either page may change later.
+pub(crate) async fn ingest_fetched(
+    state: &DbState,
+    settings: &AppSettings,
+    url: &str,
+    fetched: Fetched,
+) -> Result<Node, String> {
+    let converted = convert_fetched(url, fetched).await?;
+
+    let lookup_url = converted.canonical_url.clone();
+    let by_url = state
+        .run(move |conn| find_by_canonical_url(conn, &lookup_url).map_err(|e| e.to_string()))
+        .await?;
+
+    // The same page again: keep its node, refreshed if the text changed
+    let existing = match by_url {
+        Some((node, node_hash)) if node_hash.as_deref() == Some(converted.content_hash.as_str()) => return Ok(node),
+        Some((node, _)) => Some(node),
+        None => None,
     };
 
-    let chunks = chunk_text(&text_content, 1000); // 1000 char chunks
+    let FetchedDocument { document, bytes, canonical_url, content_hash, metadata } = converted;
+    let stored = store_document(
+        state,
+        settings,
+        document.clone(),
+        &bytes,
+        metadata.clone(),
+        Some(content_hash.clone()),
+        existing.as_ref(),
+    )
+    .await;
+    match stored {
+        // Another ingest of the page, e.g. by a crawl, stored it since the
+        // lookup; update its node instead
+        Err(StoreError::DuplicateUrl) => {
+            let (existing, _) = state
+                .run(move |conn| find_by_canonical_url(conn, &canonical_url).map_err(|e| e.to_string()))
+                .await?
+                .ok_or_else(|| StoreError::DuplicateUrl.to_string())?;
+            store_document(state, settings, document, &bytes, metadata, Some(content_hash), Some(&existing))
+                .await
+                .map_err(|e| e.to_string())
+        }
+        stored => stored.map_err(|e| e.to_string()),
+    }
+}
+
+/// Convert a fetched page to Markdown and work out its canonical URL.
+pub(crate) async fn convert_fetched(url: &str, fetched: Fetched) -> Result<FetchedDocument, String> {
+    let final_url = fetched.final_url;
+
+    let (kind, bytes) = match fetched.body {
+        // Keep the article itself, not the menus, banners and comments around it
+        Body::Html(html) => (FileKind::Html, html.into_bytes()),
+        Body::Pdf(bytes) => (FileKind::Pdf, bytes),
+        Body::Text(text) if fetched.content_type == "text/markdown" => (FileKind::Markdown, text.into_bytes()),
+        Body::Text(text) => (FileKind::Text, text.into_bytes()),
+        Body::Json(value) => {
+            let pretty = serde_json::to_string_pretty(&value).map_err(|e| e.to_string())?;
+            (FileKind::Code("json"), pretty.into_bytes())
+        }
+    };
+
+    let name = url_file_name(&final_url);
+    let base = final_url.clone();
+    let (bytes, document, canonical_link) = tauri::async_runtime::spawn_blocking(move || {
+        let document = files::convert(kind, &name, &bytes, Some(&base));
+        let canonical_link = match kind {
+            FileKind::Html => std::str::from_utf8(&bytes).ok().and_then(|html| parser::canonical_link(html, &base)),
+            _ => None,
+        };
+        (bytes, document, canonical_link)
+    })
+    .await
+    .map_err(|_| format!("Failed to parse {}", final_url))?;
+    let document = document.map_err(|_| format!("No readable content found at {}", url))?;
+
+    // The page's own canonical link wins over where the request ended up
+    let canonical_url = canonical::normalize(canonical_link.as_ref().unwrap_or(&final_url)).to_string();
+    let content_hash = format!("{:x}", Sha256::digest(document.markdown.as_bytes()));
+
+    let mut metadata = json!({
+        "url": url,
+        "final_url": final_url.as_str(),
+        "canonical_url": canonical_url,
+        "content_type": fetched.content_type,
+        "fetched_at": Utc::now().to_rfc3339()
+    });
+    // Sent back on refresh so unchanged pages needn't be downloaded again
+    if let Some(etag) = fetched.validators.etag {
+        metadata["etag"] = json!(etag);
+    }
+    if let Some(last_modified) = fetched.validators.last_modified {
+        metadata["last_modified"] = json!(last_modified);
+    }
+
+    Ok(FetchedDocument { document, bytes, canonical_url, content_hash, metadata })
+}
+
+/// Name a fetched document would have as a file: the last path segment,
+/// or the host for a site root.
+fn url_file_name(url: &Url) -> String {
+    url.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| segment.to_string())
+        .or_else(|| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| "Untitled".to_string())
+}
+
+/// Import a local Markdown, text, HTML, PDF or source file as a source
+/// node. A file whose exact contents were already imported returns the
+/// existing node instead.
+#[command]
+pub async fn ingest_file(
+    state: State<'_, DbState>,
+    path: String,
+) -> Result<Node, String> {
+    let settings = state.run(|conn| settings::load(conn).map_err(|e| e.to_string())).await?;
+    let (node, _) = import_file(&state, &settings, Path::new(&path)).await?;
+    Ok(node)
+}
+
+/// Import every supported file under a folder, recursively. `include` and
+/// `exclude` are globs over paths relative to the folder, like
+/// `notes/**/*.md`. One file failing doesn't stop the rest; each file's
+/// outcome is reported as a progress event and in the returned list.
+#[command]
+pub async fn ingest_folder(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    path: String,
+    include: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+) -> Result<Vec<FileIngestResult>, String> {
+    let settings = state.run(|conn| settings::load(conn).map_err(|e| e.to_string())).await?;
+
+    let root = PathBuf::from(&path);
+    let (include, exclude) = (include.unwrap_or_default(), exclude.unwrap_or_default());
+    let paths = tauri::async_runtime::spawn_blocking(move || files::find_files(&root, &include, &exclude))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let total = paths.len();
+    let mut results = Vec::with_capacity(total);
+    for (i, file) in paths.iter().enumerate() {
+        let result = match import_file(&state, &settings, file).await {
+            Ok((node, imported)) => FileIngestResult {
+                path: file.display().to_string(),
+                status: if imported { "imported" } else { "duplicate" }.to_string(),
+                node_id: Some(node.id),
+                error: None,
+            },
+            Err(e) => FileIngestResult {
+                path: file.display().to_string(),
+                status: "failed".to_string(),
+                node_id: None,
+                error: Some(e),
+            },
+        };
+        let _ = app.emit(FOLDER_INGEST_EVENT, FolderIngestProgress {
+            processed: i + 1,
+            total,
+            result: result.clone(),
+        });
+        results.push(result);
+    }
+    Ok(results)
+}
+
+/// Import one file. Returns the node and whether it is new, or the
+/// existing node if the same bytes were imported before.
+async fn import_file(state: &DbState, settings: &AppSettings, path: &Path) -> Result<(Node, bool), String> {
+    if FileKind::of(path).is_none() {
+        return Err(format!("Unsupported file type: {}", path.display()));
+    }
+    let bytes = tokio::fs::read(path)
+        .await
+        .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    let hash = format!("{:x}", Sha256::digest(&bytes));
+
+    let lookup = hash.clone();
+    if let Some(existing) = state.run(move |conn| find_by_hash(conn, &lookup).map_err(|e| e.to_string())).await? {
+        return Ok((existing, false));
+    }
+
+    // Parsing is CPU-bound and the PDF parser can panic on malformed
+    // files; on a blocking task a panic surfaces as a join error
+    let read_path = path.to_path_buf();
+    let (bytes, document) = tauri::async_runtime::spawn_blocking(move || {
+        let document = files::read_document(&read_path, &bytes);
+        (bytes, document)
+    })
+    .await
+    .map_err(|_| format!("Failed to parse {}", path.display()))?;
+    let document = document?;
+
+    let metadata = json!({
+        "source_path": path.display().to_string(),
+        "file_name": path.file_name().map(|n| n.to_string_lossy().into_owned())
+    });
+    let node = store_document(state, settings, document, &bytes, metadata, Some(hash), None)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok((node, true))
+}
+
+/// Chunk, embed and store a converted document as a new source node,
+/// adding the file type and embedding details to `metadata`. PDFs keep
+/// `bytes`, the original file, next to the extracted text.
+///
+/// With `existing`, that node is updated in place instead. Its current
+/// text is kept as a previous version, and its place in the graph is
+/// kept.
+pub(crate) async fn store_document(
+    state: &DbState,
+    settings: &AppSettings,
+    document: FileDocument,
+    bytes: &[u8],
+    mut metadata: Value,
+    content_hash: Option<String>,
+    existing: Option<&Node>,
+) -> Result<Node, StoreError> {
+    // Determine embedding provider
+    let embedder = embeddings::from_settings(settings)?;
+    let chunks = chunker::chunk_text(&document.markdown, &settings.chunk_options());
 
     // 1. Prepare Data & Artifacts (Async/Sync, No DB Lock)
-    let node_id = Uuid::new_v4().to_string();
+    let node_id = existing.map_or_else(|| Uuid::new_v4().to_string(), |n| n.id.clone());
     let now = Utc::now().to_rfc3339();
     let node_type = "source".to_string();
 
-    // Save content to file
+    // Stage content files; they are only moved into place once the DB commits
     let ws = Workspace::new().map_err(|e| e.to_string())?;
     let filename = format!("{}.md", node_id);
-    ws.write_artifact(&filename, text_content.as_bytes()).map_err(|e| e.to_string())?;
-
-    let metadata = json!({
-        "url": url,
-        "chunk_count": chunks.len(),
-        "provider": provider
-    });
+    let mut artifacts = vec![ws.stage_artifact(&filename, document.markdown.as_bytes()).map_err(|e| e.to_string())?];
 
-    // 2. Compute Embeddings (Async, No DB Lock)
-    let mut chunk_embeddings = Vec::new();
-    for chunk in &chunks {
-        let embedding = embed_text(chunk, &embedding_provider).await?;
-        let embedding_json = serde_json::to_string(&embedding).map_err(|e| e.to_string())?;
-        chunk_embeddings.push(embedding_json);
+    metadata["file_type"] = json!(document.kind.name());
+    metadata["chunk_count"] = json!(chunks.len());
+    metadata["provider"] = json!(settings.embedding_provider);
+    metadata["embedding_model"] = json!(embedder.model_id());
+    if let Some(front_matter) = &document.front_matter {
+        metadata["front_matter"] = front_matter.clone();
+    }
+    // Keep the PDF itself for viewing; the Markdown is only its text
+    if document.kind == FileKind::Pdf {
+        let original = format!("{}.pdf", node_id);
+        artifacts.push(ws.stage_artifact(&original, bytes).map_err(|e| e.to_string())?);
+        metadata["original_path"] = json!(original);
+        metadata["page_count"] = json!(document.page_count);
     }
 
-    // 3. Database Operations (Sync Block, DB Lock Held Here)
-    {
-        let conn = state.get_connection().map_err(|e| e.to_string())?;
+    // Files of the replaced node the new version no longer uses
+    let mut stale_artifacts = Vec::new();
+    if let Some(old) = existing {
+        let old_metadata = old.metadata.as_ref();
 
-        // Insert Node
-        conn.execute(
-            "INSERT INTO nodes (id, node_type, title, content_path, metadata, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            rusqlite::params![node_id, node_type, title, filename, metadata.to_string(), now, now],
-        ).map_err(|e| e.to_string())?;
-
-        // Insert Vectors
-        for embedding_json in chunk_embeddings {
-            conn.execute(
-                "INSERT INTO nodes_vec (node_id, embedding) VALUES (?1, ?2)",
-                rusqlite::params![node_id, embedding_json], 
-            ).map_err(|e| e.to_string())?;
+        // Keep the text being replaced so versions can be compared, up to
+        // MAX_KEPT_VERSIONS of them, newest first
+        let version = old_metadata.and_then(|m| m.get("version")?.as_u64()).unwrap_or(1);
+        let mut kept: Vec<Value> = old_metadata
+            .and_then(|m| m.get("previous_versions")?.as_array().cloned())
+            .unwrap_or_default();
+        if let Some(previous) = old.content_path.as_deref().and_then(|p| ws.read_artifact(p).ok()) {
+            let previous_path = format!("{}.v{}.md", node_id, version);
+            artifacts.push(ws.stage_artifact(&previous_path, &previous).map_err(|e| e.to_string())?);
+            kept.insert(0, json!({
+                "version": version,
+                "content_path": previous_path,
+                "updated_at": old.updated_at
+            }));
+        }
+        for dropped in kept.drain(kept.len().min(MAX_KEPT_VERSIONS)..) {
+            stale_artifacts.extend(dropped.get("content_path").and_then(Value::as_str).map(str::to_string));
+        }
+        metadata["version"] = json!(version + 1);
+        metadata["previous_versions"] = json!(kept);
+
+        for key in ["x", "y"] {
+            if let Some(value) = old_metadata.and_then(|m| m.get(key)) {
+                metadata[key] = value.clone();
+            }
         }
 
-        // Insert FTS
-        conn.execute(
-            "INSERT INTO nodes_fts (id, title, content) VALUES (?1, ?2, ?3)",
-            rusqlite::params![node_id, title, text_content],
-        ).map_err(|e| e.to_string())?;
-    } // conn is dropped here
+        // An original the new version no longer has, e.g. a page that was a PDF
+        let original = |m: &Value| m.get("original_path").and_then(Value::as_str).map(str::to_string);
+        if let Some(old_original) = old_metadata.and_then(original) {
+            if original(&metadata).as_ref() != Some(&old_original) {
+                stale_artifacts.push(old_original);
+            }
+        }
+    }
 
-    Ok(Node {
+    let node = Node {
         id: node_id,
         node_type,
-        title,
+        title: document.title,
         content_path: Some(filename),
         metadata: Some(metadata),
-        created_at: now.clone(),
+        created_at: existing.map_or_else(|| now.clone(), |n| n.created_at.clone()),
         updated_at: now,
-    })
+        index_status: index_status::INDEXED.to_string(),
+    };
+    let source = NewSource {
+        node,
+        content: document.markdown,
+        content_hash,
+        chunks,
+        artifacts,
+        replaces_existing: existing.is_some(),
+        stale_artifacts,
+    };
+    store_source(state, embedder, source).await
+}
+
+pub(crate) const NODE_COLUMNS: &str = "id, node_type, title, content_path, metadata, created_at, updated_at, index_status, content_hash";
+
+/// The live node imported from content with this hash, if any.
+fn find_by_hash(conn: &Connection, hash: &str) -> rusqlite::Result<Option<Node>> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM nodes WHERE content_hash = ?1 AND deleted_at IS NULL
+             ORDER BY created_at LIMIT 1",
+            NODE_COLUMNS
+        ),
+        params![hash],
+        node_from_row,
+    )
+    .optional()
+    .map(|found| found.map(|(node, _)| node))
+}
+
+/// The live node ingested from this canonical URL, if any, with its
+/// content hash.
+fn find_by_canonical_url(conn: &Connection, url: &str) -> rusqlite::Result<Option<(Node, Option<String>)>> {
+    // Spelled as in idx_nodes_canonical_url so the index is used
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM nodes
+             WHERE json_extract(metadata, '$.canonical_url') = ?1
+               AND deleted_at IS NULL AND json_valid(metadata)",
+            NODE_COLUMNS
+        ),
+        params![url],
+        node_from_row,
+    )
+    .optional()
+}
+
+/// A node and its content hash, from a row of `NODE_COLUMNS`.
+pub(crate) fn node_from_row(row: &rusqlite::Row) -> rusqlite::Result<(Node, Option<String>)> {
+    let metadata_str: Option<String> = row.get(4)?;
+    let node = Node {
+        id: row.get(0)?,
+        node_type: row.get(1)?,
+        title: row.get(2)?,
+        content_path: row.get(3)?,
+        metadata: metadata_str.and_then(|s| serde_json::from_str(&s).ok()),
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+        index_status: row.get(7)?,
+    };
+    Ok((node, row.get(8)?))
+}
+
+/// Why a source could not be stored.
+#[derive(Debug)]
+pub(crate) enum StoreError {
+    /// A live source with the same canonical URL was stored meanwhile.
+    DuplicateUrl,
+    Failed(String),
+}
+
+impl From<String> for StoreError {
+    fn from(e: String) -> Self {
+        StoreError::Failed(e)
+    }
+}
+
+impl std::fmt::Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoreError::DuplicateUrl => write!(f, "Another source with the same URL exists"),
+            StoreError::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// A new source node and everything stored with it.
+struct NewSource {
+    node: Node,
+    content: String,
+    /// SHA-256 of the imported file, or of the extracted text for web
+    /// pages, used to spot re-imports.
+    content_hash: Option<String>,
+    chunks: Vec<ChunkSpan>,
+    /// Published when the node is committed.
+    artifacts: Vec<StagedArtifact>,
+    /// Whether the node overwrites a stored node with the same id.
+    replaces_existing: bool,
+    /// Artifacts of the replaced node to remove once committed.
+    stale_artifacts: Vec<String>,
+}
+
+/// Embed the chunks of a new source node, then insert the node with its
+/// chunks, vectors and full-text entry in one transaction and publish its
+/// staged artifacts. A node that replaces an existing one overwrites its
+/// row and index entries instead.
+///
+/// A new web source whose canonical URL a live node already has is not
+/// stored; that is `StoreError::DuplicateUrl`.
+async fn store_source(state: &DbState, embedder: Arc<dyn Embedder>, source: NewSource) -> Result<Node, StoreError> {
+    let NewSource { node, content, content_hash, chunks, artifacts, replaces_existing, stale_artifacts } = source;
+
+    // 2. Compute Embeddings (Async, No DB Lock)
+    let texts: Vec<String> = chunks.iter().map(|c| c.text.clone()).collect();
+    let embedded = embeddings::embed_all(embedder.clone(), &texts, &BatchOptions::default())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // 3. Database Operations (Blocking Pool, One Transaction)
+    let model_id = embedder.model_id().to_string();
+    let dimension = embedder.dimension();
+
+    let stored = state.run(move |conn| {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let index = vec::ensure_index(&tx, &model_id, dimension)
+            .map_err(|e| e.to_string())?;
+
+        // Insert Node
+        let metadata = node.metadata.as_ref().map(|m| m.to_string());
+        if replaces_existing {
+            tx.execute(
+                "UPDATE nodes SET title = ?2, content_path = ?3, metadata = ?4, updated_at = ?5, content_hash = ?6, index_status = ?7
+                 WHERE id = ?1",
+                params![node.id, node.title, node.content_path, metadata, node.updated_at, content_hash, node.index_status],
+            ).map_err(|e| e.to_string())?;
+            // New chunks get new ids, so vectors of every model for the old ones go
+            vec::delete_node_vectors(&tx, &node.id).map_err(|e| e.to_string())?;
+            tx.execute("DELETE FROM nodes_fts WHERE id = ?1", params![node.id]).map_err(|e| e.to_string())?;
+        } else {
+            // Looked up in the transaction that inserts, so a page stored by
+            // a concurrent ingest is seen; the unique index catches the rest
+            let canonical_url = node.metadata.as_ref().and_then(|m| m.get("canonical_url")?.as_str());
+            if let Some(url) = canonical_url {
+                if find_by_canonical_url(&tx, url).map_err(|e| e.to_string())?.is_some() {
+                    return Ok(None);
+                }
+            }
+            let inserted = tx.execute(
+                "INSERT INTO nodes (id, node_type, title, content_path, metadata, created_at, updated_at, content_hash)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![node.id, node.node_type, node.title, node.content_path, metadata, node.created_at, node.updated_at, content_hash],
+            );
+            match inserted {
+                Err(rusqlite::Error::SqliteFailure(f, _))
+                    if f.code == rusqlite::ErrorCode::ConstraintViolation && canonical_url.is_some() =>
+                {
+                    return Ok(None);
+                }
+                inserted => inserted.map_err(|e| e.to_string())?,
+            };
+        }
+
+        // Insert Chunks + Vectors + FTS
+        vec::index_node(&tx, &index, &node.id, &node.title, &content, &chunks, &embedded)
+            .map_err(|e| e.to_string())?;
+
+        // Publish the artifacts only once every row is written, and take
+        // them back if the commit itself fails. Files they replace are
+        // restored rather than lost, as the old row still points at them.
+        let mut published = Vec::with_capacity(artifacts.len());
+        let result = artifacts
+            .into_iter()
+            .try_for_each(|artifact| artifact.publish().map(|p| published.push(p)))
+            .map_err(|e| e.to_string())
+            .and_then(|_| tx.commit().map_err(|e| e.to_string()));
+        if let Err(mut e) = result {
+            for artifact in published.into_iter().rev() {
+                let path = artifact.path.clone();
+                if let Err(err) = artifact.rollback() {
+                    e = format!("{} (and restoring {} failed: {})", e, path.display(), err);
+                }
+            }
+            return Err(e);
+        }
+        published.into_iter().for_each(PublishedArtifact::finish);
+
+        if let Ok(ws) = Workspace::new() {
+            for stale in &stale_artifacts {
+                let _ = ws.delete_artifact(stale);
+            }
+        }
+        Ok(Some(node))
+    }).await?;
+    stored.ok_or(StoreError::DuplicateUrl)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{chunks, testing::open_state};
+    use crate::scraper::fetcher::Validators;
+    use crate::scraper::pdf;
+
+    fn page(url: &str, text: &str) -> Fetched {
+        Fetched {
+            final_url: Url::parse(url).unwrap(),
+            content_type: "text/html".to_string(),
+            validators: Validators::default(),
+            body: Body::Html(format!(
+                "<html><head><title>Ownership</title></head><body><article><p>{}</p></article></body></html>",
+                text
+            )),
+        }
+    }
+
+    #[tokio::test]
+    async fn pages_are_deduplicated_by_canonical_url_only() {
+        let state = open_state();
+        let settings = state.run(|conn| settings::load(conn).map_err(|e| e.to_string())).await.unwrap();
+        let text = "Every Rust value has a single owner, and the value is dropped when its owner goes out of scope.";
+
+        let first = ingest_fetched(&state, &settings, "https://a.example/rust", page("https://a.example/rust", text))
+            .await
+            .unwrap();
+        let again = ingest_fetched(&state, &settings, "https://a.example/rust/", page("https://a.example/rust/", text))
+            .await
+            .unwrap();
+        assert_eq!(again.id, first.id);
+
+        // A mirror of the text is a source of its own
+        let mirror = ingest_fetched(&state, &settings, "https://b.example/rust", page("https://b.example/rust", text))
+            .await
+            .unwrap();
+        assert_ne!(mirror.id, first.id);
+        let canonical = mirror.metadata.as_ref().and_then(|m| m["canonical_url"].as_str().map(str::to_string));
+        assert_eq!(canonical.as_deref(), Some("https://b.example/rust"));
+    }
+
+    #[tokio::test]
+    async fn pdfs_keep_their_original_and_page_tagged_chunks() {
+        let state = open_state();
+        let settings = state.run(|conn| settings::load(conn).map_err(|e| e.to_string())).await.unwrap();
+        let bytes = pdf::testing::build(Some("Ownership"), &["Every value has an owner.", "Borrowing lends a value."]);
+        let path = state.db_path.with_file_name("ownership.pdf");
+        std::fs::write(&path, &bytes).unwrap();
+
+        let (node, imported) = import_file(&state, &settings, &path).await.unwrap();
+        assert!(imported);
+        assert_eq!(node.title, "Ownership");
+        let metadata = node.metadata.clone().unwrap();
+        assert_eq!(metadata["file_type"], "pdf");
+        assert_eq!(metadata["page_count"], 2);
+
+        let ws = Workspace::new().unwrap();
+        let original = metadata["original_path"].as_str().unwrap();
+        assert_eq!(ws.read_artifact(original).unwrap(), bytes);
+        let text = String::from_utf8(ws.read_artifact(node.content_path.as_deref().unwrap()).unwrap()).unwrap();
+        assert!(text.contains(&pdf::page_marker(2)));
+
+        let id = node.id.clone();
+        let stored = state.run(move |conn| chunks::for_node(conn, &id).map_err(|e| e.to_string())).await.unwrap();
+        assert!(!stored.is_empty());
+        assert_eq!(stored.first().unwrap().page_start, Some(1));
+        assert_eq!(stored.last().unwrap().page_end, Some(2));
+    }
+
+    #[tokio::test]
+    async fn files_with_the_same_bytes_are_imported_once() {
+        let state = open_state();
+        let settings = state.run(|conn| settings::load(conn).map_err(|e| e.to_string())).await.unwrap();
+        let text = "---\ntitle: Ownership\n---\nEvery Rust value has a single owner.\n";
+        let path = state.db_path.with_file_name("ownership.md");
+        let copy = state.db_path.with_file_name("copy.md");
+        std::fs::write(&path, text).unwrap();
+        std::fs::write(&copy, text).unwrap();
+
+        let (node, imported) = import_file(&state, &settings, &path).await.unwrap();
+        assert!(imported);
+        assert_eq!(node.title, "Ownership");
+        let metadata = node.metadata.clone().unwrap();
+        assert_eq!(metadata["front_matter"]["title"], "Ownership");
+        assert_eq!(metadata["file_name"], "ownership.md");
+
+        let (same, imported) = import_file(&state, &settings, &copy).await.unwrap();
+        assert!(!imported);
+        assert_eq!(same.id, node.id);
+
+        std::fs::write(&copy, "Borrowing lends a value without giving it away.").unwrap();
+        let (edited, imported) = import_file(&state, &settings, &copy).await.unwrap();
+        assert!(imported);
+        assert_ne!(edited.id, node.id);
+
+        let missing = state.db_path.with_file_name("missing.md");
+        assert!(import_file(&state, &settings, &missing).await.unwrap_err().starts_with("Cannot read"));
+        let image = state.db_path.with_file_name("photo.png");
+        assert!(import_file(&state, &settings, &image).await.unwrap_err().starts_with("Unsupported file type"));
+    }
 }
@@ -1,5 +1,10 @@
 pub mod nodes;
 pub mod graph;
 pub mod ingest;
+pub mod crawl;
+pub mod refresh;
 pub mod search;
 pub mod chat;
+pub mod reindex;
+pub mod settings;
+pub mod sessions;
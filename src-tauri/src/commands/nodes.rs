@@ -1,8 +1,10 @@
 use tauri::{AppHandle, State, Runtime};
-use crate::db::DbState;
-use crate::models::Node;
+use crate::db::trash::{self, Purged, TrashedNode};
+use crate::commands::reindex::schedule_node_index;
+use crate::db::{vec, DbState};
+use crate::models::{index_status, Node};
 use crate::fs_manager::Workspace;
-use rusqlite::params;
+use rusqlite::{params, OptionalExtension};
 use serde_json::json;
 use uuid::Uuid;
 
@@ -37,6 +39,7 @@ pub fn create_node(
         metadata: Some(meta_json_value),
         created_at: now.clone(),
         updated_at: now,
+        index_status: index_status::INDEXED.to_string(),
     })
 }
 
@@ -45,7 +48,7 @@ pub fn get_node(state: State<DbState>, id: String) -> Result<Option<Node>, Strin
     let conn = state.get_connection().map_err(|e| e.to_string())?;
     
     let mut stmt = conn.prepare(
-        "SELECT id, node_type, title, content_path, metadata, created_at, updated_at FROM nodes WHERE id = ?1"
+        "SELECT id, node_type, title, content_path, metadata, created_at, updated_at, index_status FROM nodes WHERE id = ?1 AND deleted_at IS NULL"
     ).map_err(|e| e.to_string())?;
     
     let node = stmt.query_row(params![id], |row| {
@@ -60,6 +63,7 @@ pub fn get_node(state: State<DbState>, id: String) -> Result<Option<Node>, Strin
             metadata,
             created_at: row.get(5)?,
             updated_at: row.get(6)?,
+            index_status: row.get(7)?,
         })
     }).map_err(|e| e.to_string());
 
@@ -70,45 +74,123 @@ pub fn get_node(state: State<DbState>, id: String) -> Result<Option<Node>, Strin
     }
 }
 
+/// Save a node's content. Full-text search sees it at once; chunks and
+/// vectors are rebuilt in the background once edits settle, and the node
+/// is marked stale until then.
 #[tauri::command]
 pub fn save_node_content(
+    app: AppHandle,
     state: State<DbState>,
     id: String,
     content: String,
 ) -> Result<(), String> {
-    let conn = state.get_connection().map_err(|e| e.to_string())?;
+    let mut conn = state.get_connection().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let title: String = tx
+        .query_row("SELECT title FROM nodes WHERE id = ?1 AND deleted_at IS NULL", params![id], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Node {} not found", id))?;
     
     let ws = Workspace::new().map_err(|e| e.to_string())?;
     let filename = format!("{}.md", id); // Use ID as filename for simplicity
     
-    ws.write_artifact(&filename, content.as_bytes()).map_err(|e| e.to_string())?;
-    
+    let artifact = ws.stage_artifact(&filename, content.as_bytes()).map_err(|e| e.to_string())?;
+
     // Update DB with content path
-    conn.execute(
-        "UPDATE nodes SET content_path = ?1, updated_at = ?2 WHERE id = ?3",
-        params![filename, chrono::Utc::now().to_rfc3339(), id],
+    tx.execute(
+        "UPDATE nodes SET content_path = ?1, updated_at = ?2, index_status = ?3 WHERE id = ?4",
+        params![filename, chrono::Utc::now().to_rfc3339(), index_status::STALE, id],
     ).map_err(|e| e.to_string())?;
-    
+    vec::update_fts(&tx, &id, &title, &content).map_err(|e| e.to_string())?;
+
+    // Publish once the rows are written; if the commit fails the previous
+    // content comes back, as the index still describes it
+    let published = artifact.publish().map_err(|e| e.to_string())?;
+    if let Err(e) = tx.commit() {
+        let path = published.path.clone();
+        return Err(match published.rollback() {
+            Ok(()) => e.to_string(),
+            Err(err) => format!("{} (and restoring {} failed: {})", e, path.display(), err),
+        });
+    }
+    published.finish();
+
+    schedule_node_index(&app, &id);
     Ok(())
 }
 
+/// Remove the artifact of a purged node. The rows are already gone, so a
+/// failure only leaves an orphan file behind.
+fn remove_artifact(purged: &Purged) {
+    let Ok(ws) = Workspace::new() else {
+        return;
+    };
+    for p in purged.content_path.iter().chain(&purged.original_path).chain(&purged.previous_paths) {
+        let _ = ws.delete_artifact(p); // Ignore error if file doesn't exist
+    }
+}
+
+/// Move a node to the trash, or delete it for good with `permanent`.
 #[tauri::command]
-pub fn delete_node(state: State<DbState>, id: String) -> Result<(), String> {
+pub fn delete_node(state: State<DbState>, id: String, permanent: Option<bool>) -> Result<(), String> {
+    if permanent.unwrap_or(false) {
+        return purge_node(state, id);
+    }
+
     let conn = state.get_connection().map_err(|e| e.to_string())?;
-    
-    // Get content path to delete file
-    let path: Option<String> = conn.query_row(
-        "SELECT content_path FROM nodes WHERE id = ?1",
-        params![id],
-        |row| row.get(0),
-    ).unwrap_or(None);
-
-    if let Some(p) = path {
-        let ws = Workspace::new().map_err(|e| e.to_string())?;
-        let _ = ws.delete_artifact(&p); // Ignore error if file doesn't exist
+    if !trash::soft_delete(&conn, &id).map_err(|e| e.to_string())? {
+        return Err(format!("Node {} not found", id));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn restore_node(state: State<DbState>, id: String) -> Result<(), String> {
+    let conn = state.get_connection().map_err(|e| e.to_string())?;
+    let restored = trash::restore(&conn, &id).map_err(|e| match e {
+        // Only the unique canonical URL of sources can conflict
+        rusqlite::Error::SqliteFailure(f, _) if f.code == rusqlite::ErrorCode::ConstraintViolation => {
+            "Another source with the same URL exists; delete it before restoring this one".to_string()
+        }
+        e => e.to_string(),
+    })?;
+    if !restored {
+        return Err(format!("Node {} is not in the trash", id));
     }
+    Ok(())
+}
 
-    conn.execute("DELETE FROM nodes WHERE id = ?1", params![id]).map_err(|e| e.to_string())?;
-    
+#[tauri::command]
+pub fn list_trash(state: State<DbState>) -> Result<Vec<TrashedNode>, String> {
+    let conn = state.get_connection().map_err(|e| e.to_string())?;
+    trash::list(&conn).map_err(|e| e.to_string())
+}
+
+/// Permanently delete a node with its indexes, edges and artifact.
+#[tauri::command]
+pub fn purge_node(state: State<DbState>, id: String) -> Result<(), String> {
+    let mut conn = state.get_connection().map_err(|e| e.to_string())?;
+    let purged = trash::purge(&mut conn, &id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Node {} not found", id))?;
+    remove_artifact(&purged);
     Ok(())
 }
+
+/// Purge every trashed node. Returns how many were deleted.
+#[tauri::command]
+pub fn empty_trash(state: State<DbState>) -> Result<usize, String> {
+    let mut conn = state.get_connection().map_err(|e| e.to_string())?;
+    let ids = trash::trashed_ids(&conn).map_err(|e| e.to_string())?;
+
+    let mut purged_count = 0;
+    for id in ids {
+        if let Some(purged) = trash::purge(&mut conn, &id).map_err(|e| e.to_string())? {
+            remove_artifact(&purged);
+            purged_count += 1;
+        }
+    }
+    Ok(purged_count)
+}
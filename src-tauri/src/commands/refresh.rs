@@ -0,0 +1,563 @@
+use tauri::{command, AppHandle, Emitter, Manager, State};
+use crate::ai::embeddings;
+use crate::commands::ingest::{convert_fetched, node_from_row, store_document, FetchedDocument, NODE_COLUMNS};
+use crate::db::DbState;
+use crate::fs_manager::Workspace;
+use crate::models::Node;
+use crate::scraper::fetcher::{self, FetchError, FetchOptions, Fetched, Validators};
+use crate::settings::{self, AppSettings};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use similar::{ChangeTag, TextDiff};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+pub const SOURCE_REFRESH_EVENT: &str = "source-refresh-progress";
+/// A scheduled refresh that failed before reaching any source, e.g. on
+/// invalid settings. Carries the error message.
+pub const SOURCE_REFRESH_ERROR_EVENT: &str = "source-refresh-error";
+
+/// How often the scheduler looks for sources due a refresh.
+const SCHEDULER_TICK: Duration = Duration::from_secs(10 * 60);
+
+static REFRESH_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Clears the running flag even if the refresh panics.
+struct RunningGuard;
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        REFRESH_RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Outcome of refreshing one source.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshResult {
+    pub node_id: String,
+    pub title: String,
+    /// "updated", "unchanged", "not_modified", "edited" (changed in the app
+    /// since it was fetched, so left alone) or "failed"
+    pub status: String,
+    /// Lines of text added and removed, for an update
+    pub added_lines: usize,
+    pub removed_lines: usize,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceRefreshProgress {
+    pub processed: usize,
+    pub total: usize,
+    pub result: RefreshResult,
+}
+
+/// Where refreshed sources are fetched from.
+#[async_trait]
+trait SourceFetcher: Send + Sync {
+    /// Fetch `url` unless it is unchanged since the response `validators`
+    /// came from.
+    async fn fetch(&self, url: &str, validators: &Validators) -> Result<Fetched, FetchError>;
+}
+
+/// Sources fetched from the web.
+struct Web;
+
+#[async_trait]
+impl SourceFetcher for Web {
+    async fn fetch(&self, url: &str, validators: &Validators) -> Result<Fetched, FetchError> {
+        fetcher::fetch_if_modified(url, &FetchOptions::default(), validators).await
+    }
+}
+
+/// How a source's text changed since a kept earlier version.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceChanges {
+    pub node_id: String,
+    pub previous_version: u64,
+    /// When the earlier text was stored
+    pub previous_updated_at: String,
+    pub updated_at: String,
+    /// Unified diff from the previous text to the current one
+    pub diff: String,
+    pub added_lines: usize,
+    pub removed_lines: usize,
+}
+
+/// Re-fetch a web source and re-index it if its text changed. Pages that
+/// sent an ETag or Last-Modified are only downloaded again if the server
+/// says they changed. The replaced text is kept; see `get_source_changes`.
+/// Sources edited in the app since they were fetched are left alone.
+#[command]
+pub async fn refresh_source(state: State<'_, DbState>, id: String) -> Result<RefreshResult, String> {
+    let settings = state.run(|conn| settings::load(conn).map_err(|e| e.to_string())).await?;
+    embeddings::from_settings(&settings)?;
+
+    // One refresh at a time, so this can't race another over the same node
+    if REFRESH_RUNNING.swap(true, Ordering::SeqCst) {
+        return Err("A refresh is already running".to_string());
+    }
+    let _guard = RunningGuard;
+
+    let lookup = id.clone();
+    let (node, content_hash) = state
+        .run(move |conn| web_sources(conn, Some(&lookup)).map_err(|e| e.to_string()))
+        .await?
+        .pop()
+        .ok_or_else(|| format!("Node {} is not a web source", id))?;
+    Ok(refresh(&state, &settings, &Web, node, content_hash).await)
+}
+
+/// Refresh every web source, one after another. Each source's outcome is
+/// reported as a `source-refresh-progress` event and in the returned list.
+#[command]
+pub async fn refresh_sources(app: AppHandle) -> Result<Vec<RefreshResult>, String> {
+    let state = app.state::<DbState>();
+    let settings = state.run(|conn| settings::load(conn).map_err(|e| e.to_string())).await?;
+    // Fail now rather than on every source
+    embeddings::from_settings(&settings)?;
+
+    if REFRESH_RUNNING.swap(true, Ordering::SeqCst) {
+        return Err("A refresh is already running".to_string());
+    }
+    let _guard = RunningGuard;
+
+    let sources = state.run(|conn| web_sources(conn, None).map_err(|e| e.to_string())).await?;
+    Ok(refresh_all(&app, &settings, sources).await)
+}
+
+/// What changed in a source's text since a kept earlier version, by
+/// default the one before the last update.
+#[command]
+pub async fn get_source_changes(
+    state: State<'_, DbState>,
+    id: String,
+    version: Option<u64>,
+) -> Result<SourceChanges, String> {
+    let lookup = id.clone();
+    let node = state
+        .run(move |conn| {
+            conn.query_row(
+                &format!("SELECT {} FROM nodes WHERE id = ?1", NODE_COLUMNS),
+                params![lookup],
+                node_from_row,
+            )
+            .optional()
+            .map_err(|e| e.to_string())
+        })
+        .await?
+        .map(|(node, _)| node)
+        .ok_or_else(|| format!("Node {} not found", id))?;
+
+    let kept = node
+        .metadata
+        .as_ref()
+        .and_then(|m| m.get("previous_versions")?.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let previous = match version {
+        Some(version) => kept.iter().find(|v| v.get("version").and_then(Value::as_u64) == Some(version)),
+        None => kept.first(),
+    };
+    let (Some(previous), Some(current_path)) = (previous, node.content_path.as_deref()) else {
+        return Err(match version {
+            Some(version) => format!("Version {} of {} is not kept", version, node.title),
+            None => format!("No earlier version of {} is kept", node.title),
+        });
+    };
+    let previous_path = previous
+        .get("content_path")
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("No earlier version of {} is kept", node.title))?;
+
+    let ws = Workspace::new().map_err(|e| e.to_string())?;
+    let read = |path: &str| {
+        ws.read_artifact(path)
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .map_err(|e| e.to_string())
+    };
+    let (old, new) = (read(previous_path)?, read(current_path)?);
+
+    let diff = TextDiff::from_lines(&old, &new);
+    let (added_lines, removed_lines) = count_changes(&diff);
+    Ok(SourceChanges {
+        previous_version: previous.get("version").and_then(Value::as_u64).unwrap_or_default(),
+        previous_updated_at: previous
+            .get("updated_at")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+        updated_at: node.updated_at.clone(),
+        diff: diff.unified_diff().header("previous", "current").to_string(),
+        added_lines,
+        removed_lines,
+        node_id: node.id,
+    })
+}
+
+/// Refresh web sources in the background once they are
+/// `source_refresh_hours` old. The setting is read on every check, so
+/// changing it needs no restart.
+pub fn schedule_source_refresh(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(SCHEDULER_TICK).await;
+            if let Err(e) = refresh_due_sources(&app).await {
+                let _ = app.emit(SOURCE_REFRESH_ERROR_EVENT, format!("Scheduled source refresh failed: {}", e));
+            }
+        }
+    });
+}
+
+async fn refresh_due_sources(app: &AppHandle) -> Result<(), String> {
+    let state = app.state::<DbState>();
+    let settings = state.run(|conn| settings::load(conn).map_err(|e| e.to_string())).await?;
+    if settings.source_refresh_hours == 0 {
+        return Ok(());
+    }
+    embeddings::from_settings(&settings)?;
+
+    // A refresh the user started is under way; check again next time
+    if REFRESH_RUNNING.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+    let _guard = RunningGuard;
+
+    let max_age = chrono::Duration::hours(i64::from(settings.source_refresh_hours));
+    let now = Utc::now();
+    let due: Vec<(Node, Option<String>)> = state
+        .run(|conn| web_sources(conn, None).map_err(|e| e.to_string()))
+        .await?
+        .into_iter()
+        .filter(|(node, _)| last_checked(node).is_none_or(|at| now - at >= max_age))
+        .collect();
+    if !due.is_empty() {
+        refresh_all(app, &settings, due).await;
+    }
+    Ok(())
+}
+
+/// When a source was last refreshed, failed attempts included, or else
+/// ingested.
+fn last_checked(node: &Node) -> Option<DateTime<Utc>> {
+    let metadata = node.metadata.as_ref();
+    let at = metadata
+        .and_then(|m| m.get("checked_at").or_else(|| m.get("fetched_at"))?.as_str())
+        .unwrap_or(&node.updated_at);
+    DateTime::parse_from_rfc3339(at).ok().map(|at| at.with_timezone(&Utc))
+}
+
+async fn refresh_all(
+    app: &AppHandle,
+    settings: &AppSettings,
+    sources: Vec<(Node, Option<String>)>,
+) -> Vec<RefreshResult> {
+    let state = app.state::<DbState>();
+    let total = sources.len();
+    let mut results = Vec::with_capacity(total);
+    for (i, (node, content_hash)) in sources.into_iter().enumerate() {
+        let result = refresh(&state, settings, &Web, node, content_hash).await;
+        let _ = app.emit(SOURCE_REFRESH_EVENT, SourceRefreshProgress {
+            processed: i + 1,
+            total,
+            result: result.clone(),
+        });
+        results.push(result);
+    }
+    results
+}
+
+/// Refresh one source, recording a failure in its metadata.
+async fn refresh(
+    state: &DbState,
+    settings: &AppSettings,
+    pages: &dyn SourceFetcher,
+    node: Node,
+    content_hash: Option<String>,
+) -> RefreshResult {
+    let mut result = RefreshResult {
+        node_id: node.id.clone(),
+        title: node.title.clone(),
+        status: "failed".to_string(),
+        added_lines: 0,
+        removed_lines: 0,
+        error: None,
+    };
+
+    let checked_at = Utc::now().to_rfc3339();
+    let id = node.id.clone();
+    match refresh_node(state, settings, pages, node, content_hash, &checked_at).await {
+        Ok((status, added_lines, removed_lines)) => {
+            result.status = status.to_string();
+            result.added_lines = added_lines;
+            result.removed_lines = removed_lines;
+        }
+        Err(e) => {
+            let patch = json!({ "checked_at": checked_at, "refresh_error": e });
+            result.error = Some(match patch_metadata(state, &id, patch, None).await {
+                Ok(()) => e,
+                // The refresh failure is still the one worth reporting
+                Err(err) => format!("{} (and recording the failure failed: {})", e, err),
+            });
+        }
+    }
+    result
+}
+
+/// Returns the status with the lines added and removed.
+async fn refresh_node(
+    state: &DbState,
+    settings: &AppSettings,
+    pages: &dyn SourceFetcher,
+    node: Node,
+    content_hash: Option<String>,
+    checked_at: &str,
+) -> Result<(&'static str, usize, usize), String> {
+    let ws = Workspace::new().map_err(|e| e.to_string())?;
+    let stored = node
+        .content_path
+        .as_deref()
+        .and_then(|p| ws.read_artifact(p).ok())
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        .unwrap_or_default();
+
+    // The hash is of the text as fetched, so a mismatch means it was edited
+    // in the app; a refresh would throw those edits away. Sources ingested
+    // before hashes were kept may have been edited too; an update keeps
+    // their current text as a version, so nothing is lost.
+    if content_hash.is_some_and(|hash| hash != format!("{:x}", Sha256::digest(stored.as_bytes()))) {
+        let patch = json!({
+            "checked_at": checked_at,
+            "refresh_error": "Edited since it was fetched, so not refreshed"
+        });
+        patch_metadata(state, &node.id, patch, None).await?;
+        return Ok(("edited", 0, 0));
+    }
+
+    let metadata = node.metadata.clone().unwrap_or_default();
+    let field = |key: &str| metadata.get(key).and_then(Value::as_str).map(str::to_string);
+    let url = field("url").ok_or_else(|| format!("{} has no URL", node.title))?;
+    let validators = Validators {
+        etag: field("etag"),
+        last_modified: field("last_modified"),
+    };
+
+    let fetched = match pages.fetch(&url, &validators).await {
+        Ok(fetched) => fetched,
+        Err(FetchError::NotModified) => {
+            let patch = json!({ "checked_at": checked_at, "refresh_error": null });
+            patch_metadata(state, &node.id, patch, None).await?;
+            return Ok(("not_modified", 0, 0));
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+    let converted = convert_fetched(&url, fetched).await?;
+
+    // Compare against the stored text rather than the hash, which sources
+    // ingested before hashes were kept don't have
+    if stored == converted.document.markdown {
+        let patch = json!({
+            "checked_at": checked_at,
+            "fetched_at": converted.metadata["fetched_at"],
+            "etag": converted.metadata.get("etag"),
+            "last_modified": converted.metadata.get("last_modified"),
+            "refresh_error": null
+        });
+        patch_metadata(state, &node.id, patch, Some(converted.content_hash)).await?;
+        return Ok(("unchanged", 0, 0));
+    }
+
+    let (added_lines, removed_lines) = count_changes(&TextDiff::from_lines(&stored, &converted.document.markdown));
+    let FetchedDocument { document, bytes, content_hash, mut metadata, .. } = converted;
+    // The node stays known by the address it was ingested under
+    if let Some(canonical_url) = field("canonical_url") {
+        metadata["canonical_url"] = json!(canonical_url);
+    }
+    metadata["checked_at"] = json!(checked_at);
+    store_document(state, settings, document, &bytes, metadata, Some(content_hash), Some(&node))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(("updated", added_lines, removed_lines))
+}
+
+fn count_changes<'a>(diff: &TextDiff<'a, 'a, 'a, str>) -> (usize, usize) {
+    diff.iter_all_changes().fold((0, 0), |(added, removed), change| match change.tag() {
+        ChangeTag::Insert => (added + 1, removed),
+        ChangeTag::Delete => (added, removed + 1),
+        ChangeTag::Equal => (added, removed),
+    })
+}
+
+/// Merge `patch` into a node's metadata; null values remove keys.
+async fn patch_metadata(state: &DbState, id: &str, patch: Value, content_hash: Option<String>) -> Result<(), String> {
+    let id = id.to_string();
+    state.run(move |conn| {
+        conn.execute(
+            "UPDATE nodes SET metadata = json_patch(metadata, ?2), content_hash = coalesce(?3, content_hash)
+             WHERE id = ?1 AND json_valid(metadata)",
+            params![id, patch.to_string(), content_hash],
+        ).map_err(|e| e.to_string())?;
+        Ok(())
+    }).await
+}
+
+/// Live source nodes ingested from a URL with their content hashes, oldest
+/// first; only `id` if given.
+fn web_sources(conn: &Connection, id: Option<&str>) -> rusqlite::Result<Vec<(Node, Option<String>)>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM nodes
+         WHERE node_type = 'source' AND deleted_at IS NULL
+           AND json_valid(metadata) AND json_type(metadata, '$.url') = 'text'
+           AND (?1 IS NULL OR id = ?1)
+         ORDER BY created_at",
+        NODE_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![id], node_from_row)?;
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::ingest::ingest_fetched;
+    use crate::db::testing::{open_state, TestState};
+    use crate::scraper::fetcher::Body;
+    use reqwest::Url;
+
+    const URL: &str = "https://docs.test/ownership";
+
+    /// A server answering with `html`, or "not modified" without any.
+    struct Stub(Option<String>);
+
+    #[async_trait]
+    impl SourceFetcher for Stub {
+        async fn fetch(&self, url: &str, _validators: &Validators) -> Result<Fetched, FetchError> {
+            let html = self.0.clone().ok_or(FetchError::NotModified)?;
+            Ok(Fetched {
+                final_url: Url::parse(url).unwrap(),
+                content_type: "text/html".to_string(),
+                validators: Validators::default(),
+                body: Body::Html(html),
+            })
+        }
+    }
+
+    fn page(text: &str) -> String {
+        format!(
+            "<html><head><title>Ownership</title></head><body><article><h1>Ownership</h1><p>{}</p></article></body></html>",
+            text
+        )
+    }
+
+    const FIRST: &str = "Every value has a single owner.";
+    const SECOND: &str = "Every value has a single owner, and borrowing lends it out.";
+
+    /// A web source ingested from `FIRST`.
+    async fn ingested(state: &TestState) -> Node {
+        let settings = state.run(|conn| settings::load(conn).map_err(|e| e.to_string())).await.unwrap();
+        let fetched = Stub(Some(page(FIRST))).fetch(URL, &Validators::default()).await.unwrap();
+        ingest_fetched(state, &settings, URL, fetched).await.unwrap()
+    }
+
+    /// The source `id` with its content hash.
+    async fn source(state: &TestState, id: &str) -> (Node, Option<String>) {
+        let id = id.to_string();
+        let found = state.run(move |conn| web_sources(conn, Some(&id)).map_err(|e| e.to_string())).await;
+        found.unwrap().pop().unwrap()
+    }
+
+    /// Refresh `id` from `stub`, returning the outcome and the node after.
+    async fn refresh_with(state: &TestState, id: &str, stub: Stub) -> (RefreshResult, Node) {
+        let settings = state.run(|conn| settings::load(conn).map_err(|e| e.to_string())).await.unwrap();
+        let (node, hash) = source(state, id).await;
+        let result = refresh(state, &settings, &stub, node, hash).await;
+        (result, source(state, id).await.0)
+    }
+
+    fn text_of(node: &Node) -> String {
+        let ws = Workspace::new().unwrap();
+        String::from_utf8(ws.read_artifact(node.content_path.as_deref().unwrap()).unwrap()).unwrap()
+    }
+
+    fn kept_versions(node: &Node) -> Vec<String> {
+        let ws = Workspace::new().unwrap();
+        node.metadata.as_ref().unwrap()["previous_versions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|v| String::from_utf8(ws.read_artifact(v["content_path"].as_str().unwrap()).unwrap()).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn unmodified_sources_are_left_as_they_are() {
+        let state = open_state();
+        let source = ingested(&state).await;
+
+        let (result, node) = refresh_with(&state, &source.id, Stub(None)).await;
+        assert_eq!(result.status, "not_modified");
+        assert_eq!(node.updated_at, source.updated_at);
+        assert!(node.metadata.unwrap().get("checked_at").is_some());
+
+        let (result, node) = refresh_with(&state, &source.id, Stub(Some(page(FIRST)))).await;
+        assert_eq!(result.status, "unchanged");
+        assert!(kept_versions(&node).is_empty());
+    }
+
+    #[tokio::test]
+    async fn changed_sources_are_updated_keeping_the_old_text() {
+        let state = open_state();
+        let source = ingested(&state).await;
+        let before = text_of(&source);
+
+        let (result, node) = refresh_with(&state, &source.id, Stub(Some(page(SECOND)))).await;
+        assert_eq!(result.status, "updated");
+        assert!(result.added_lines > 0 && result.removed_lines > 0);
+        assert!(text_of(&node).contains("borrowing lends it out"));
+        assert_eq!(kept_versions(&node), [before]);
+    }
+
+    #[tokio::test]
+    async fn sources_edited_in_the_app_are_not_refreshed() {
+        let state = open_state();
+        let source = ingested(&state).await;
+        let ws = Workspace::new().unwrap();
+        let edited = format!("{}\n\nMy own notes.", text_of(&source));
+        std::fs::write(ws.artifacts.join(source.content_path.as_deref().unwrap()), &edited).unwrap();
+
+        let (result, node) = refresh_with(&state, &source.id, Stub(Some(page(SECOND)))).await;
+        assert_eq!(result.status, "edited");
+        assert_eq!(text_of(&node), edited);
+    }
+
+    #[tokio::test]
+    async fn sources_without_a_hash_keep_their_text_when_updated() {
+        let state = open_state();
+        let source = ingested(&state).await;
+        let ws = Workspace::new().unwrap();
+        let edited = format!("{}\n\nMy own notes.", text_of(&source));
+        std::fs::write(ws.artifacts.join(source.content_path.as_deref().unwrap()), &edited).unwrap();
+        let id = source.id.clone();
+        state
+            .run(move |conn| {
+                conn.execute("UPDATE nodes SET content_hash = NULL WHERE id = ?1", params![id])
+                    .map_err(|e| e.to_string())
+            })
+            .await
+            .unwrap();
+
+        // Edits can't be told apart from the page changing, so the text
+        // they may be in is kept as a version
+        let (result, node) = refresh_with(&state, &source.id, Stub(Some(page(SECOND)))).await;
+        assert_eq!(result.status, "updated");
+        assert_eq!(kept_versions(&node), [edited]);
+    }
+}
@@ -0,0 +1,469 @@
+use tauri::{command, AppHandle, Emitter, Manager};
+use crate::ai::embeddings::{self, BatchOptions, Embedder};
+use crate::db::{chunks, vec, DbState};
+use crate::fs_manager::Workspace;
+use crate::models::index_status;
+use crate::scraper::chunker::{chunk_text, ChunkOptions};
+use crate::settings;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+pub const REINDEX_PROGRESS_EVENT: &str = "reindex-progress";
+pub const NODE_INDEX_EVENT: &str = "node-index-status";
+
+/// How long a node must go without edits before it is re-indexed.
+const EDIT_DEBOUNCE: Duration = Duration::from_millis(1500);
+
+static REINDEX_RUNNING: AtomicBool = AtomicBool::new(false);
+
+static NEXT_EDIT: AtomicU64 = AtomicU64::new(1);
+
+/// Latest pending re-index per node. A scheduled re-index only runs if no
+/// newer edit replaced it while it waited.
+#[derive(Default)]
+pub struct PendingNodeIndexes(Mutex<HashMap<String, u64>>);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeIndexEvent {
+    /// None when the failure isn't about one node, e.g. listing stale ones
+    pub node_id: Option<String>,
+    /// One of the `index_status` values
+    pub status: String,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReindexProgress {
+    pub job_id: String,
+    /// "running", "done" or "error"
+    pub status: String,
+    pub model: String,
+    pub processed: usize,
+    pub total: usize,
+    pub failed: Vec<String>,
+    pub message: Option<String>,
+}
+
+/// Clears the running flag even if the job panics.
+struct RunningGuard;
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        REINDEX_RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Rebuild the vector index of every node with the configured embedding model.
+///
+/// Returns a job id immediately; progress is reported through
+/// `reindex-progress` events. Vectors are written to a staging table and
+/// only swapped in once every node has been processed, so search keeps
+/// working on the old index meanwhile.
+#[command]
+pub async fn reindex_embeddings(app: AppHandle) -> Result<String, String> {
+    let settings = app
+        .state::<DbState>()
+        .run(|conn| settings::load(conn).map_err(|e| e.to_string()))
+        .await?;
+    let embedder = embeddings::from_settings(&settings)?;
+
+    if REINDEX_RUNNING.swap(true, Ordering::SeqCst) {
+        return Err("A re-index is already running".to_string());
+    }
+    let guard = RunningGuard;
+
+    let job_id = Uuid::new_v4().to_string();
+    let job = job_id.clone();
+    tauri::async_runtime::spawn(async move {
+        let _guard = guard;
+        run_reindex(&app, &job, embedder, settings.chunk_options()).await;
+    });
+
+    Ok(job_id)
+}
+
+async fn run_reindex(app: &AppHandle, job_id: &str, embedder: Arc<dyn Embedder>, options: ChunkOptions) {
+    let mut progress = ReindexProgress {
+        job_id: job_id.to_string(),
+        status: "running".to_string(),
+        model: embedder.model_id().to_string(),
+        processed: 0,
+        total: 0,
+        failed: Vec::new(),
+        message: None,
+    };
+
+    let result = reindex_all(app, embedder, &options, &mut progress).await;
+    match result {
+        Ok(()) => progress.status = "done".to_string(),
+        Err(e) => {
+            progress.status = "error".to_string();
+            progress.message = Some(e);
+        }
+    }
+    let _ = app.emit(REINDEX_PROGRESS_EVENT, progress);
+}
+
+async fn reindex_all(
+    app: &AppHandle,
+    embedder: Arc<dyn Embedder>,
+    options: &ChunkOptions,
+    progress: &mut ReindexProgress,
+) -> Result<(), String> {
+    let state = app.state::<DbState>();
+    let ws = Workspace::new().map_err(|e| e.to_string())?;
+
+    let model_id = embedder.model_id().to_string();
+    let dimension = embedder.dimension();
+    let (nodes, staging) = state.run(move |conn| {
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, title, content_path, updated_at FROM nodes
+                 WHERE content_path IS NOT NULL AND deleted_at IS NULL",
+            )
+            .map_err(|e| e.to_string())?;
+        let nodes = stmt
+            .query_map([], |row| {
+                Ok(ReindexTarget {
+                    node_id: row.get(0)?,
+                    title: row.get(1)?,
+                    content_path: row.get(2)?,
+                    version: row.get(3)?,
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        let staging = vec::create_staging_index(conn, &model_id, dimension)
+            .map_err(|e| e.to_string())?;
+        Ok((nodes, staging))
+    }).await?;
+
+    progress.total = nodes.len();
+    let _ = app.emit(REINDEX_PROGRESS_EVENT, progress.clone());
+
+    let result = async {
+        for node in &nodes {
+            if let Err(e) = reindex_node(&state, &ws, &embedder, options, &staging, node).await {
+                progress.failed.push(node.node_id.clone());
+                progress.message = Some(format!("{}: {}", node.title, e));
+            }
+            progress.processed += 1;
+            let _ = app.emit(REINDEX_PROGRESS_EVENT, progress.clone());
+        }
+
+        let staging = staging.clone();
+        let stale = state.run(move |conn| activate_staging(conn, &staging).map_err(|e| e.to_string())).await?;
+        for node_id in stale {
+            schedule_node_index(app, &node_id);
+        }
+        Ok(())
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = state.run(move |conn| vec::drop_staging_index(conn, &staging).map_err(|e| e.to_string())).await;
+    }
+    result
+}
+
+/// Swap `staging` in for its model's index. Nodes that were ingested or
+/// edited while it was built, or whose re-embedding failed, first get the
+/// vectors the live index holds for their current chunks; any still
+/// missing some are marked stale, and returned to be indexed again.
+fn activate_staging(conn: &mut Connection, staging: &vec::VecIndex) -> rusqlite::Result<Vec<String>> {
+    let tx = conn.transaction()?;
+    let live = vec::find_index(&tx, &staging.model_id, staging.dimension)?;
+
+    let mut stale = Vec::new();
+    for node_id in vec::out_of_date_nodes(&tx, staging)? {
+        vec::delete_index_vectors(&tx, staging, &node_id)?;
+        let copied = match &live {
+            Some(live) => vec::copy_node_vectors(&tx, live, staging, &node_id)?,
+            None => 0,
+        };
+        if copied < chunks::for_node(&tx, &node_id)?.len() {
+            tx.execute(
+                "UPDATE nodes SET index_status = ?1 WHERE id = ?2",
+                params![index_status::STALE, node_id],
+            )?;
+            stale.push(node_id);
+        }
+    }
+
+    vec::activate_index(&tx, staging)?;
+    tx.commit()?;
+    Ok(stale)
+}
+
+/// A node to re-embed, as it was when the re-index started.
+struct ReindexTarget {
+    node_id: String,
+    title: String,
+    content_path: String,
+    /// `updated_at` when listed; a node edited since is left to its own
+    /// re-index
+    version: String,
+}
+
+async fn reindex_node(
+    state: &DbState,
+    ws: &Workspace,
+    embedder: &Arc<dyn Embedder>,
+    options: &ChunkOptions,
+    staging: &vec::VecIndex,
+    node: &ReindexTarget,
+) -> Result<(), String> {
+    let bytes = ws.read_artifact(&node.content_path).map_err(|e| e.to_string())?;
+    let text = String::from_utf8_lossy(&bytes).into_owned();
+
+    let spans = chunk_text(&text, options);
+    let texts: Vec<String> = spans.iter().map(|s| s.text.clone()).collect();
+    let embedded = embeddings::embed_all(embedder.clone(), &texts, &BatchOptions::default()).await?;
+
+    let (staging, node_id, version) = (staging.clone(), node.node_id.clone(), node.version.clone());
+    state.run(move |conn| {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        // An edit since listing keeps the node stale; activating the
+        // staging index then schedules it
+        let current: Option<(String, String)> = tx
+            .query_row(
+                "SELECT title, updated_at FROM nodes WHERE id = ?1",
+                params![node_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let title = match current {
+            Some((title, updated_at)) if updated_at == version => title,
+            _ => return Ok(()),
+        };
+
+        // Unchanged chunks keep their ids and live vectors until the swap;
+        // vectors of replaced ones would point at no chunk, so they go now
+        let chunk_ids = chunks::sync(&tx, &node_id, &spans).map_err(|e| e.to_string())?;
+        for (chunk_id, embedding) in chunk_ids.iter().zip(&embedded) {
+            vec::insert_embedding(&tx, &staging, *chunk_id, &node_id, embedding).map_err(|e| e.to_string())?;
+        }
+        // Edited notes may never have reached FTS, refresh it from the artifact too
+        vec::update_fts(&tx, &node_id, &title, &text).map_err(|e| e.to_string())?;
+        tx.execute(
+            "UPDATE nodes SET index_status = ?1 WHERE id = ?2",
+            params![index_status::INDEXED, node_id],
+        ).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    }).await
+}
+
+/// Re-index `node_id` in the background once edits to it settle.
+pub fn schedule_node_index(app: &AppHandle, node_id: &str) {
+    let ticket = NEXT_EDIT.fetch_add(1, Ordering::SeqCst);
+    if let Ok(mut pending) = app.state::<PendingNodeIndexes>().0.lock() {
+        pending.insert(node_id.to_string(), ticket);
+    }
+
+    let app = app.clone();
+    let node_id = node_id.to_string();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(EDIT_DEBOUNCE).await;
+
+        // A newer edit has its own task waiting
+        let latest = match app.state::<PendingNodeIndexes>().0.lock() {
+            Ok(mut pending) if pending.get(&node_id) == Some(&ticket) => {
+                pending.remove(&node_id);
+                true
+            }
+            _ => false,
+        };
+        if latest {
+            index_edited_node(&app, &node_id).await;
+        }
+    });
+}
+
+/// Queue every live node whose index is not up to date, e.g. after a crash
+/// interrupted a re-index.
+pub fn schedule_stale_nodes(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let ids = app.state::<DbState>().run(|conn| {
+            let mut stmt = conn
+                .prepare("SELECT id FROM nodes WHERE index_status != ?1 AND deleted_at IS NULL")
+                .map_err(|e| e.to_string())?;
+            let ids = stmt
+                .query_map(params![index_status::INDEXED], |row| row.get::<_, String>(0))
+                .map_err(|e| e.to_string())?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?;
+            Ok(ids)
+        }).await;
+
+        match ids {
+            Ok(ids) => {
+                for id in ids {
+                    schedule_node_index(&app, &id);
+                }
+            }
+            Err(e) => {
+                emit_index_failure(&app, None, format!("Failed to list stale nodes: {}", e));
+            }
+        }
+    });
+}
+
+async fn index_edited_node(app: &AppHandle, node_id: &str) {
+    let state = app.state::<DbState>();
+
+    // The edit this run indexes; nothing is written if a newer one lands meanwhile
+    let id = node_id.to_string();
+    let snapshot = state.run(move |conn| {
+        conn.query_row(
+            "SELECT content_path, updated_at FROM nodes WHERE id = ?1",
+            params![id],
+            |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, String>(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+    }).await;
+
+    let (content_path, version) = match snapshot {
+        Ok(Some((Some(content_path), version))) => (content_path, version),
+        // Purged, or nothing to index
+        Ok(_) => return,
+        Err(e) => {
+            emit_index_failure(app, Some(node_id), e);
+            return;
+        }
+    };
+
+    set_index_status(app, node_id, &version, index_status::INDEXING, None).await;
+    match refresh_node_index(&state, node_id, &content_path, &version).await {
+        Ok(true) => emit_index_status(app, node_id, index_status::INDEXED, None),
+        Ok(false) => {}
+        Err(e) => set_index_status(app, node_id, &version, index_status::FAILED, Some(e)).await,
+    }
+}
+
+/// Bring the chunks, vectors and full-text entry of an edited node in line
+/// with its artifact, replacing only that node's rows. Returns false,
+/// writing nothing, if the node was edited again after `version`.
+async fn refresh_node_index(
+    state: &DbState,
+    node_id: &str,
+    content_path: &str,
+    version: &str,
+) -> Result<bool, String> {
+    let ws = Workspace::new().map_err(|e| e.to_string())?;
+    let bytes = ws.read_artifact(content_path).map_err(|e| e.to_string())?;
+    let text = String::from_utf8_lossy(&bytes).into_owned();
+
+    let settings = state.run(|conn| settings::load(conn).map_err(|e| e.to_string())).await?;
+    let spans = chunk_text(&text, &settings.chunk_options());
+    let embedder = embeddings::from_settings(&settings)?;
+    let model_id = embedder.model_id().to_string();
+    let dimension = embedder.dimension();
+
+    // Edits that leave every chunk as it was need no embedding
+    let (id, current_spans, model) = (node_id.to_string(), spans.clone(), model_id.clone());
+    let up_to_date = state.run(move |conn| {
+        let existing = chunks::for_node(conn, &id).map_err(|e| e.to_string())?;
+        if !chunks::same_spans(&existing, &current_spans) {
+            return Ok(false);
+        }
+        match vec::find_index(conn, &model, dimension).map_err(|e| e.to_string())? {
+            Some(index) => Ok(vec::count_node_vectors(conn, &index, &id).map_err(|e| e.to_string())? == existing.len()),
+            None => Ok(existing.is_empty()),
+        }
+    }).await?;
+
+    let embedded = if up_to_date {
+        None
+    } else {
+        let texts: Vec<String> = spans.iter().map(|s| s.text.clone()).collect();
+        Some(embeddings::embed_all(embedder.clone(), &texts, &BatchOptions::default()).await?)
+    };
+
+    let (node_id, version) = (node_id.to_string(), version.to_string());
+    state.run(move |conn| {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let current: Option<(String, String)> = tx
+            .query_row(
+                "SELECT title, updated_at FROM nodes WHERE id = ?1",
+                params![node_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let title = match current {
+            Some((title, updated_at)) if updated_at == version => title,
+            _ => return Ok(false),
+        };
+
+        if let Some(embedded) = embedded {
+            let index = vec::ensure_index(&tx, &model_id, dimension).map_err(|e| e.to_string())?;
+            // New chunks get new ids, so vectors of every model for the old ones go
+            vec::delete_node_vectors(&tx, &node_id).map_err(|e| e.to_string())?;
+            let chunk_ids = chunks::replace(&tx, &node_id, &spans).map_err(|e| e.to_string())?;
+            for (chunk_id, embedding) in chunk_ids.iter().zip(&embedded) {
+                vec::insert_embedding(&tx, &index, *chunk_id, &node_id, embedding).map_err(|e| e.to_string())?;
+            }
+        }
+        vec::update_fts(&tx, &node_id, &title, &text).map_err(|e| e.to_string())?;
+        tx.execute(
+            "UPDATE nodes SET index_status = ?1 WHERE id = ?2",
+            params![index_status::INDEXED, node_id],
+        ).map_err(|e| e.to_string())?;
+
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(true)
+    }).await
+}
+
+/// Record `status` unless the node was edited after `version`.
+async fn set_index_status(app: &AppHandle, node_id: &str, version: &str, status: &str, error: Option<String>) {
+    let (id, version, value) = (node_id.to_string(), version.to_string(), status.to_string());
+    let updated = app.state::<DbState>().run(move |conn| {
+        conn.execute(
+            "UPDATE nodes SET index_status = ?1 WHERE id = ?2 AND updated_at = ?3",
+            params![value, id, version],
+        ).map_err(|e| e.to_string())
+    }).await;
+
+    match updated {
+        Ok(0) => {}
+        Ok(_) => emit_index_status(app, node_id, status, error),
+        Err(e) => {
+            // Whatever went wrong with indexing is still worth reporting
+            let message = match error {
+                Some(error) => format!("{} (and recording the failure failed: {})", error, e),
+                None => format!("Failed to record index status: {}", e),
+            };
+            emit_index_failure(app, Some(node_id), message);
+        }
+    }
+}
+
+fn emit_index_status(app: &AppHandle, node_id: &str, status: &str, error: Option<String>) {
+    let _ = app.emit(NODE_INDEX_EVENT, NodeIndexEvent {
+        node_id: Some(node_id.to_string()),
+        status: status.to_string(),
+        error,
+    });
+}
+
+/// Report a background indexing error that left nothing recorded in the
+/// database, so the frontend still hears of it.
+fn emit_index_failure(app: &AppHandle, node_id: Option<&str>, error: String) {
+    let _ = app.emit(NODE_INDEX_EVENT, NodeIndexEvent {
+        node_id: node_id.map(str::to_string),
+        status: index_status::FAILED.to_string(),
+        error: Some(error),
+    });
+}
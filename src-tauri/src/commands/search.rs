@@ -1,9 +1,11 @@
 use tauri::command;
 use crate::db::DbState;
-use crate::ai::embeddings::{embed_text, EmbeddingProvider};
+use crate::ai::embeddings::{self, embed_query, BatchOptions};
+use crate::db::vec;
+use crate::settings;
 use serde::{Serialize, Deserialize};
-use rusqlite::params;
-use std::collections::HashMap;
+use rusqlite::{params, Connection};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SearchResult {
@@ -13,86 +15,81 @@ pub struct SearchResult {
     pub snippet: String,
 }
 
+/// Results of a search. In hybrid mode a failed semantic search leaves
+/// the keyword results, with the reason semantic results are missing.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResponse {
+    pub results: Vec<SearchResult>,
+    pub semantic_error: Option<String>,
+}
+
+/// Nodes a semantic search returns at most.
+const SEMANTIC_LIMIT: usize = 20;
+
+/// Most neighbours sqlite-vec returns for one query.
+const MAX_NEIGHBOURS: usize = 4096;
+
 #[command]
 pub async fn search_nodes(
     state: tauri::State<'_, DbState>,
     query: String,
     mode: String, // "fuzzy", "semantic", "hybrid"
-) -> Result<Vec<SearchResult>, String> {
-    let conn = state.get_connection().map_err(|e| e.to_string())?;
+) -> Result<SearchResponse, String> {
+    search(&state, query, mode).await
+}
 
+async fn search(state: &DbState, query: String, mode: String) -> Result<SearchResponse, String> {
     let mut results: HashMap<String, SearchResult> = HashMap::new();
 
     // 1. Fuzzy Search (FTS5)
     if mode == "fuzzy" || mode == "hybrid" {
-        let mut stmt = conn.prepare(
-            "SELECT id, title, snippet(nodes_fts, 2, '<b>', '</b>', '...', 10) as snippet, rank 
-             FROM nodes_fts 
-             WHERE nodes_fts MATCH ? 
-             ORDER BY rank 
-             LIMIT 20"
-        ).map_err(|e| e.to_string())?;
-        
-        let fuzzy_rows = stmt.query_map(params![query], |row| {
-            Ok(SearchResult {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                score: row.get::<_, f64>(3)? as f32, // FTS rank is usually negative (lower is better) or positive depending on config. standard FTS5 bm25 is negative.
-                snippet: row.get(2)?,
-            })
-        }).map_err(|e| e.to_string())?;
-
-        for row in fuzzy_rows {
-            if let Ok(r) = row {
-                results.insert(r.id.clone(), r);
-            }
+        let query = query.clone();
+        let fuzzy_rows = state.run(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT nodes_fts.id, nodes_fts.title, snippet(nodes_fts, 2, '<b>', '</b>', '...', 10) as snippet, rank 
+                 FROM nodes_fts 
+                 JOIN nodes n ON n.id = nodes_fts.id AND n.deleted_at IS NULL
+                 WHERE nodes_fts MATCH ? 
+                 ORDER BY rank 
+                 LIMIT 20"
+            ).map_err(|e| e.to_string())?;
+
+            let rows = stmt.query_map(params![query], |row| {
+                Ok(SearchResult {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    score: row.get::<_, f64>(3)? as f32, // FTS rank is usually negative (lower is better) or positive depending on config. standard FTS5 bm25 is negative.
+                    snippet: row.get(2)?,
+                })
+            }).map_err(|e| e.to_string())?;
+            Ok(rows.filter_map(Result::ok).collect::<Vec<_>>())
+        }).await?;
+
+        for r in fuzzy_rows {
+            results.insert(r.id.clone(), r);
         }
     }
 
     // 2. Semantic Search (Vector)
+    let mut semantic_error = None;
     if mode == "semantic" || mode == "hybrid" {
-        // Embed query
-        // Assume default provider (Ollama) for search query embedding if not specified?
-        // Or re-use the same provider config? For now, hardcode Ollama default or use env.
-        let provider = EmbeddingProvider::Ollama { model: "nomic-embed-text".to_string() };
-        let embedding = embed_text(&query, &provider).await?;
-        let embedding_json = serde_json::to_string(&embedding).map_err(|e| e.to_string())?;
-
-        // Vector search query
-        // Assuming `vec_distance_cosine` or `vec_top_k` depending on sqlite-vec usage.
-        // Standard sqlite-vec usage:
-        // SELECT rowid, distance FROM nodes_vec WHERE embedding MATCH ? ORDER BY distance LIMIT 20
-        // We need to join with `nodes` to get title/content.
-        // `nodes_vec` has `node_id` which links to `nodes.id`.
-        
-        let mut stmt = conn.prepare(
-            "SELECT n.id, n.title, v.distance 
-             FROM nodes_vec v
-             JOIN nodes n ON v.node_id = n.id
-             WHERE v.embedding MATCH ?
-             ORDER BY v.distance
-             LIMIT 20"
-        ).map_err(|e| e.to_string())?;
-
-        let vector_rows = stmt.query_map(params![embedding_json], |row| {
-            Ok(SearchResult {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                score: 1.0 - (row.get::<_, f32>(2)?), // Convert distance to similarity score
-                snippet: "".to_string(), // Vector search doesn't give snippets easily without retrieving content
-            })
-        }).map_err(|e| e.to_string())?;
-
-        for row in vector_rows {
-            if let Ok(mut r) = row {
-                // If existing result from fuzzy, merge/boost?
-                if let Some(existing) = results.get(&r.id) {
-                    // Simple merge: average score or take max?
-                    // FTS rank is weird, let's just favor vector score for sorting if hybrid
-                    r.snippet = existing.snippet.clone(); 
+        match semantic_search(state, &query).await {
+            Ok(vector_rows) => {
+                for mut r in vector_rows {
+                    // If existing result from fuzzy, merge/boost?
+                    if let Some(existing) = results.get(&r.id) {
+                        // Simple merge: average score or take max?
+                        // FTS rank is weird, let's just favor vector score for sorting if hybrid
+                        r.snippet = existing.snippet.clone(); 
+                    }
+                    results.insert(r.id.clone(), r);
                 }
-                results.insert(r.id.clone(), r);
             }
+            // No index for the model or an unreachable embedder still
+            // leaves the keyword results worth showing
+            Err(e) if mode == "hybrid" => semantic_error = Some(e),
+            Err(e) => return Err(e),
         }
     }
 
@@ -100,5 +97,162 @@ pub async fn search_nodes(
     // Sort by score descending
     final_results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
     
-    Ok(final_results)
+    Ok(SearchResponse { results: final_results, semantic_error })
+}
+
+async fn semantic_search(state: &DbState, query: &str) -> Result<Vec<SearchResult>, String> {
+    // Embed query with the configured model, the one new vectors are stored with
+    let settings = state.run(|conn| settings::load(conn).map_err(|e| e.to_string())).await?;
+    let embedder = embeddings::from_settings(&settings)?;
+    let embedding = embed_query(embedder.as_ref(), query, &BatchOptions::default()).await?;
+
+    let model_id = embedder.model_id().to_string();
+    let dimension = embedder.dimension();
+    state
+        .run(move |conn| semantic_results(conn, &model_id, dimension, &embedding))
+        .await
+}
+
+/// Nodes nearest to `embedding` in the index of `model_id`, best first,
+/// one result per node.
+fn semantic_results(
+    conn: &Connection,
+    model_id: &str,
+    dimension: usize,
+    embedding: &[f32],
+) -> Result<Vec<SearchResult>, String> {
+    // Only vectors produced by the same model are comparable
+    let index = vec::require_index(conn, model_id, dimension)?;
+    let mut title_stmt = conn.prepare("SELECT title FROM nodes WHERE id = ?1 AND deleted_at IS NULL").map_err(|e| e.to_string())?;
+
+    // Trashed nodes and further chunks of a node take up neighbours
+    // without giving results, so widen the search until enough are left
+    let mut k = SEMANTIC_LIMIT * 2;
+    loop {
+        let hits = vec::search(conn, &index, embedding, k).map_err(|e| e.to_string())?;
+        let exhausted = hits.len() < k || k == MAX_NEIGHBOURS;
+
+        let mut seen = HashSet::new();
+        let mut vector_rows = Vec::new();
+        for hit in hits {
+            if vector_rows.len() == SEMANTIC_LIMIT {
+                break;
+            }
+            // Several chunks of a node can match; hits are sorted, so keep the first
+            if !seen.insert(hit.node_id.clone()) {
+                continue;
+            }
+            let row = title_stmt.query_row(params![hit.node_id], |row| {
+                Ok(SearchResult {
+                    id: hit.node_id.clone(),
+                    title: row.get(0)?,
+                    score: 1.0 - hit.distance, // Convert distance to similarity score
+                    snippet: "".to_string(), // Vector search doesn't give snippets easily without retrieving content
+                })
+            });
+            if let Ok(r) = row {
+                vector_rows.push(r);
+            }
+        }
+
+        if vector_rows.len() == SEMANTIC_LIMIT || exhausted {
+            return Ok(vector_rows);
+        }
+        k = (k * 4).min(MAX_NEIGHBOURS);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::embeddings::{Embedder, HashEmbedder};
+    use crate::db::testing::{add_document, open_db, open_state};
+    use crate::settings::AppSettings;
+
+    #[test]
+    fn ingested_documents_are_found_by_meaning() {
+        let mut conn = open_db();
+        let embedder = HashEmbedder::default();
+        let rust = add_document(
+            &mut conn,
+            &embedder,
+            "Rust ownership",
+            "Rust ownership rules: every value has a single owner, and borrowing lets code use a value without taking ownership.",
+        );
+        let bread = add_document(
+            &mut conn,
+            &embedder,
+            "Sourdough",
+            "Sourdough bread rises with a starter of flour and water; bake the loaf in a hot oven.",
+        );
+
+        let query = embedder.embed_sync("how does borrowing and ownership work in rust");
+        let results = semantic_results(&conn, embedder.model_id(), embedder.dimension(), &query).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].id, rust);
+        assert_eq!(results[0].title, "Rust ownership");
+        assert!(results[0].score > results[1].score);
+
+        let query = embedder.embed_sync("baking a sourdough loaf");
+        let results = semantic_results(&conn, embedder.model_id(), embedder.dimension(), &query).unwrap();
+        assert_eq!(results[0].id, bread);
+    }
+
+    #[test]
+    fn trashed_documents_are_not_found() {
+        let mut conn = open_db();
+        let embedder = HashEmbedder::default();
+        let id = add_document(&mut conn, &embedder, "Gone", "A note that was moved to the trash.");
+        crate::db::trash::soft_delete(&conn, &id).unwrap();
+
+        let query = embedder.embed_sync("note moved to the trash");
+        let results = semantic_results(&conn, embedder.model_id(), embedder.dimension(), &query).unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn searching_without_an_index_is_an_error() {
+        let conn = open_db();
+        let embedder = HashEmbedder::new(16);
+        let query = embedder.embed_sync("anything");
+        let error = semantic_results(&conn, embedder.model_id(), embedder.dimension(), &query).unwrap_err();
+        assert!(error.contains("hash-16"), "{}", error);
+    }
+
+    #[test]
+    fn trashed_documents_do_not_crowd_out_live_ones() {
+        let mut conn = open_db();
+        let embedder = HashEmbedder::default();
+        for i in 0..SEMANTIC_LIMIT * 3 {
+            let id = add_document(&mut conn, &embedder, &format!("Draft {}", i), "Rust ownership and borrowing notes.");
+            crate::db::trash::soft_delete(&conn, &id).unwrap();
+        }
+        let live = add_document(&mut conn, &embedder, "Kept", "Notes on ownership in Rust programs.");
+
+        let query = embedder.embed_sync("Rust ownership and borrowing notes.");
+        let results = semantic_results(&conn, embedder.model_id(), embedder.dimension(), &query).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, live);
+    }
+
+    #[tokio::test]
+    async fn hybrid_search_falls_back_to_keywords_without_semantic_results() {
+        let state = open_state();
+        let id = {
+            let mut conn = state.get_connection().unwrap();
+            let id = add_document(&mut conn, &HashEmbedder::default(), "Sourdough", "Bread rises with a starter.");
+            // A model nothing was indexed with
+            let settings = AppSettings { embedding_dimension: 16, ..settings::load(&conn).unwrap() };
+            settings::save(&conn, &settings).unwrap();
+            id
+        };
+
+        let response = search(&state, "starter".to_string(), "hybrid".to_string()).await.unwrap();
+        assert_eq!(response.results.len(), 1);
+        assert_eq!(response.results[0].id, id);
+        assert!(response.semantic_error.unwrap().contains("hash-16"));
+
+        // Asked for explicitly, semantic search still reports the failure
+        assert!(search(&state, "starter".to_string(), "semantic".to_string()).await.is_err());
+    }
 }
@@ -0,0 +1,153 @@
+use tauri::{command, AppHandle, State};
+use crate::db::{sessions, vec, DbState};
+use crate::fs_manager::Workspace;
+use crate::commands::reindex::schedule_node_index;
+use crate::models::{index_status, ChatSession, ChatSessionMessage, Node};
+use serde::Serialize;
+use serde_json::json;
+use uuid::Uuid;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatSessionDetail {
+    pub session: ChatSession,
+    pub messages: Vec<ChatSessionMessage>,
+}
+
+#[command]
+pub fn list_chat_sessions(state: State<'_, DbState>) -> Result<Vec<ChatSession>, String> {
+    let conn = state.get_connection().map_err(|e| e.to_string())?;
+    sessions::list(&conn).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn create_chat_session(state: State<'_, DbState>, title: Option<String>) -> Result<ChatSession, String> {
+    let conn = state.get_connection().map_err(|e| e.to_string())?;
+    let title = title
+        .filter(|t| !t.trim().is_empty())
+        .unwrap_or_else(|| sessions::DEFAULT_TITLE.to_string());
+    sessions::create(&conn, title.trim()).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn open_chat_session(state: State<'_, DbState>, id: String) -> Result<ChatSessionDetail, String> {
+    let conn = state.get_connection().map_err(|e| e.to_string())?;
+    let session = sessions::get(&conn, &id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Chat session {} not found", id))?;
+    let messages = sessions::messages(&conn, &id).map_err(|e| e.to_string())?;
+    Ok(ChatSessionDetail { session, messages })
+}
+
+#[command]
+pub fn rename_chat_session(state: State<'_, DbState>, id: String, title: String) -> Result<(), String> {
+    let title = title.trim();
+    if title.is_empty() {
+        return Err("Title must not be empty".to_string());
+    }
+    let conn = state.get_connection().map_err(|e| e.to_string())?;
+    if !sessions::rename(&conn, &id, title).map_err(|e| e.to_string())? {
+        return Err(format!("Chat session {} not found", id));
+    }
+    Ok(())
+}
+
+#[command]
+pub fn delete_chat_session(state: State<'_, DbState>, id: String) -> Result<(), String> {
+    let mut conn = state.get_connection().map_err(|e| e.to_string())?;
+    sessions::delete(&mut conn, &id).map_err(|e| e.to_string())
+}
+
+/// Render a session as Markdown, listing each answer's sources.
+fn transcript(session: &ChatSession, messages: &[ChatSessionMessage]) -> String {
+    let mut out = format!("# {}\n\n", session.title);
+    for message in messages {
+        let speaker = match message.role.as_str() {
+            "user" => "You",
+            "assistant" => "Assistant",
+            other => other,
+        };
+        out.push_str(&format!("**{}:** {}\n\n", speaker, message.content.trim()));
+
+        if !message.sources.is_empty() {
+            out.push_str("Sources:\n");
+            for source in &message.sources {
+                out.push_str(&format!("- [{}] {}\n", source.number, source.title));
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Save a conversation as a document node, linked to every node it cited.
+/// It is searchable by text at once and embedded in the background.
+#[command]
+pub fn save_chat_session_as_node(app: AppHandle, state: State<'_, DbState>, id: String) -> Result<Node, String> {
+    let mut conn = state.get_connection().map_err(|e| e.to_string())?;
+    let session = sessions::get(&conn, &id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Chat session {} not found", id))?;
+    let messages = sessions::messages(&conn, &id).map_err(|e| e.to_string())?;
+    if messages.is_empty() {
+        return Err("Cannot save an empty conversation".to_string());
+    }
+
+    let node_id = Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().to_rfc3339();
+    let node_type = "document".to_string();
+    let content = transcript(&session, &messages);
+    let metadata = json!({
+        "chat_session_id": session.id,
+        "message_count": messages.len()
+    });
+
+    // Staged until the node is committed, so a failure leaves no orphan file
+    let ws = Workspace::new().map_err(|e| e.to_string())?;
+    let filename = format!("{}.md", node_id);
+    let artifact = ws.stage_artifact(&filename, content.as_bytes()).map_err(|e| e.to_string())?;
+
+    let mut cited: Vec<&str> = Vec::new();
+    for source in messages.iter().flat_map(|m| &m.sources) {
+        if !cited.contains(&source.node_id.as_str()) {
+            cited.push(&source.node_id);
+        }
+    }
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    tx.execute(
+        "INSERT INTO nodes (id, node_type, title, content_path, metadata, created_at, updated_at, index_status)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        rusqlite::params![node_id, node_type, session.title, filename, metadata.to_string(), now, now, index_status::STALE],
+    ).map_err(|e| e.to_string())?;
+    vec::update_fts(&tx, &node_id, &session.title, &content).map_err(|e| e.to_string())?;
+
+    // Cited nodes may have been deleted or trashed since
+    for target in cited {
+        tx.execute(
+            "INSERT INTO edges (id, source, target, label, created_at)
+             SELECT ?1, ?2, id, 'cites', ?4 FROM nodes WHERE id = ?3 AND deleted_at IS NULL",
+            rusqlite::params![Uuid::new_v4().to_string(), node_id, target, now],
+        ).map_err(|e| e.to_string())?;
+    }
+
+    let published = artifact.publish().map_err(|e| e.to_string())?;
+    if let Err(e) = tx.commit() {
+        // The file is new, so failing to remove it only leaves an orphan
+        let _ = published.rollback();
+        return Err(e.to_string());
+    }
+    published.finish();
+    schedule_node_index(&app, &node_id);
+
+    Ok(Node {
+        id: node_id,
+        node_type,
+        title: session.title,
+        content_path: Some(filename),
+        metadata: Some(metadata),
+        created_at: now.clone(),
+        updated_at: now,
+        index_status: index_status::STALE.to_string(),
+    })
+}
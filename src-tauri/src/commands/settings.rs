@@ -0,0 +1,25 @@
+use tauri::{command, State};
+use crate::db::DbState;
+use crate::settings::{self, AppSettings};
+
+#[command]
+pub fn get_settings(state: State<'_, DbState>) -> Result<AppSettings, String> {
+    let conn = state.get_connection().map_err(|e| e.to_string())?;
+    settings::load(&conn).map_err(|e| e.to_string())
+}
+
+/// Update any subset of the settings, e.g. `{ "llmModel": "llama3.1:8b" }`.
+/// The merged result is validated before anything is written.
+#[command]
+pub fn update_settings(
+    state: State<'_, DbState>,
+    settings: serde_json::Value,
+) -> Result<AppSettings, String> {
+    let conn = state.get_connection().map_err(|e| e.to_string())?;
+
+    let current = settings::load(&conn).map_err(|e| e.to_string())?;
+    let updated = settings::merge(&current, settings)?;
+    settings::save(&conn, &updated)?;
+
+    Ok(updated)
+}
@@ -0,0 +1,207 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+/// A span of a node's text, ready to be stored as a chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkSpan {
+    /// Byte offsets into the node content, `text == content[start..end]`.
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+    pub token_count: usize,
+    /// Headings the span sits under, outermost first.
+    pub heading_path: Vec<String>,
+    /// First and last page the span covers, for text extracted from PDFs.
+    pub page_start: Option<u32>,
+    pub page_end: Option<u32>,
+}
+
+/// A stored chunk. Its id is also the rowid of its vectors in every
+/// sqlite-vec table.
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    pub id: i64,
+    pub node_id: String,
+    pub ordinal: usize,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub text: String,
+    pub token_count: usize,
+    pub heading_path: Vec<String>,
+    pub page_start: Option<u32>,
+    pub page_end: Option<u32>,
+}
+
+pub fn init_table(conn: &Connection) -> Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'chunks')",
+        [],
+        |row| row.get(0),
+    )?;
+    if exists {
+        return Ok(());
+    }
+
+    conn.execute(
+        "CREATE TABLE chunks (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            node_id TEXT NOT NULL,
+            ordinal INTEGER NOT NULL,
+            start_byte INTEGER NOT NULL,
+            end_byte INTEGER NOT NULL,
+            text TEXT NOT NULL,
+            token_count INTEGER NOT NULL,
+            UNIQUE(node_id, ordinal),
+            FOREIGN KEY(node_id) REFERENCES nodes(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    // Vectors written before this table existed have no chunk; start ids
+    // past their rowids so a new chunk never claims an old vector
+    let mut max_rowid: i64 = 0;
+    for index in super::vec::list_indexes(conn)? {
+        let table_max: Option<i64> = conn.query_row(
+            &format!("SELECT MAX(rowid) FROM {}", index.table_name),
+            [],
+            |row| row.get(0),
+        )?;
+        max_rowid = max_rowid.max(table_max.unwrap_or(0));
+    }
+    if max_rowid > 0 {
+        conn.execute(
+            "INSERT INTO sqlite_sequence (name, seq) VALUES ('chunks', ?1)",
+            params![max_rowid],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn from_row(row: &rusqlite::Row) -> Result<Chunk> {
+    Ok(Chunk {
+        id: row.get(0)?,
+        node_id: row.get(1)?,
+        ordinal: row.get::<_, i64>(2)? as usize,
+        start_byte: row.get::<_, i64>(3)? as usize,
+        end_byte: row.get::<_, i64>(4)? as usize,
+        text: row.get(5)?,
+        token_count: row.get::<_, i64>(6)? as usize,
+        heading_path: serde_json::from_str(&row.get::<_, String>(7)?).unwrap_or_default(),
+        page_start: row.get(8)?,
+        page_end: row.get(9)?,
+    })
+}
+
+const COLUMNS: &str = "id, node_id, ordinal, start_byte, end_byte, text, token_count, heading_path, page_start, page_end";
+
+/// Chunks of `node_id` in document order.
+pub fn for_node(conn: &Connection, node_id: &str) -> Result<Vec<Chunk>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM chunks WHERE node_id = ?1 ORDER BY ordinal",
+        COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![node_id], from_row)?;
+    rows.collect()
+}
+
+/// The chunk behind a vector hit. Returns None for vectors older than the
+/// chunk table, whose rowid may belong to no chunk or to another node.
+pub fn get(conn: &Connection, id: i64, node_id: &str) -> Result<Option<Chunk>> {
+    conn.query_row(
+        &format!("SELECT {} FROM chunks WHERE id = ?1 AND node_id = ?2", COLUMNS),
+        params![id, node_id],
+        from_row,
+    )
+    .optional()
+}
+
+/// Store `spans` as the chunks of `node_id`, replacing any previous ones,
+/// and return the new ids in order.
+pub fn replace(conn: &Connection, node_id: &str, spans: &[ChunkSpan]) -> Result<Vec<i64>> {
+    delete_for_node(conn, node_id)?;
+
+    let mut stmt = conn.prepare(
+        "INSERT INTO chunks (node_id, ordinal, start_byte, end_byte, text, token_count, heading_path, page_start, page_end)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    )?;
+    let mut ids = Vec::with_capacity(spans.len());
+    for (ordinal, span) in spans.iter().enumerate() {
+        stmt.execute(params![
+            node_id,
+            ordinal as i64,
+            span.start as i64,
+            span.end as i64,
+            span.text,
+            span.token_count as i64,
+            serde_json::json!(span.heading_path).to_string(),
+            span.page_start,
+            span.page_end
+        ])?;
+        ids.push(conn.last_insert_rowid());
+    }
+    Ok(ids)
+}
+
+/// Whether `existing` chunks are exactly `spans`.
+pub fn same_spans(existing: &[Chunk], spans: &[ChunkSpan]) -> bool {
+    existing.len() == spans.len()
+        && existing
+            .iter()
+            .zip(spans)
+            .all(|(c, s)| {
+                c.start_byte == s.start
+                    && c.end_byte == s.end
+                    && c.text == s.text
+                    && c.heading_path == s.heading_path
+                    && c.page_start == s.page_start
+                    && c.page_end == s.page_end
+            })
+}
+
+/// Like `replace`, but keeps the existing chunks (and so the vectors other
+/// indexes hold for them) when the text still splits the same way.
+/// Otherwise the vectors of the replaced chunks go too, from every index.
+pub fn sync(conn: &Connection, node_id: &str, spans: &[ChunkSpan]) -> Result<Vec<i64>> {
+    let existing = for_node(conn, node_id)?;
+
+    if same_spans(&existing, spans) {
+        Ok(existing.into_iter().map(|c| c.id).collect())
+    } else {
+        super::vec::delete_node_vectors(conn, node_id)?;
+        replace(conn, node_id, spans)
+    }
+}
+
+pub fn delete_for_node(conn: &Connection, node_id: &str) -> Result<()> {
+    conn.execute("DELETE FROM chunks WHERE node_id = ?1", params![node_id])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::embeddings::{Embedder, HashEmbedder};
+    use crate::db::testing::{add_document, open_db};
+    use crate::db::vec;
+    use crate::scraper::chunker::{chunk_text, ChunkOptions};
+
+    #[test]
+    fn sync_keeps_unchanged_chunks_and_drops_vectors_of_replaced_ones() {
+        let mut conn = open_db();
+        let embedder = HashEmbedder::default();
+        let content = "Every Rust value has a single owner.";
+        let id = add_document(&mut conn, &embedder, "Rust", content);
+        let index = vec::find_index(&conn, embedder.model_id(), embedder.dimension()).unwrap().unwrap();
+        let before: Vec<i64> = for_node(&conn, &id).unwrap().iter().map(|c| c.id).collect();
+
+        let same = chunk_text(content, &ChunkOptions::default());
+        assert_eq!(sync(&conn, &id, &same).unwrap(), before);
+        assert_eq!(vec::count_node_vectors(&conn, &index, &id).unwrap(), before.len());
+
+        let edited = chunk_text("Values are dropped when their owner goes out of scope.", &ChunkOptions::default());
+        let after = sync(&conn, &id, &edited).unwrap();
+        assert!(after.iter().all(|id| !before.contains(id)));
+        assert_eq!(vec::count_node_vectors(&conn, &index, &id).unwrap(), 0);
+        assert!(vec::out_of_date_nodes(&conn, &index).unwrap().contains(&id));
+    }
+}
@@ -1,6 +1,167 @@
-use rusqlite::{Connection, Result};
+use crate::scraper::canonical;
+use reqwest::Url;
+use rusqlite::{params, Connection, Result};
+use std::collections::HashSet;
+use std::fmt;
 
-pub fn run_migrations(conn: &Connection) -> Result<()> {
+/// One step of the schema history. `version` is stored in
+/// `PRAGMA user_version` once `up` has run.
+struct Migration {
+    version: u32,
+    description: &'static str,
+    up: fn(&Connection) -> Result<()>,
+}
+
+/// Every schema change, oldest first. Append new steps with the next
+/// version; never edit a step that has shipped.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "baseline schema",
+        up: baseline,
+    },
+    Migration {
+        version: 2,
+        description: "soft delete for nodes",
+        up: node_trash,
+    },
+    Migration {
+        version: 3,
+        description: "index status for nodes",
+        up: node_index_status,
+    },
+    Migration {
+        version: 4,
+        description: "heading path for chunks",
+        up: chunk_heading_path,
+    },
+    Migration {
+        version: 5,
+        description: "page range for chunks",
+        up: chunk_page_range,
+    },
+    Migration {
+        version: 6,
+        description: "content hash for nodes",
+        up: node_content_hash,
+    },
+    Migration {
+        version: 7,
+        description: "unique canonical URL for sources",
+        up: node_canonical_url,
+    },
+    Migration {
+        version: 8,
+        description: "cosine metric for the legacy vector index",
+        up: legacy_vec_cosine,
+    },
+];
+
+/// Schema version this build writes.
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    /// The database was written by a newer version of the app.
+    TooNew { found: u32, supported: u32 },
+    /// The pre-upgrade backup failed, so nothing was migrated.
+    Backup(rusqlite::Error),
+    Failed { version: u32, description: &'static str, source: rusqlite::Error },
+    Sqlite(rusqlite::Error),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::TooNew { found, supported } => write!(
+                f,
+                "Database schema version {} is newer than this app supports ({}); please update the app",
+                found, supported
+            ),
+            MigrationError::Backup(e) => write!(f, "Failed to back up database before upgrading: {}", e),
+            MigrationError::Failed { version, description, source } => {
+                write!(f, "Migration {} ({}) failed: {}", version, description, source)
+            }
+            MigrationError::Sqlite(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MigrationError::TooNew { .. } => None,
+            MigrationError::Backup(e) | MigrationError::Sqlite(e) => Some(e),
+            MigrationError::Failed { source, .. } => Some(source),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for MigrationError {
+    fn from(e: rusqlite::Error) -> Self {
+        MigrationError::Sqlite(e)
+    }
+}
+
+/// Bring the database up to `latest_version`, one transaction per step.
+///
+/// Existing databases are copied to `<db>.backup-v<N>-<timestamp>` first.
+/// Databases from a newer app are refused rather than touched.
+pub fn run_migrations(conn: &mut Connection) -> std::result::Result<(), MigrationError> {
+    let current: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let latest = latest_version();
+
+    if current > latest {
+        return Err(MigrationError::TooNew { found: current, supported: latest });
+    }
+    if current == latest {
+        return Ok(());
+    }
+
+    // Databases from before versioning have tables but user_version 0
+    let has_tables: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table')",
+        [],
+        |row| row.get(0),
+    )?;
+    if has_tables {
+        backup(conn, current).map_err(MigrationError::Backup)?;
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction()?;
+        (migration.up)(&tx).map_err(|source| MigrationError::Failed {
+            version: migration.version,
+            description: migration.description,
+            source,
+        })?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Snapshot the database next to itself. In-memory databases are skipped.
+fn backup(conn: &Connection, version: u32) -> Result<()> {
+    let Some(path) = conn.path().filter(|p| !p.is_empty()) else {
+        return Ok(());
+    };
+    let target = format!(
+        "{}.backup-v{}-{}",
+        path,
+        version,
+        chrono::Utc::now().format("%Y%m%d%H%M%S")
+    );
+    conn.execute("VACUUM INTO ?1", [&target])?;
+    Ok(())
+}
+
+/// v1: the schema as it stood when versioning was introduced. Every
+/// statement is idempotent so unversioned databases adopt it in place.
+fn baseline(conn: &Connection) -> Result<()> {
     // Nodes table
     conn.execute(
         "CREATE TABLE IF NOT EXISTS nodes (
@@ -29,8 +190,233 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Settings table
+    crate::settings::init_table(conn)?;
+
     // Initialize Vector Search Tables (sqlite-vec & FTS5)
     super::vec::init_vector_tables(conn)?;
 
+    // Chunk text behind each vector
+    super::chunks::init_table(conn)?;
+
+    // Chat sessions and their messages
+    super::sessions::init_tables(conn)?;
+
+    Ok(())
+}
+
+/// v2: `deleted_at` marks nodes moved to the trash.
+fn node_trash(conn: &Connection) -> Result<()> {
+    conn.execute("ALTER TABLE nodes ADD COLUMN deleted_at TEXT", [])?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_nodes_deleted_at ON nodes(deleted_at)",
+        [],
+    )?;
     Ok(())
 }
+
+/// v3: `index_status` tracks whether a node's index matches its content.
+/// Notes edited before this never reached the index, so they start stale.
+fn node_index_status(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "ALTER TABLE nodes ADD COLUMN index_status TEXT NOT NULL DEFAULT 'indexed'",
+        [],
+    )?;
+    conn.execute(
+        "UPDATE nodes SET index_status = 'stale'
+         WHERE content_path IS NOT NULL AND id NOT IN (SELECT id FROM nodes_fts)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// v4: the Markdown headings each chunk sits under, as a JSON array.
+fn chunk_heading_path(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "ALTER TABLE chunks ADD COLUMN heading_path TEXT NOT NULL DEFAULT '[]'",
+        [],
+    )?;
+    Ok(())
+}
+
+/// v5: the pages a chunk covers, for nodes extracted from PDFs.
+fn chunk_page_range(conn: &Connection) -> Result<()> {
+    conn.execute("ALTER TABLE chunks ADD COLUMN page_start INTEGER", [])?;
+    conn.execute("ALTER TABLE chunks ADD COLUMN page_end INTEGER", [])?;
+    Ok(())
+}
+
+/// v6: `content_hash` identifies imported content, so importing the same
+/// file twice finds the existing node.
+fn node_content_hash(conn: &Connection) -> Result<()> {
+    conn.execute("ALTER TABLE nodes ADD COLUMN content_hash TEXT", [])?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_nodes_content_hash ON nodes(content_hash)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// v7: `canonical_url` in the metadata of web sources is unique among
+/// live nodes. Sources ingested before get one from their URL; when
+/// several live ones share it, the oldest keeps it.
+fn node_canonical_url(conn: &Connection) -> Result<()> {
+    let mut stmt = conn.prepare(
+        "SELECT id, json_extract(metadata, '$.url'), deleted_at IS NOT NULL FROM nodes
+         WHERE json_valid(metadata) AND json_type(metadata, '$.url') = 'text'
+         ORDER BY created_at",
+    )?;
+    let sources = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, bool>(2)?)))?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut taken = HashSet::new();
+    for (id, url, deleted) in sources {
+        let Ok(url) = Url::parse(&url) else {
+            continue;
+        };
+        let canonical = canonical::normalize(&url).to_string();
+        // Trashed ones keep theirs, so restoring a duplicate is refused
+        if deleted || taken.insert(canonical.clone()) {
+            conn.execute(
+                "UPDATE nodes SET metadata = json_set(metadata, '$.canonical_url', ?1) WHERE id = ?2",
+                params![canonical, id],
+            )?;
+        }
+    }
+
+    // Partial, so malformed metadata is never parsed and trashed
+    // duplicates don't block new ingests
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_nodes_canonical_url
+         ON nodes(json_extract(metadata, '$.canonical_url'))
+         WHERE deleted_at IS NULL AND json_valid(metadata)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// v8: the adopted `nodes_vec` table measures L2 distance while every
+/// other index and the similarity scores assume cosine, so its vectors
+/// move to a cosine table.
+fn legacy_vec_cosine(conn: &Connection) -> Result<()> {
+    super::vec::recreate_legacy_index(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{register_sqlite_vec, vec};
+
+    /// The schema before versioning, as the first releases created it.
+    const UNVERSIONED_SCHEMA: &str = "
+        CREATE TABLE nodes (
+            id TEXT PRIMARY KEY,
+            node_type TEXT NOT NULL,
+            title TEXT NOT NULL,
+            content_path TEXT,
+            metadata TEXT,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE edges (
+            id TEXT PRIMARY KEY,
+            source TEXT NOT NULL,
+            target TEXT NOT NULL,
+            label TEXT NOT NULL,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE VIRTUAL TABLE nodes_fts USING fts5(id UNINDEXED, title, content, tokenize = 'porter');
+        CREATE VIRTUAL TABLE nodes_vec USING vec0(node_id TEXT, embedding float[768]);
+    ";
+
+    fn vector(first: f32) -> String {
+        let mut v = vec![0.0f32; 768];
+        v[0] = first;
+        v[1] = 1.0;
+        serde_json::to_string(&v).unwrap()
+    }
+
+    fn scratch_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("research-migrations-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn unversioned_databases_upgrade_to_the_latest_version() {
+        register_sqlite_vec();
+        let dir = scratch_dir();
+        let mut conn = Connection::open(dir.join("research.db")).unwrap();
+        conn.execute_batch(UNVERSIONED_SCHEMA).unwrap();
+        conn.execute_batch(
+            "INSERT INTO nodes (id, node_type, title, content_path, metadata, created_at) VALUES
+                ('note', 'note', 'Draft', 'note.md', NULL, '2024-01-01'),
+                ('indexed', 'note', 'Indexed', 'indexed.md', NULL, '2024-01-02'),
+                ('first', 'source', 'First', 'first.md', '{\"url\": \"https://Example.com/a/?utm_source=feed\"}', '2024-01-03'),
+                ('second', 'source', 'Second', 'second.md', '{\"url\": \"https://example.com/a\"}', '2024-01-04');
+             INSERT INTO nodes_fts (id, title, content) VALUES ('indexed', 'Indexed', 'Some text');",
+        )
+        .unwrap();
+        conn.execute("INSERT INTO nodes_vec (rowid, node_id, embedding) VALUES (1, 'indexed', ?1)", [vector(0.0)])
+            .unwrap();
+        conn.execute("INSERT INTO nodes_vec (rowid, node_id, embedding) VALUES (2, 'first', ?1)", [vector(3.0)])
+            .unwrap();
+
+        run_migrations(&mut conn).unwrap();
+
+        let version: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap();
+        assert_eq!(version, latest_version());
+        let backups = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().starts_with("research.db.backup-v0-"))
+            .count();
+        assert_eq!(backups, 1);
+
+        // v3: notes that never reached the index start stale
+        let status = |id: &str| -> String {
+            conn.query_row("SELECT index_status FROM nodes WHERE id = ?1", [id], |row| row.get(0)).unwrap()
+        };
+        assert_eq!(status("note"), "stale");
+        assert_eq!(status("indexed"), "indexed");
+
+        // v7: the oldest source with a URL owns its canonical form
+        let canonical = |id: &str| -> Option<String> {
+            conn.query_row("SELECT json_extract(metadata, '$.canonical_url') FROM nodes WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+            .unwrap()
+        };
+        assert_eq!(canonical("first").as_deref(), Some("https://example.com/a"));
+        assert_eq!(canonical("second"), None);
+
+        // v8: the legacy vectors moved to a cosine index with their rowids
+        let index = vec::find_index(&conn, "nomic-embed-text", 768).unwrap().unwrap();
+        assert_ne!(index.table_name, "nodes_vec");
+        let query: Vec<f32> = serde_json::from_str(&vector(0.0)).unwrap();
+        let hits = vec::search(&conn, &index, &query, 2).unwrap();
+        assert_eq!((hits[0].rowid, hits[0].node_id.as_str()), (1, "indexed"));
+        assert!(hits[0].distance.abs() < 1e-6);
+        assert!(hits[1].distance < 1.0, "cosine distance, got {}", hits[1].distance);
+        let legacy: bool = conn
+            .query_row("SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'nodes_vec')", [], |row| row.get(0))
+            .unwrap();
+        assert!(!legacy);
+
+        // Running again is a no-op
+        run_migrations(&mut conn).unwrap();
+        drop(conn);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn databases_from_a_newer_app_are_refused() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", latest_version() + 1).unwrap();
+
+        let error = run_migrations(&mut conn).unwrap_err();
+        assert!(matches!(error, MigrationError::TooNew { .. }), "{}", error);
+        let version: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap();
+        assert_eq!(version, latest_version() + 1);
+    }
+}
@@ -1,18 +1,66 @@
-use rusqlite::{Connection, Result};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::Connection;
 use std::path::PathBuf;
 use std::fs;
+use std::time::Duration;
 use tauri::{AppHandle, Manager};
 
+pub mod chunks;
 pub mod migrations;
+pub mod sessions;
+pub mod trash;
 pub mod vec;
 
+pub type DbPool = r2d2::Pool<SqliteConnectionManager>;
+pub type PooledConnection = r2d2::PooledConnection<SqliteConnectionManager>;
+
+/// Connections kept open for commands and background jobs.
+const POOL_SIZE: u32 = 8;
+
+/// How long a writer waits for another one before "database is locked".
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub struct DbState {
     pub db_path: PathBuf,
+    pool: DbPool,
 }
 
 impl DbState {
-    pub fn get_connection(&self) -> Result<Connection, rusqlite::Error> {
-        Connection::open(&self.db_path)
+    /// Open a pool on `db_path`. Every connection enforces foreign keys and
+    /// uses WAL so readers don't block the writer.
+    pub fn open(db_path: PathBuf) -> Result<Self, r2d2::Error> {
+        let manager = SqliteConnectionManager::file(&db_path).with_init(|conn| {
+            conn.busy_timeout(BUSY_TIMEOUT)?;
+            conn.execute_batch(
+                "PRAGMA journal_mode = WAL;
+                 PRAGMA synchronous = NORMAL;
+                 PRAGMA foreign_keys = ON;",
+            )
+        });
+        let pool = r2d2::Pool::builder().max_size(POOL_SIZE).build(manager)?;
+        Ok(Self { db_path, pool })
+    }
+
+    /// Borrow a pooled connection. Blocks while all are in use, so async
+    /// code should prefer `run`.
+    pub fn get_connection(&self) -> Result<PooledConnection, r2d2::Error> {
+        self.pool.get()
+    }
+
+    /// Run `f` with a pooled connection on the blocking thread pool, keeping
+    /// SQLite work off the async executor.
+    pub async fn run<F, T>(&self, f: F) -> Result<T, String>
+    where
+        F: FnOnce(&mut Connection) -> Result<T, String> + Send + 'static,
+        T: Send + 'static,
+    {
+        let pool = self.pool.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            let mut conn = pool.get().map_err(|e| e.to_string())?;
+            f(&mut conn)
+        })
+        .await
+        .map_err(|e| e.to_string())?
     }
 }
 
@@ -21,19 +69,106 @@ pub fn init(app_handle: &AppHandle) -> Result<DbState, Box<dyn std::error::Error
     if !app_dir.exists() {
         fs::create_dir_all(&app_dir)?;
     }
-    
+
     let db_path = app_dir.join("research.db");
-    
-    // Load sqlite-vec extension
+
+    register_sqlite_vec();
+
+    let state = DbState::open(db_path)?;
+
+    // Migrate before any command can use the pool
+    let mut conn = state.get_connection()?;
+    migrations::run_migrations(&mut conn)?;
+    drop(conn);
+
+    Ok(state)
+}
+
+/// Load the sqlite-vec extension into every connection opened from now on.
+fn register_sqlite_vec() {
     unsafe {
         let _ = rusqlite::ffi::sqlite3_auto_extension(Some(std::mem::transmute(
             sqlite_vec::sqlite3_vec_init as *const (),
         )));
     }
+}
+
+/// Helpers for tests that need a real database.
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::{migrations, register_sqlite_vec, vec, DbState};
+    use crate::ai::embeddings::{Embedder, HashEmbedder};
+    use crate::scraper::chunker::{chunk_text, ChunkOptions};
+    use crate::settings::{self, AppSettings};
+    use rusqlite::{params, Connection};
+    use std::ops::Deref;
+    use std::path::PathBuf;
 
-    // Connect to run migrations
-    let conn = Connection::open(&db_path)?;
-    migrations::run_migrations(&conn)?;
-    
-    Ok(DbState { db_path })
+    /// An in-memory database with the current schema.
+    pub fn open_db() -> Connection {
+        register_sqlite_vec();
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::run_migrations(&mut conn).unwrap();
+        conn
+    }
+
+    /// A pool on a database file in its own temporary directory, for code
+    /// that takes a `DbState`. The directory is removed on drop.
+    pub struct TestState {
+        state: DbState,
+        dir: PathBuf,
+    }
+
+    impl Deref for TestState {
+        type Target = DbState;
+
+        fn deref(&self) -> &DbState {
+            &self.state
+        }
+    }
+
+    impl Drop for TestState {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// A migrated database whose settings use the offline providers: the
+    /// mock chat model and hash embeddings.
+    pub fn open_state() -> TestState {
+        register_sqlite_vec();
+        let dir = std::env::temp_dir().join(format!("research-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = DbState::open(dir.join("research.db")).unwrap();
+
+        let mut conn = state.get_connection().unwrap();
+        migrations::run_migrations(&mut conn).unwrap();
+        let offline = AppSettings {
+            llm_provider: "mock".to_string(),
+            embedding_provider: "hash".to_string(),
+            ..AppSettings::default()
+        };
+        settings::save(&conn, &offline).unwrap();
+        drop(conn);
+
+        TestState { state, dir }
+    }
+
+    /// Insert a note and index it the way ingest does. Returns its id.
+    pub fn add_document(conn: &mut Connection, embedder: &HashEmbedder, title: &str, content: &str) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        let spans = chunk_text(content, &ChunkOptions::default());
+        let embedded: Vec<Vec<f32>> = spans.iter().map(|s| embedder.embed_sync(&s.text)).collect();
+
+        let tx = conn.transaction().unwrap();
+        tx.execute(
+            "INSERT INTO nodes (id, node_type, title, content_path) VALUES (?1, 'note', ?2, ?3)",
+            params![id, title, format!("{}.md", id)],
+        )
+        .unwrap();
+        let index = vec::ensure_index(&tx, embedder.model_id(), embedder.dimension()).unwrap();
+        vec::index_node(&tx, &index, &id, title, content, &spans, &embedded).unwrap();
+        tx.commit().unwrap();
+        id
+    }
 }
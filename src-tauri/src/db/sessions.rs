@@ -0,0 +1,168 @@
+use crate::ai::rag::Source;
+use crate::models::{ChatSession, ChatSessionMessage};
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+/// Title of a session until its first message names it.
+pub const DEFAULT_TITLE: &str = "New chat";
+
+pub fn init_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS chat_sessions (
+            id TEXT PRIMARY KEY,
+            title TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS chat_messages (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL,
+            role TEXT NOT NULL,
+            content TEXT NOT NULL,
+            model TEXT,
+            sources TEXT NOT NULL DEFAULT '[]', -- JSON, cited nodes and passages
+            created_at TEXT NOT NULL,
+            FOREIGN KEY(session_id) REFERENCES chat_sessions(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_chat_messages_session ON chat_messages(session_id, id)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn session_from_row(row: &rusqlite::Row) -> Result<ChatSession> {
+    Ok(ChatSession {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        message_count: row.get::<_, i64>(2)? as usize,
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+    })
+}
+
+const SESSION_QUERY: &str = "SELECT s.id, s.title,
+        (SELECT COUNT(*) FROM chat_messages m WHERE m.session_id = s.id),
+        s.created_at, s.updated_at
+     FROM chat_sessions s";
+
+pub fn create(conn: &Connection, title: &str) -> Result<ChatSession> {
+    let id = uuid::Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().to_rfc3339();
+    conn.execute(
+        "INSERT INTO chat_sessions (id, title, created_at, updated_at) VALUES (?1, ?2, ?3, ?4)",
+        params![id, title, now, now],
+    )?;
+    Ok(ChatSession {
+        id,
+        title: title.to_string(),
+        message_count: 0,
+        created_at: now.clone(),
+        updated_at: now,
+    })
+}
+
+/// All sessions, most recently active first.
+pub fn list(conn: &Connection) -> Result<Vec<ChatSession>> {
+    let mut stmt = conn.prepare(&format!("{} ORDER BY s.updated_at DESC", SESSION_QUERY))?;
+    let rows = stmt.query_map([], session_from_row)?;
+    rows.collect()
+}
+
+pub fn get(conn: &Connection, id: &str) -> Result<Option<ChatSession>> {
+    conn.query_row(&format!("{} WHERE s.id = ?1", SESSION_QUERY), params![id], session_from_row)
+        .optional()
+}
+
+/// Messages of a session, oldest first.
+pub fn messages(conn: &Connection, session_id: &str) -> Result<Vec<ChatSessionMessage>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, session_id, role, content, model, sources, created_at
+         FROM chat_messages WHERE session_id = ?1 ORDER BY id",
+    )?;
+    let rows = stmt.query_map(params![session_id], |row| {
+        let sources: String = row.get(5)?;
+        Ok(ChatSessionMessage {
+            id: row.get(0)?,
+            session_id: row.get(1)?,
+            role: row.get(2)?,
+            content: row.get(3)?,
+            model: row.get(4)?,
+            sources: serde_json::from_str(&sources).unwrap_or_default(),
+            created_at: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Append a message and mark the session as active.
+pub fn add_message(
+    conn: &Connection,
+    session_id: &str,
+    role: &str,
+    content: &str,
+    model: Option<&str>,
+    sources: &[Source],
+) -> Result<ChatSessionMessage> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let sources_json = serde_json::to_string(sources)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+    conn.execute(
+        "INSERT INTO chat_messages (session_id, role, content, model, sources, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![session_id, role, content, model, sources_json, now],
+    )?;
+    let id = conn.last_insert_rowid();
+    conn.execute(
+        "UPDATE chat_sessions SET updated_at = ?1 WHERE id = ?2",
+        params![now, session_id],
+    )?;
+
+    Ok(ChatSessionMessage {
+        id,
+        session_id: session_id.to_string(),
+        role: role.to_string(),
+        content: content.to_string(),
+        model: model.map(str::to_string),
+        sources: sources.to_vec(),
+        created_at: now,
+    })
+}
+
+/// Returns false if the session does not exist.
+pub fn rename(conn: &Connection, id: &str, title: &str) -> Result<bool> {
+    let changed = conn.execute(
+        "UPDATE chat_sessions SET title = ?1, updated_at = ?2 WHERE id = ?3",
+        params![title, chrono::Utc::now().to_rfc3339(), id],
+    )?;
+    Ok(changed > 0)
+}
+
+pub fn delete(conn: &mut Connection, id: &str) -> Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM chat_messages WHERE session_id = ?1", params![id])?;
+    tx.execute("DELETE FROM chat_sessions WHERE id = ?1", params![id])?;
+    tx.commit()
+}
+
+/// Session title derived from its first question.
+pub fn title_from(message: &str) -> String {
+    const MAX_CHARS: usize = 60;
+    let line = message.lines().find(|l| !l.trim().is_empty()).unwrap_or("").trim();
+    if line.is_empty() {
+        return DEFAULT_TITLE.to_string();
+    }
+    if line.chars().count() <= MAX_CHARS {
+        return line.to_string();
+    }
+    let cut: String = line.chars().take(MAX_CHARS).collect();
+    format!("{}…", cut.trim_end())
+}
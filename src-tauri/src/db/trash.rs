@@ -0,0 +1,190 @@
+use crate::models::Node;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::Serialize;
+
+/// A node in the trash.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedNode {
+    #[serde(flatten)]
+    pub node: Node,
+    pub deleted_at: String,
+}
+
+/// Move a node to the trash. It keeps its content and indexes but is
+/// hidden from the graph, search and chat. Returns false if there is no
+/// such live node.
+pub fn soft_delete(conn: &Connection, id: &str) -> Result<bool> {
+    let changed = conn.execute(
+        "UPDATE nodes SET deleted_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+        params![chrono::Utc::now().to_rfc3339(), id],
+    )?;
+    Ok(changed > 0)
+}
+
+/// Bring a node back from the trash. Returns false if it is not trashed.
+pub fn restore(conn: &Connection, id: &str) -> Result<bool> {
+    let changed = conn.execute(
+        "UPDATE nodes SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL",
+        params![id],
+    )?;
+    Ok(changed > 0)
+}
+
+/// Trashed nodes, most recently deleted first.
+pub fn list(conn: &Connection) -> Result<Vec<TrashedNode>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, node_type, title, content_path, metadata, created_at, updated_at, index_status, deleted_at
+         FROM nodes WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let metadata_str: Option<String> = row.get(4)?;
+        Ok(TrashedNode {
+            node: Node {
+                id: row.get(0)?,
+                node_type: row.get(1)?,
+                title: row.get(2)?,
+                content_path: row.get(3)?,
+                metadata: metadata_str.and_then(|s| serde_json::from_str(&s).ok()),
+                created_at: row.get(5)?,
+                updated_at: row.get(6)?,
+                index_status: row.get(7)?,
+            },
+            deleted_at: row.get(8)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// What a purge leaves to clean up outside the database.
+pub struct Purged {
+    pub content_path: Option<String>,
+    /// The imported original, e.g. a PDF, when kept next to the content.
+    pub original_path: Option<String>,
+    /// Earlier texts of a web source, kept when it was updated.
+    pub previous_paths: Vec<String>,
+}
+
+/// Permanently delete a node and everything derived from it: vectors in
+/// every index, chunks, the full-text entry and its edges, in one
+/// transaction. Returns None if the node did not exist.
+pub fn purge(conn: &mut Connection, id: &str) -> Result<Option<Purged>> {
+    let tx = conn.transaction()?;
+
+    let row: Option<(Option<String>, Option<String>)> = tx
+        .query_row("SELECT content_path, metadata FROM nodes WHERE id = ?1", params![id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .optional()?;
+    let Some((content_path, metadata)) = row else {
+        return Ok(None);
+    };
+    let metadata = metadata.and_then(|m| serde_json::from_str::<serde_json::Value>(&m).ok());
+    let original_path = metadata
+        .as_ref()
+        .and_then(|m| m.get("original_path")?.as_str().map(str::to_string));
+    let previous_paths = metadata
+        .as_ref()
+        .and_then(|m| m.get("previous_versions")?.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.get("content_path")?.as_str().map(str::to_string))
+        .collect();
+
+    super::vec::delete_node_vectors(&tx, id)?;
+    super::chunks::delete_for_node(&tx, id)?;
+    tx.execute("DELETE FROM nodes_fts WHERE id = ?1", params![id])?;
+    tx.execute("DELETE FROM edges WHERE source = ?1 OR target = ?1", params![id])?;
+    tx.execute("DELETE FROM nodes WHERE id = ?1", params![id])?;
+
+    tx.commit()?;
+    Ok(Some(Purged { content_path, original_path, previous_paths }))
+}
+
+/// Ids of every trashed node.
+pub fn trashed_ids(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT id FROM nodes WHERE deleted_at IS NOT NULL")?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::embeddings::{Embedder, HashEmbedder};
+    use crate::db::testing::{add_document, open_db};
+    use crate::db::{chunks, vec};
+
+    fn count(conn: &Connection, sql: &str, id: &str) -> i64 {
+        conn.query_row(sql, params![id], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn trashed_nodes_can_be_listed_and_restored() {
+        let mut conn = open_db();
+        let embedder = HashEmbedder::default();
+        let id = add_document(&mut conn, &embedder, "Rust", "Every Rust value has a single owner.");
+
+        assert!(soft_delete(&conn, &id).unwrap());
+        assert!(!soft_delete(&conn, &id).unwrap());
+        let trashed = list(&conn).unwrap();
+        assert_eq!(trashed.len(), 1);
+        assert_eq!(trashed[0].node.id, id);
+        assert_eq!(trashed_ids(&conn).unwrap(), vec![id.clone()]);
+
+        // Trashing keeps everything needed to bring the node back
+        let index = vec::find_index(&conn, embedder.model_id(), embedder.dimension()).unwrap().unwrap();
+        assert!(vec::count_node_vectors(&conn, &index, &id).unwrap() > 0);
+
+        assert!(restore(&conn, &id).unwrap());
+        assert!(!restore(&conn, &id).unwrap());
+        assert!(list(&conn).unwrap().is_empty());
+        assert!(!restore(&conn, "missing").unwrap());
+    }
+
+    #[test]
+    fn purge_removes_the_node_and_everything_derived_from_it() {
+        let mut conn = open_db();
+        let embedder = HashEmbedder::default();
+        let id = add_document(&mut conn, &embedder, "Rust", "Every Rust value has a single owner.");
+        let other = add_document(&mut conn, &embedder, "Borrowing", "References borrow a value without owning it.");
+        conn.execute(
+            "INSERT INTO edges (id, source, target, label) VALUES ('e1', ?1, ?2, 'links_to')",
+            params![id, other],
+        )
+        .unwrap();
+        let metadata = serde_json::json!({
+            "original_path": "rust.pdf",
+            "previous_versions": [{ "content_path": "rust.v1.md" }, { "content_path": "rust.v2.md" }],
+        });
+        conn.execute(
+            "UPDATE nodes SET metadata = ?1 WHERE id = ?2",
+            params![metadata.to_string(), id],
+        )
+        .unwrap();
+
+        // A vector in a second index, as left behind by a model switch
+        let index = vec::find_index(&conn, embedder.model_id(), embedder.dimension()).unwrap().unwrap();
+        let old_index = vec::ensure_index(&conn, "old-model", 4).unwrap();
+        let chunk_id = chunks::for_node(&conn, &id).unwrap()[0].id;
+        vec::insert_embedding(&conn, &old_index, chunk_id, &id, &[0.5, 0.5, 0.5, 0.5]).unwrap();
+
+        let purged = purge(&mut conn, &id).unwrap().unwrap();
+        assert_eq!(purged.content_path, Some(format!("{}.md", id)));
+        assert_eq!(purged.original_path.as_deref(), Some("rust.pdf"));
+        assert_eq!(purged.previous_paths, vec!["rust.v1.md", "rust.v2.md"]);
+
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM nodes WHERE id = ?1", &id), 0);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM nodes_fts WHERE id = ?1", &id), 0);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM edges WHERE source = ?1 OR target = ?1", &id), 0);
+        assert!(chunks::for_node(&conn, &id).unwrap().is_empty());
+        assert_eq!(vec::count_node_vectors(&conn, &index, &id).unwrap(), 0);
+        assert_eq!(vec::count_node_vectors(&conn, &old_index, &id).unwrap(), 0);
+
+        // The other node is untouched
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM nodes_fts WHERE id = ?1", &other), 1);
+        assert!(vec::count_node_vectors(&conn, &index, &other).unwrap() > 0);
+
+        assert!(purge(&mut conn, &id).unwrap().is_none());
+    }
+}
@@ -1,4 +1,67 @@
-use rusqlite::{Connection, Result};
+use super::chunks::{self, ChunkSpan};
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use std::fmt;
+
+/// Model and dimension of the vectors stored before per-model tables existed.
+const LEGACY_MODEL: &str = "nomic-embed-text";
+const LEGACY_DIMENSION: usize = 768;
+
+/// One sqlite-vec table holding embeddings produced by a single model.
+#[derive(Debug, Clone)]
+pub struct VecIndex {
+    pub table_name: String,
+    pub model_id: String,
+    pub dimension: usize,
+}
+
+/// A nearest-neighbour hit from a vector index.
+#[derive(Debug, Clone)]
+pub struct VecHit {
+    /// Id of the chunk the vector was computed from.
+    pub rowid: i64,
+    pub node_id: String,
+    pub distance: f32,
+}
+
+/// Raised when a vector does not have the dimension of the index it is
+/// written to or searched against.
+#[derive(Debug)]
+pub struct DimensionMismatch {
+    pub table_name: String,
+    pub expected: usize,
+    pub actual: usize,
+}
+
+impl fmt::Display for DimensionMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Embedding has {} dimensions but index {} expects {}",
+            self.actual, self.table_name, self.expected
+        )
+    }
+}
+
+impl std::error::Error for DimensionMismatch {}
+
+/// Raised when searching with a model that has no index yet.
+#[derive(Debug)]
+pub struct MissingIndex {
+    pub model_id: String,
+    pub dimension: usize,
+}
+
+impl fmt::Display for MissingIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "No index for model {} (dimension {}); run re-index",
+            self.model_id, self.dimension
+        )
+    }
+}
+
+impl std::error::Error for MissingIndex {}
 
 /// Initialize vector search extension and tables
 /// Note: This requires the sqlite-vec extension to be loaded at runtime
@@ -9,55 +72,354 @@ pub fn init_vector_tables(conn: &Connection) -> Result<()> {
     // We create a virtual table for full-text search on node content
     conn.execute(
         "CREATE VIRTUAL TABLE IF NOT EXISTS nodes_fts USING fts5(
-            id UNINDEXED, 
-            title, 
-            content, 
+            id UNINDEXED,
+            title,
+            content,
             tokenize = 'porter'
         )",
         [],
     )?;
 
-    // 2. Enable Vector Search
-    // We create a virtual table for vector embeddings using sqlite-vec
-    // Defaulting to 768 dimensions (common for nomic-embed-text / mxbai-embed-large)
-    // If using OpenAI text-embedding-3-small, this would be 1536.
-    // The dimension must match the embedding model output.
+    // 2. Registry of vector tables
+    // Every embedding model/dimension pair gets its own vec0 table, since
+    // vectors of different models are not comparable (even at equal size).
     conn.execute(
-        "CREATE VIRTUAL TABLE IF NOT EXISTS nodes_vec USING vec0(
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            node_id TEXT,
-            embedding float[768]
+        "CREATE TABLE IF NOT EXISTS vec_indexes (
+            model_id TEXT NOT NULL,
+            dimension INTEGER NOT NULL,
+            table_name TEXT NOT NULL UNIQUE,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (model_id, dimension)
         )",
         [],
     )?;
 
+    // 3. Adopt the old single `nodes_vec` table (768-d, always embedded with
+    // nomic-embed-text by search and chat) instead of dropping its vectors.
+    let legacy_exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'nodes_vec')",
+        [],
+        |row| row.get(0),
+    )?;
+    if legacy_exists {
+        conn.execute(
+            "INSERT OR IGNORE INTO vec_indexes (model_id, dimension, table_name) VALUES (?1, ?2, 'nodes_vec')",
+            params![LEGACY_MODEL, LEGACY_DIMENSION as i64],
+        )?;
+    }
+
     Ok(())
 }
 
-/// Helper to add a node to the search index
-pub fn index_node(conn: &Connection, node_id: &str, title: &str, content: &str, embedding: &[f32]) -> Result<()> {
-    // Insert into FTS
+/// Move the vectors of the adopted `nodes_vec` table, which uses the L2
+/// metric, into a cosine table registered in its place. Chunk ids are
+/// kept as rowids.
+pub(crate) fn recreate_legacy_index(conn: &Connection) -> Result<()> {
+    let registered = conn
+        .query_row(
+            "SELECT 1 FROM vec_indexes WHERE table_name = 'nodes_vec'",
+            [],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    if !registered {
+        return Ok(());
+    }
+
+    let table_name = table_name_for(LEGACY_MODEL, LEGACY_DIMENSION);
+    create_vec_table(conn, &table_name, LEGACY_DIMENSION)?;
     conn.execute(
-        "INSERT INTO nodes_fts (id, title, content) VALUES (?1, ?2, ?3)",
-        (node_id, title, content),
+        &format!(
+            "INSERT INTO {} (rowid, node_id, embedding) SELECT rowid, node_id, embedding FROM nodes_vec",
+            table_name
+        ),
+        [],
+    )?;
+    conn.execute(
+        "UPDATE vec_indexes SET table_name = ?1 WHERE table_name = 'nodes_vec'",
+        params![table_name],
     )?;
+    conn.execute("DROP TABLE nodes_vec", [])?;
+    Ok(())
+}
+
+/// Name of the vec0 table for a model, e.g. `vec_nomic_embed_text_768`.
+pub fn table_name_for(model_id: &str, dimension: usize) -> String {
+    let mut slug = String::new();
+    for c in model_id.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.ends_with('_') {
+            slug.push('_');
+        }
+    }
+    format!("vec_{}_{}", slug.trim_matches('_'), dimension)
+}
+
+/// Look up the index holding vectors of `model_id`, if any were ever stored.
+pub fn find_index(conn: &Connection, model_id: &str, dimension: usize) -> Result<Option<VecIndex>> {
+    conn.query_row(
+        "SELECT table_name FROM vec_indexes WHERE model_id = ?1 AND dimension = ?2",
+        params![model_id, dimension as i64],
+        |row| row.get::<_, String>(0),
+    )
+    .optional()
+    .map(|table| {
+        table.map(|table_name| VecIndex {
+            table_name,
+            model_id: model_id.to_string(),
+            dimension,
+        })
+    })
+}
+
+/// Like `find_index`, but a missing index is an error, for searches that
+/// would otherwise silently come back empty.
+pub fn require_index(conn: &Connection, model_id: &str, dimension: usize) -> std::result::Result<VecIndex, String> {
+    find_index(conn, model_id, dimension)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| {
+            MissingIndex {
+                model_id: model_id.to_string(),
+                dimension,
+            }
+            .to_string()
+        })
+}
+
+/// All registered indexes.
+pub fn list_indexes(conn: &Connection) -> Result<Vec<VecIndex>> {
+    let mut stmt = conn.prepare("SELECT table_name, model_id, dimension FROM vec_indexes")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(VecIndex {
+            table_name: row.get(0)?,
+            model_id: row.get(1)?,
+            dimension: row.get::<_, i64>(2)? as usize,
+        })
+    })?;
+    rows.collect()
+}
 
-    // Insert into Vector table
-    // Note: sqlite-vec expects raw bytes or specific format depending on version.
-    // For this stub, we assume standard insertion.
-    // Ensure embedding length matches schema (768).
-    if embedding.len() != 768 {
-        // In a real app, handle resizing or error
-        // println!("Warning: Embedding dimension mismatch");
+/// Get the index for `model_id`, creating its table on first use.
+pub fn ensure_index(conn: &Connection, model_id: &str, dimension: usize) -> Result<VecIndex> {
+    if let Some(index) = find_index(conn, model_id, dimension)? {
+        return Ok(index);
     }
 
+    let table_name = table_name_for(model_id, dimension);
+    create_vec_table(conn, &table_name, dimension)?;
+    conn.execute(
+        "INSERT INTO vec_indexes (model_id, dimension, table_name) VALUES (?1, ?2, ?3)",
+        params![model_id, dimension as i64, table_name],
+    )?;
+
+    Ok(VecIndex {
+        table_name,
+        model_id: model_id.to_string(),
+        dimension,
+    })
+}
+
+/// Create a vec0 table. `table_name` must come from `table_name_for`,
+/// as it is interpolated into the statement.
+pub(crate) fn create_vec_table(conn: &Connection, table_name: &str, dimension: usize) -> Result<()> {
+    conn.execute(
+        &format!(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS {} USING vec0(
+                node_id TEXT,
+                embedding float[{}] distance_metric=cosine
+            )",
+            table_name, dimension
+        ),
+        [],
+    )?;
+    Ok(())
+}
+
+/// Create an unregistered table for rebuilding the index of `model_id`.
+/// It stays invisible to search until passed to `activate_index`.
+pub fn create_staging_index(conn: &Connection, model_id: &str, dimension: usize) -> Result<VecIndex> {
+    let table_name = format!(
+        "{}_{}",
+        table_name_for(model_id, dimension),
+        chrono::Utc::now().timestamp_millis()
+    );
+    create_vec_table(conn, &table_name, dimension)?;
+    Ok(VecIndex {
+        table_name,
+        model_id: model_id.to_string(),
+        dimension,
+    })
+}
+
+/// Register `index` for its model in place of the model's current index,
+/// whose table is dropped. Indexes of other models are kept, so switching
+/// back to one needs no re-index. Call inside a transaction so readers
+/// never see a half-swapped state.
+pub fn activate_index(conn: &Connection, index: &VecIndex) -> Result<()> {
+    if let Some(old) = find_index(conn, &index.model_id, index.dimension)? {
+        if old.table_name != index.table_name {
+            conn.execute(&format!("DROP TABLE IF EXISTS {}", old.table_name), [])?;
+        }
+        conn.execute(
+            "DELETE FROM vec_indexes WHERE model_id = ?1 AND dimension = ?2",
+            params![index.model_id, index.dimension as i64],
+        )?;
+    }
+    conn.execute(
+        "INSERT INTO vec_indexes (model_id, dimension, table_name) VALUES (?1, ?2, ?3)",
+        params![index.model_id, index.dimension as i64, index.table_name],
+    )?;
+    Ok(())
+}
+
+/// Nodes whose chunks and vectors in `index` disagree: a chunk has no
+/// vector, or a vector's chunk is gone.
+pub fn out_of_date_nodes(conn: &Connection, index: &VecIndex) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT node_id FROM chunks WHERE id NOT IN (SELECT rowid FROM {0})
+         UNION
+         SELECT node_id FROM {0} WHERE rowid NOT IN (SELECT id FROM chunks)",
+        index.table_name
+    ))?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// Remove the vectors `index` holds for `node_id`.
+pub fn delete_index_vectors(conn: &Connection, index: &VecIndex, node_id: &str) -> Result<()> {
+    conn.execute(
+        &format!("DELETE FROM {} WHERE node_id = ?1", index.table_name),
+        params![node_id],
+    )?;
+    Ok(())
+}
+
+/// Copy the vectors of `node_id`'s current chunks from one index to
+/// another of the same model. Returns how many were copied.
+pub fn copy_node_vectors(conn: &Connection, from: &VecIndex, to: &VecIndex, node_id: &str) -> Result<usize> {
+    conn.execute(
+        &format!(
+            "INSERT INTO {} (rowid, node_id, embedding)
+             SELECT rowid, node_id, embedding FROM {}
+             WHERE node_id = ?1 AND rowid IN (SELECT id FROM chunks WHERE node_id = ?1)",
+            to.table_name, from.table_name
+        ),
+        params![node_id],
+    )
+}
+
+/// Drop the table behind an index that was never activated.
+pub fn drop_staging_index(conn: &Connection, index: &VecIndex) -> Result<()> {
+    conn.execute(&format!("DROP TABLE IF EXISTS {}", index.table_name), [])?;
+    Ok(())
+}
+
+fn check_dimension(index: &VecIndex, embedding: &[f32]) -> Result<()> {
+    if embedding.len() != index.dimension {
+        return Err(rusqlite::Error::ToSqlConversionFailure(Box::new(DimensionMismatch {
+            table_name: index.table_name.clone(),
+            expected: index.dimension,
+            actual: embedding.len(),
+        })));
+    }
+    Ok(())
+}
+
+fn to_json(embedding: &[f32]) -> Result<String> {
     // Serialize the embedding to a JSON string for sqlite-vec
-    let embedding_json = serde_json::to_string(embedding).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    serde_json::to_string(embedding).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+/// Store the embedding of chunk `chunk_id`, using the chunk id as rowid.
+pub fn insert_embedding(
+    conn: &Connection,
+    index: &VecIndex,
+    chunk_id: i64,
+    node_id: &str,
+    embedding: &[f32],
+) -> Result<()> {
+    check_dimension(index, embedding)?;
+    conn.execute(
+        &format!("INSERT INTO {} (rowid, node_id, embedding) VALUES (?1, ?2, ?3)", index.table_name),
+        params![chunk_id, node_id, to_json(embedding)?],
+    )?;
+    Ok(())
+}
+
+/// K nearest neighbours of `embedding` in `index`, closest first.
+pub fn search(conn: &Connection, index: &VecIndex, embedding: &[f32], k: usize) -> Result<Vec<VecHit>> {
+    check_dimension(index, embedding)?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT rowid, node_id, distance
+         FROM {}
+         WHERE embedding MATCH ?1 AND k = ?2
+         ORDER BY distance",
+        index.table_name
+    ))?;
+    let rows = stmt.query_map(params![to_json(embedding)?, k as i64], |row| {
+        Ok(VecHit {
+            rowid: row.get(0)?,
+            node_id: row.get(1)?,
+            distance: row.get::<_, f64>(2)? as f32,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Remove every vector of `node_id` from all registered indexes.
+pub fn delete_node_vectors(conn: &Connection, node_id: &str) -> Result<()> {
+    for index in list_indexes(conn)? {
+        conn.execute(
+            &format!("DELETE FROM {} WHERE node_id = ?1", index.table_name),
+            params![node_id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Number of vectors `index` holds for `node_id`.
+pub fn count_node_vectors(conn: &Connection, index: &VecIndex, node_id: &str) -> Result<usize> {
+    let count: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM {} WHERE node_id = ?1", index.table_name),
+        params![node_id],
+        |row| row.get(0),
+    )?;
+    Ok(count as usize)
+}
 
+/// Replace the full-text entry of a node.
+pub fn update_fts(conn: &Connection, node_id: &str, title: &str, content: &str) -> Result<()> {
+    conn.execute("DELETE FROM nodes_fts WHERE id = ?1", params![node_id])?;
     conn.execute(
-        "INSERT INTO nodes_vec (node_id, embedding) VALUES (?1, ?2)",
-        rusqlite::params![node_id, embedding_json],
+        "INSERT INTO nodes_fts (id, title, content) VALUES (?1, ?2, ?3)",
+        (node_id, title, content),
     )?;
+    Ok(())
+}
+
+/// Helper to add a node to the search index
+pub fn index_node(
+    conn: &Connection,
+    index: &VecIndex,
+    node_id: &str,
+    title: &str,
+    content: &str,
+    spans: &[ChunkSpan],
+    embeddings: &[Vec<f32>],
+) -> Result<()> {
+    // Insert into FTS
+    conn.execute(
+        "INSERT INTO nodes_fts (id, title, content) VALUES (?1, ?2, ?3)",
+        (node_id, title, content),
+    )?;
+
+    // Insert chunks, then one vector per chunk keyed by its id
+    let chunk_ids = chunks::replace(conn, node_id, spans)?;
+    for (chunk_id, embedding) in chunk_ids.iter().zip(embeddings) {
+        insert_embedding(conn, index, *chunk_id, node_id, embedding)?;
+    }
 
     Ok(())
 }
@@ -6,14 +6,98 @@ use tauri::ipc::Response;
 pub struct Workspace {
     pub root: PathBuf,
     pub artifacts: PathBuf,
+    pub staging: PathBuf,
+}
+
+/// An artifact written to the staging area, invisible to readers until
+/// `publish` moves it into place. Dropped unpublished, it is deleted.
+pub struct StagedArtifact {
+    staged: PathBuf,
+    target: PathBuf,
+    committed: bool,
+}
+
+/// An artifact moved into place that can still be taken back, until the
+/// write it belongs to is known to have succeeded.
+pub struct PublishedArtifact {
+    pub path: PathBuf,
+    /// A link to the file it replaced, if any.
+    backup: Option<PathBuf>,
+}
+
+impl StagedArtifact {
+    /// Atomically move the file to its final name, keeping the file it
+    /// replaces until `PublishedArtifact::finish`, so `rollback` can bring
+    /// it back.
+    pub fn publish(mut self) -> io::Result<PublishedArtifact> {
+        // A hard link keeps the old content without ever leaving the
+        // target missing
+        let backup = if self.target.exists() {
+            let backup = self.staged.with_extension("bak");
+            fs::hard_link(&self.target, &backup).or_else(|_| fs::copy(&self.target, &backup).map(|_| ()))?;
+            Some(backup)
+        } else {
+            None
+        };
+        if let Err(e) = fs::rename(&self.staged, &self.target) {
+            if let Some(backup) = &backup {
+                let _ = fs::remove_file(backup);
+            }
+            return Err(e);
+        }
+        self.committed = true;
+        Ok(PublishedArtifact { path: self.target.clone(), backup })
+    }
+}
+
+impl PublishedArtifact {
+    /// Restore what was there before: the replaced file, or nothing.
+    pub fn rollback(self) -> io::Result<()> {
+        match &self.backup {
+            Some(backup) => fs::rename(backup, &self.path),
+            None => match fs::remove_file(&self.path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            },
+        }
+    }
+
+    /// Let go of the replaced file.
+    pub fn finish(self) {
+        if let Some(backup) = &self.backup {
+            let _ = fs::remove_file(backup);
+        }
+    }
+}
+
+impl Drop for StagedArtifact {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.staged);
+        }
+    }
+}
+
+#[cfg(not(test))]
+fn data_root() -> io::Result<PathBuf> {
+    let home = dirs::home_dir().ok_or(io::Error::new(io::ErrorKind::NotFound, "Home directory not found"))?;
+    Ok(home.join(".research_data"))
+}
+
+/// Tests share a scratch workspace per run rather than the user's data.
+/// Artifacts are named after node ids, so they never collide.
+#[cfg(test)]
+fn data_root() -> io::Result<PathBuf> {
+    Ok(std::env::temp_dir().join(format!("research-test-workspace-{}", std::process::id())))
 }
 
 impl Workspace {
     pub fn new() -> io::Result<Self> {
-        let home = dirs::home_dir().ok_or(io::Error::new(io::ErrorKind::NotFound, "Home directory not found"))?;
-        let root = home.join(".research_data");
+        let root = data_root()?;
         let artifacts = root.join("artifacts");
-        Ok(Self { root, artifacts })
+        // Next to artifacts so publishing is a same-filesystem rename
+        let staging = root.join("staging");
+        Ok(Self { root, artifacts, staging })
     }
 
     pub fn ensure_workspace(&self) -> io::Result<()> {
@@ -26,12 +110,29 @@ impl Workspace {
         Ok(())
     }
 
-    pub fn write_artifact(&self, filename: &str, content: &[u8]) -> io::Result<PathBuf> {
+    /// Write `content` to the staging area; see `StagedArtifact`.
+    pub fn stage_artifact(&self, filename: &str, content: &[u8]) -> io::Result<StagedArtifact> {
         self.ensure_workspace()?;
-        let path = self.artifacts.join(filename);
-        let mut file = fs::File::create(&path)?;
+        fs::create_dir_all(&self.staging)?;
+        let staged = self.staging.join(format!("{}.{}.tmp", filename, uuid::Uuid::new_v4()));
+        let artifact = StagedArtifact {
+            staged,
+            target: self.artifacts.join(filename),
+            committed: false,
+        };
+        // On error the dropped artifact deletes the partial file
+        let mut file = fs::File::create(&artifact.staged)?;
         file.write_all(content)?;
-        Ok(path)
+        file.sync_all()?;
+        Ok(artifact)
+    }
+
+    /// Remove staged files left behind by an interrupted write.
+    pub fn clear_staging(&self) -> io::Result<()> {
+        if self.staging.exists() {
+            fs::remove_dir_all(&self.staging)?;
+        }
+        Ok(())
     }
 
     pub fn read_artifact(&self, filename: &str) -> io::Result<Vec<u8>> {
@@ -5,20 +5,39 @@ pub mod commands;
 pub mod db;
 pub mod fs_manager;
 pub mod models;
+pub mod scraper;
+pub mod settings;
 
-use commands::nodes::{create_node, get_node, save_node_content, delete_node};
+use commands::nodes::{
+    create_node, get_node, save_node_content, delete_node, restore_node, list_trash, purge_node, empty_trash,
+};
 use commands::graph::{connect_nodes, disconnect_nodes, get_graph_data, update_node_position};
-use commands::ingest::ingest_url;
+use commands::ingest::{ingest_file, ingest_folder, ingest_url};
+use commands::crawl::{cancel_crawl, pause_crawl, resume_crawl, start_crawl, CrawlJobs};
+use commands::refresh::{get_source_changes, refresh_source, refresh_sources, schedule_source_refresh};
 use commands::search::search_nodes;
-use commands::chat::chat;
+use commands::chat::{chat, chat_stream, cancel_chat, ChatStreams};
+use commands::reindex::{reindex_embeddings, schedule_stale_nodes, PendingNodeIndexes};
+use commands::sessions::{
+    create_chat_session, delete_chat_session, list_chat_sessions, open_chat_session,
+    rename_chat_session, save_chat_session_as_node,
+};
+use commands::settings::{get_settings, update_settings};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .setup(|app| {
+            // Staged artifacts of ingests interrupted by a previous exit
+            if let Ok(ws) = fs_manager::Workspace::new() {
+                let _ = ws.clear_staging();
+            }
+
             match db::init(app.handle()) {
                 Ok(state) => {
                     app.manage(state);
+                    schedule_stale_nodes(app.handle());
+                    schedule_source_refresh(app.handle());
                     Ok(())
                 },
                 Err(e) => {
@@ -27,19 +46,46 @@ pub fn run() {
                 }
             }
         })
+        .manage(ChatStreams::default())
+        .manage(PendingNodeIndexes::default())
+        .manage(CrawlJobs::default())
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
             create_node,
             get_node,
             save_node_content,
             delete_node,
+            restore_node,
+            list_trash,
+            purge_node,
+            empty_trash,
             connect_nodes,
             disconnect_nodes,
             get_graph_data,
             update_node_position,
             ingest_url,
+            ingest_file,
+            ingest_folder,
+            start_crawl,
+            pause_crawl,
+            resume_crawl,
+            cancel_crawl,
+            refresh_source,
+            refresh_sources,
+            get_source_changes,
             search_nodes,
-            chat
+            chat,
+            chat_stream,
+            cancel_chat,
+            reindex_embeddings,
+            get_settings,
+            update_settings,
+            list_chat_sessions,
+            create_chat_session,
+            open_chat_session,
+            rename_chat_session,
+            delete_chat_session,
+            save_chat_session_as_node
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,3 +1,4 @@
+use crate::ai::rag::Source;
 use serde::{Serialize, Deserialize};
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -11,6 +12,20 @@ pub struct Node {
     pub metadata: Option<serde_json::Value>,
     pub created_at: String,
     pub updated_at: String,
+    /// One of the `index_status` values.
+    pub index_status: String,
+}
+
+/// Whether a node's search index reflects its current content.
+pub mod index_status {
+    /// Chunks, vectors and full-text entry match the content.
+    pub const INDEXED: &str = "indexed";
+    /// The content changed and a re-index is pending.
+    pub const STALE: &str = "stale";
+    /// A re-index is running.
+    pub const INDEXING: &str = "indexing";
+    /// The last re-index failed; the previous index rows are kept.
+    pub const FAILED: &str = "failed";
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -22,3 +37,27 @@ pub struct Edge {
     pub label: String,
     pub created_at: String,
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatSession {
+    pub id: String,
+    pub title: String,
+    pub message_count: usize,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatSessionMessage {
+    pub id: i64,
+    pub session_id: String,
+    pub role: String,
+    pub content: String,
+    /// Model that produced an assistant message.
+    pub model: Option<String>,
+    /// Sources cited by an assistant message.
+    pub sources: Vec<Source>,
+    pub created_at: String,
+}
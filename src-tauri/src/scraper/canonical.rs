@@ -0,0 +1,86 @@
+use reqwest::Url;
+
+/// Query parameters that only track where a visitor came from.
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "yclid", "twclid", "igshid",
+    "mc_cid", "mc_eid", "_ga", "_gl", "_hsenc", "_hsmi", "mkt_tok", "ref_src", "ref_url",
+    "spm", "si",
+];
+
+/// Query parameter prefixes of tracking families, like `utm_source`.
+const TRACKING_PREFIXES: &[&str] = &["utm_", "pk_", "mtm_", "hsa_", "oly_"];
+
+/// The form of a URL used to tell whether two URLs are the same page: no
+/// fragment, no tracking parameters, the rest of the query sorted, and no
+/// trailing slash except on the root. Scheme and host case and default
+/// ports are already normalized by `Url`.
+pub fn normalize(url: &Url) -> Url {
+    let mut url = url.clone();
+    url.set_fragment(None);
+
+    let mut pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !is_tracking(key))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    if pairs.is_empty() {
+        url.set_query(None);
+    } else {
+        pairs.sort();
+        url.query_pairs_mut().clear().extend_pairs(&pairs);
+    }
+
+    let path = url.path().to_string();
+    if path.len() > 1 && path.ends_with('/') {
+        url.set_path(path.trim_end_matches('/'));
+    }
+    url
+}
+
+fn is_tracking(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    TRACKING_PARAMS.contains(&key.as_str()) || TRACKING_PREFIXES.iter().any(|p| key.starts_with(p))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalized(url: &str) -> String {
+        normalize(&Url::parse(url).unwrap()).to_string()
+    }
+
+    #[test]
+    fn drops_fragment_and_tracking_parameters() {
+        assert_eq!(
+            normalized("https://example.com/post?utm_source=x&id=3&fbclid=abc&UTM_Medium=y#comments"),
+            "https://example.com/post?id=3"
+        );
+        assert_eq!(normalized("https://example.com/post?gclid=1"), "https://example.com/post");
+    }
+
+    #[test]
+    fn sorts_the_remaining_query() {
+        assert_eq!(normalized("https://example.com/s?q=rust&page=2"), "https://example.com/s?page=2&q=rust");
+        assert_eq!(normalized("https://example.com/s?page=2&q=rust"), "https://example.com/s?page=2&q=rust");
+    }
+
+    #[test]
+    fn trims_trailing_slash_except_on_root() {
+        assert_eq!(normalized("https://example.com/docs/"), "https://example.com/docs");
+        assert_eq!(normalized("https://example.com/"), "https://example.com/");
+        assert_eq!(normalized("https://example.com"), "https://example.com/");
+    }
+
+    #[test]
+    fn normalizes_scheme_host_and_default_port() {
+        assert_eq!(normalized("HTTPS://Example.COM:443/Path"), "https://example.com/Path");
+        assert_eq!(normalized("http://example.com:8080/a/"), "http://example.com:8080/a");
+    }
+
+    #[test]
+    fn is_idempotent() {
+        let once = normalized("https://example.com/a/?b=2&a=1&utm_campaign=z#top");
+        assert_eq!(normalized(&once), once);
+    }
+}
@@ -1,28 +1,490 @@
-/// Chunk text into approximately N tokens
-/// For now, we approximate 1 token ~= 0.75 words, so 500 tokens ~= 375 words.
-/// We'll use a word-based chunker.
-pub fn chunk_text(text: &str, target_token_count: usize) -> Vec<String> {
-    let words: Vec<&str> = text.split_whitespace().collect();
-    let words_per_chunk = (target_token_count as f32 * 0.75) as usize; // Approximation
-    
-    let mut chunks = Vec::new();
-    let mut current_chunk = Vec::new();
-    let mut current_count = 0;
+use super::pdf::parse_page_marker;
+use crate::db::chunks::ChunkSpan;
+use std::sync::OnceLock;
+use tiktoken_rs::CoreBPE;
+
+pub const DEFAULT_MAX_TOKENS: usize = 512;
+pub const DEFAULT_OVERLAP_TOKENS: usize = 64;
 
-    for word in words {
-        current_chunk.push(word);
-        current_count += 1;
+/// Chunk sizes, in tokens of the bundled `cl100k_base` vocabulary.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkOptions {
+    pub max_tokens: usize,
+    /// Tokens of the previous chunk repeated at the start of the next one,
+    /// so a passage cut at a boundary is still whole in one of them.
+    pub overlap_tokens: usize,
+}
 
-        if current_count >= words_per_chunk {
-            chunks.push(current_chunk.join(" "));
-            current_chunk.clear();
-            current_count = 0;
+impl Default for ChunkOptions {
+    fn default() -> Self {
+        Self {
+            max_tokens: DEFAULT_MAX_TOKENS,
+            overlap_tokens: DEFAULT_OVERLAP_TOKENS,
         }
     }
+}
+
+fn tokenizer() -> &'static CoreBPE {
+    static BPE: OnceLock<CoreBPE> = OnceLock::new();
+    // The vocabulary is compiled into the binary, so this cannot fail at runtime
+    BPE.get_or_init(|| tiktoken_rs::cl100k_base().expect("bundled cl100k_base vocabulary"))
+}
+
+/// Number of BPE tokens in `text`.
+pub fn count_tokens(text: &str) -> usize {
+    tokenizer().encode_ordinary(text).len()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BlockKind {
+    Heading,
+    Prose,
+    /// Lists, tables and quotes, split by line.
+    Lines,
+    Code,
+}
+
+/// A heading, paragraph, list, table or code block.
+#[derive(Debug)]
+struct Block {
+    kind: BlockKind,
+    start: usize,
+    end: usize,
+    /// Index into the heading paths.
+    path: usize,
+    /// Page it is on, for text with page markers.
+    page: Option<u32>,
+}
+
+/// A run of text that is never split across chunks unless it alone
+/// exceeds the limit.
+#[derive(Debug)]
+struct Unit {
+    start: usize,
+    end: usize,
+    tokens: usize,
+    /// Index of the block it came from; chunks prefer to break between blocks.
+    block: usize,
+    heading: bool,
+    /// Index into the heading paths.
+    path: usize,
+    page: Option<u32>,
+}
 
-    if !current_chunk.is_empty() {
-        chunks.push(current_chunk.join(" "));
+/// Split Markdown (or plain text) into chunks of at most
+/// `options.max_tokens`, breaking at headings, then paragraphs, then
+/// sentences. Each chunk carries the headings it sits under; chunks after
+/// the first repeat up to `options.overlap_tokens` of the text before them.
+/// Text extracted from PDFs has page markers, and its chunks also carry
+/// the pages they cover.
+pub fn chunk_text(text: &str, options: &ChunkOptions) -> Vec<ChunkSpan> {
+    let max = options.max_tokens.max(1);
+    let (blocks, paths) = blocks(text);
+
+    let mut units = Vec::new();
+    for (index, block) in blocks.iter().enumerate() {
+        for (start, end) in split_block(text, block, max) {
+            units.push(Unit {
+                start,
+                end,
+                tokens: count_tokens(&text[start..end]),
+                block: index,
+                heading: block.kind == BlockKind::Heading,
+                path: block.path,
+                page: block.page,
+            });
+        }
     }
 
+    let mut chunks = Vec::new();
+    let (mut i, mut fresh) = (0, 0);
+    while i < units.len() {
+        let end = chunk_end(&units, i, fresh, max);
+        let (start_byte, end_byte) = (units[i].start, units[end - 1].end);
+        let chunk = &text[start_byte..end_byte];
+        let pages = units[i..end].iter().filter_map(|u| u.page);
+        chunks.push(ChunkSpan {
+            start: start_byte,
+            end: end_byte,
+            text: chunk.to_string(),
+            token_count: count_tokens(chunk),
+            heading_path: paths[units[i].path].clone(),
+            page_start: pages.clone().min(),
+            page_end: pages.max(),
+        });
+
+        if end == units.len() {
+            break;
+        }
+        i = overlap_start(&units, i, end, options.overlap_tokens.min(max.saturating_sub(units[end].tokens)));
+        fresh = end;
+    }
     chunks
 }
+
+/// Exclusive end of the chunk starting at unit `start`. Units before
+/// `fresh` repeat the previous chunk, so at least one past them is taken.
+fn chunk_end(units: &[Unit], start: usize, fresh: usize, max: usize) -> usize {
+    let mut end = start;
+    let mut tokens = 0;
+    while end < units.len() {
+        let unit = &units[end];
+        if end > start && end > fresh {
+            // A new section starts a new chunk once this one has some substance
+            if unit.heading && tokens >= max / 4 {
+                break;
+            }
+            // Headings always stay with what follows them
+            if tokens + unit.tokens > max && !units[end - 1].heading {
+                break;
+            }
+        }
+        tokens += unit.tokens;
+        end += 1;
+    }
+
+    // Cut at the last paragraph boundary rather than mid-paragraph, unless
+    // that leaves the chunk less than half full
+    let mid_block = end < units.len() && !units[end].heading && units[end].block == units[end - 1].block;
+    if mid_block {
+        let mut kept = tokens;
+        for k in (start.max(fresh) + 1..end).rev() {
+            kept -= units[k].tokens;
+            if kept < max / 2 {
+                break;
+            }
+            if units[k].block != units[k - 1].block && !units[k - 1].heading {
+                return k;
+            }
+        }
+    }
+    end
+}
+
+/// Where the chunk after `start..end` begins: far enough back to repeat up
+/// to `overlap` tokens, without crossing into another section.
+fn overlap_start(units: &[Unit], start: usize, end: usize, overlap: usize) -> usize {
+    let mut next = end;
+    let mut tokens = 0;
+    while next > start + 1 {
+        let unit = &units[next - 1];
+        if unit.heading || unit.path != units[end].path || tokens + unit.tokens > overlap {
+            break;
+        }
+        tokens += unit.tokens;
+        next -= 1;
+    }
+    next
+}
+
+/// Blocks of `text`, and the heading paths they refer to.
+fn blocks(text: &str) -> (Vec<Block>, Vec<Vec<String>>) {
+    let mut blocks = Vec::new();
+    let mut paths: Vec<Vec<String>> = vec![Vec::new()];
+    let mut headings: Vec<(usize, String)> = Vec::new();
+
+    let mut current: Option<(BlockKind, usize, usize)> = None;
+    let mut fence: Option<(char, usize)> = None;
+    let mut page = None;
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let content = line.trim_end_matches(['\n', '\r']);
+        let trimmed = content.trim_start();
+        let content_start = line_start + (content.len() - trimmed.len());
+        let content_end = line_start + content.len();
+        let path = paths.len() - 1;
+
+        if let Some((marker, len)) = fence {
+            if let Some((_, start, _)) = current {
+                current = Some((BlockKind::Code, start, content_end));
+            }
+            if trimmed.starts_with(&marker.to_string().repeat(len)) && trimmed.trim_start_matches(marker).trim().is_empty() {
+                fence = None;
+                close(&mut current, &mut blocks, path, page);
+            }
+            continue;
+        }
+
+        // Page markers are not content; they only move the page on
+        if let Some(number) = parse_page_marker(trimmed) {
+            close(&mut current, &mut blocks, path, page);
+            page = Some(number);
+            continue;
+        }
+
+        if let Some(opening) = fence_marker(trimmed) {
+            close(&mut current, &mut blocks, path, page);
+            fence = Some(opening);
+            current = Some((BlockKind::Code, content_start, content_end));
+            continue;
+        }
+
+        if trimmed.is_empty() {
+            close(&mut current, &mut blocks, path, page);
+            continue;
+        }
+
+        let indent = content.len() - trimmed.len();
+        if let Some((level, title)) = heading(trimmed).filter(|_| indent < 4) {
+            close(&mut current, &mut blocks, path, page);
+            headings.retain(|(l, _)| *l < level);
+            headings.push((level, title));
+            paths.push(headings.iter().map(|(_, t)| t.clone()).collect());
+            blocks.push(Block {
+                kind: BlockKind::Heading,
+                start: content_start,
+                end: content_end,
+                path: paths.len() - 1,
+                page,
+            });
+            continue;
+        }
+
+        current = match current {
+            Some((kind, start, _)) => Some((kind, start, content_end)),
+            None => Some((line_kind(trimmed), content_start, content_end)),
+        };
+    }
+    let path = paths.len() - 1;
+    close(&mut current, &mut blocks, path, page);
+
+    (blocks, paths)
+}
+
+fn close(
+    current: &mut Option<(BlockKind, usize, usize)>,
+    blocks: &mut Vec<Block>,
+    path: usize,
+    page: Option<u32>,
+) {
+    if let Some((kind, start, end)) = current.take() {
+        blocks.push(Block { kind, start, end, path, page });
+    }
+}
+
+/// Level and text of an ATX heading line.
+fn heading(line: &str) -> Option<(usize, String)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &line[level..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let title = rest.trim().trim_end_matches('#').trim();
+    if title.is_empty() {
+        return None;
+    }
+    Some((level, title.to_string()))
+}
+
+fn fence_marker(line: &str) -> Option<(char, usize)> {
+    let marker = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = line.chars().take_while(|c| *c == marker).count();
+    (len >= 3).then_some((marker, len))
+}
+
+fn line_kind(line: &str) -> BlockKind {
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    let is_list = line.starts_with("- ")
+        || line.starts_with("* ")
+        || line.starts_with("+ ")
+        || (digits > 0 && (line[digits..].starts_with(". ") || line[digits..].starts_with(") ")));
+    if is_list || line.starts_with('|') || line.starts_with('>') {
+        BlockKind::Lines
+    } else {
+        BlockKind::Prose
+    }
+}
+
+/// Byte ranges of the units of a block: sentences of prose, lines of
+/// lists and tables. Code stays whole unless it alone is too big. Anything
+/// still over `max` is cut at whitespace.
+fn split_block(text: &str, block: &Block, max: usize) -> Vec<(usize, usize)> {
+    let (start, end) = (block.start, block.end);
+    let pieces = match block.kind {
+        BlockKind::Heading => vec![(start, end)],
+        BlockKind::Prose => sentences(text, start, end),
+        BlockKind::Lines => lines(text, start, end),
+        BlockKind::Code if count_tokens(&text[start..end]) > max => lines(text, start, end),
+        BlockKind::Code => vec![(start, end)],
+    };
+
+    let mut units = Vec::new();
+    for (start, end) in pieces {
+        if count_tokens(&text[start..end]) > max {
+            hard_split(text, start, end, max, &mut units);
+        } else {
+            units.push((start, end));
+        }
+    }
+    units
+}
+
+/// Sentence ranges: a sentence ends at `.`, `!` or `?` followed by
+/// whitespace, or at a line break.
+fn sentences(text: &str, start: usize, end: usize) -> Vec<(usize, usize)> {
+    let block = &text[start..end];
+    let mut ranges = Vec::new();
+    let mut sentence_start = 0;
+    let mut chars = block.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let next_is_space = chars.peek().is_some_and(|(_, n)| n.is_whitespace());
+        let ends = c == '\n' || (matches!(c, '.' | '!' | '?') && next_is_space);
+        if ends {
+            push_trimmed(block, sentence_start, i + c.len_utf8(), start, &mut ranges);
+            sentence_start = i + c.len_utf8();
+        }
+    }
+    push_trimmed(block, sentence_start, block.len(), start, &mut ranges);
+    ranges
+}
+
+fn lines(text: &str, start: usize, end: usize) -> Vec<(usize, usize)> {
+    let block = &text[start..end];
+    let mut ranges = Vec::new();
+    let mut line_start = 0;
+    for (i, _) in block.match_indices('\n') {
+        push_trimmed(block, line_start, i, start, &mut ranges);
+        line_start = i + 1;
+    }
+    push_trimmed(block, line_start, block.len(), start, &mut ranges);
+    ranges
+}
+
+/// Push `block[from..to]` without surrounding whitespace, offset by `base`.
+fn push_trimmed(block: &str, from: usize, to: usize, base: usize, ranges: &mut Vec<(usize, usize)>) {
+    let piece = &block[from..to];
+    let trimmed = piece.trim();
+    if trimmed.is_empty() {
+        return;
+    }
+    let lead = piece.len() - piece.trim_start().len();
+    let start = base + from + lead;
+    ranges.push((start, start + trimmed.len()));
+}
+
+/// Cut `text[start..end]` into pieces of at most `max` tokens, at the last
+/// whitespace that fits, or anywhere for long unbroken runs.
+fn hard_split(text: &str, mut start: usize, end: usize, max: usize, units: &mut Vec<(usize, usize)>) {
+    while start < end {
+        let rest = &text[start..end];
+        if count_tokens(rest) <= max {
+            units.push((start, end));
+            return;
+        }
+
+        let boundaries: Vec<usize> = rest.char_indices().map(|(i, _)| i).skip(1).collect();
+        // Largest prefix within the limit; a single character always fits
+        let fits = boundaries.partition_point(|&i| count_tokens(&rest[..i]) <= max);
+        let mut cut = boundaries.get(fits.saturating_sub(1)).copied().unwrap_or(rest.len());
+        if let Some(space) = rest[..cut].rfind(char::is_whitespace).filter(|&s| s > 0) {
+            cut = space;
+        }
+
+        push_trimmed(text, start, start + cut, 0, units);
+        start += cut;
+        start += text[start..end].len() - text[start..end].trim_start().len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(max_tokens: usize, overlap_tokens: usize) -> ChunkOptions {
+        ChunkOptions { max_tokens, overlap_tokens }
+    }
+
+    fn prose(sentences: usize) -> String {
+        (0..sentences)
+            .map(|i| format!("Sentence number {} talks about the topic at some length.", i))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn empty_input_has_no_chunks() {
+        assert!(chunk_text("", &ChunkOptions::default()).is_empty());
+        assert!(chunk_text(" \n\n\t\n", &ChunkOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn short_text_is_one_chunk() {
+        let chunks = chunk_text("Just a sentence.", &ChunkOptions::default());
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].text, "Just a sentence.");
+        assert!(chunks[0].heading_path.is_empty());
+    }
+
+    #[test]
+    fn chunks_stay_within_max_tokens() {
+        let text = format!("{}\n\n{}", prose(40), prose(25));
+        let chunks = chunk_text(&text, &options(50, 10));
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.token_count <= 50, "{} tokens: {}", chunk.token_count, chunk.text);
+            assert_eq!(&text[chunk.start..chunk.end], chunk.text);
+        }
+    }
+
+    #[test]
+    fn unbroken_text_is_cut_to_max_tokens() {
+        let text = "x".repeat(5000);
+        let chunks = chunk_text(&text, &options(20, 0));
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.token_count <= 20));
+        assert_eq!(chunks.iter().map(|c| c.text.len()).sum::<usize>(), text.len());
+    }
+
+    #[test]
+    fn chunks_overlap_by_at_most_overlap_tokens() {
+        let text = prose(40);
+        let chunks = chunk_text(&text, &options(60, 15));
+        assert!(chunks.len() > 2);
+        for pair in chunks.windows(2) {
+            let (previous, next) = (&pair[0], &pair[1]);
+            assert!(next.start < previous.end, "no overlap between chunks");
+            assert!(next.start > previous.start);
+            assert!(count_tokens(&text[next.start..previous.end]) <= 15);
+        }
+    }
+
+    #[test]
+    fn no_overlap_when_disabled() {
+        let text = prose(40);
+        let chunks = chunk_text(&text, &options(60, 0));
+        for pair in chunks.windows(2) {
+            assert!(pair[1].start >= pair[0].end);
+        }
+    }
+
+    #[test]
+    fn chunks_carry_their_headings() {
+        let text = format!("# Guide\n\n## Setup\n\n{}", prose(30));
+        let chunks = chunk_text(&text, &options(60, 20));
+        assert!(chunks.len() > 2);
+        assert_eq!(chunks[0].heading_path, ["Guide"]);
+        // Chunks continuing a section still know where they are
+        for chunk in &chunks[1..] {
+            assert_eq!(chunk.heading_path, ["Guide", "Setup"]);
+            assert!(!chunk.text.starts_with('#'));
+        }
+    }
+
+    #[test]
+    fn sections_start_new_chunks() {
+        let text = format!("# Guide\n\n{}\n\n## Setup\n\n{}\n\n# Reference\n\n{}", prose(3), prose(3), prose(3));
+        let chunks = chunk_text(&text, &options(60, 20));
+        let paths: Vec<_> = chunks.iter().map(|c| c.heading_path.clone()).collect();
+        assert_eq!(paths, [vec!["Guide"], vec!["Guide", "Setup"], vec!["Reference"]]);
+        // Overlap never reaches back into the previous section
+        assert!(chunks[1].text.starts_with("## Setup"));
+        assert!(chunks[2].text.starts_with("# Reference"));
+    }
+}
@@ -1,15 +1,529 @@
-use reqwest::header::USER_AGENT;
-use std::error::Error;
-
-/// Fetch HTML content from a URL
-pub async fn fetch_url(url: &str) -> Result<String, Box<dyn Error>> {
-    let client = reqwest::Client::new();
-    let res = client
-        .get(url)
-        .header(USER_AGENT, "Mozilla/5.0 (compatible; RE_ReSearch/1.0; +http://re-research.local)")
-        .send()
-        .await?;
-
-    let text = res.text().await?;
-    Ok(text)
+use super::{politeness, robots};
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use reqwest::header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, LOCATION};
+use reqwest::{redirect, Client, StatusCode, Url};
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const USER_AGENT_VALUE: &str = "Mozilla/5.0 (compatible; RE_ReSearch/1.0; +http://re-research.local)";
+const ACCEPT_VALUE: &str = "text/html,application/xhtml+xml,application/pdf;q=0.9,text/plain;q=0.8,application/json;q=0.8,*/*;q=0.5";
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Largest body read, 20 MiB; big enough for long PDFs.
+pub const DEFAULT_MAX_BYTES: usize = 20 * 1024 * 1024;
+const MAX_REDIRECTS: usize = 10;
+
+/// Limits for one fetch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FetchOptions {
+    /// For the whole request, body included.
+    pub timeout: Duration,
+    pub max_bytes: usize,
+    /// Skip the robots.txt check, for a URL the user asked for explicitly
+    /// and wherever it redirects. Per-host rate limits still apply.
+    pub ignore_robots: bool,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_TIMEOUT,
+            max_bytes: DEFAULT_MAX_BYTES,
+            ignore_robots: false,
+        }
+    }
+}
+
+/// A response body, by what it contains.
+#[derive(Debug, Clone)]
+pub enum Body {
+    Html(String),
+    Pdf(Vec<u8>),
+    /// Plain text, Markdown, CSV and other `text/*` types.
+    Text(String),
+    Json(serde_json::Value),
+}
+
+/// Cache validators of a response, sent back to ask whether it changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// A successful response.
+#[derive(Debug, Clone)]
+pub struct Fetched {
+    /// Where the content was found, after redirects.
+    pub final_url: Url,
+    /// MIME type without parameters, e.g. `text/html`; empty if the server
+    /// sent none.
+    pub content_type: String,
+    pub validators: Validators,
+    pub body: Body,
+}
+
+#[derive(Debug)]
+pub enum FetchError {
+    InvalidUrl(String),
+    /// The site's robots.txt disallows the URL.
+    Disallowed(Url),
+    /// The server answered with a non-2xx status.
+    Status { status: u16, url: Url },
+    /// A conditional request found the content unchanged.
+    NotModified,
+    /// More than `MAX_REDIRECTS` redirects, starting from the URL.
+    TooManyRedirects(Url),
+    /// The body is larger than `FetchOptions::max_bytes`.
+    TooLarge { limit: usize },
+    UnsupportedType(String),
+    Timeout,
+    Request(reqwest::Error),
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::InvalidUrl(url) => write!(f, "Invalid URL: {}", url),
+            FetchError::Disallowed(url) => write!(f, "{} is disallowed by the site's robots.txt", url),
+            FetchError::Status { status, url } => write!(f, "{} returned HTTP {}", url, status),
+            FetchError::NotModified => write!(f, "Not modified since the last fetch"),
+            FetchError::TooManyRedirects(url) => write!(f, "{} redirected more than {} times", url, MAX_REDIRECTS),
+            FetchError::TooLarge { limit } if *limit >= 1024 * 1024 => {
+                write!(f, "Response is larger than {} MB", limit / (1024 * 1024))
+            }
+            FetchError::TooLarge { limit } => write!(f, "Response is larger than {} bytes", limit),
+            FetchError::UnsupportedType(content_type) => write!(f, "Unsupported content type: {}", content_type),
+            FetchError::Timeout => write!(f, "Request timed out"),
+            FetchError::Request(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FetchError::Request(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            FetchError::Timeout
+        } else {
+            FetchError::Request(e)
+        }
+    }
+}
+
+/// Client for robots.txt, which follows redirects itself.
+pub(crate) fn client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        Client::builder()
+            .user_agent(USER_AGENT_VALUE)
+            .connect_timeout(CONNECT_TIMEOUT)
+            .redirect(redirect::Policy::limited(MAX_REDIRECTS))
+            .build()
+            .expect("HTTP client configuration is valid")
+    })
+}
+
+/// Client for pages. Redirects are followed by `fetch_if_modified`, so
+/// every hop is checked against robots.txt and rate limited.
+fn page_client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        Client::builder()
+            .user_agent(USER_AGENT_VALUE)
+            .connect_timeout(CONNECT_TIMEOUT)
+            .redirect(redirect::Policy::none())
+            .build()
+            .expect("HTTP client configuration is valid")
+    })
+}
+
+/// Fetch a URL with the default limits.
+pub async fn fetch_url(url: &str) -> Result<Fetched, FetchError> {
+    fetch(url, &FetchOptions::default()).await
+}
+
+/// Fetch a URL, following redirects, and decode the body by its content
+/// type. Non-2xx responses, bodies over `options.max_bytes` and content
+/// that can't be ingested are errors. The site's robots.txt is obeyed and
+/// requests to one host are spaced out; see `politeness`.
+pub async fn fetch(url: &str, options: &FetchOptions) -> Result<Fetched, FetchError> {
+    fetch_if_modified(url, options, &Validators::default()).await
+}
+
+/// Fetch a URL as `fetch` does, unless it is unchanged since the response
+/// `validators` came from; that is the `NotModified` error.
+pub async fn fetch_if_modified(url: &str, options: &FetchOptions, validators: &Validators) -> Result<Fetched, FetchError> {
+    let parsed = Url::parse(url).map_err(|_| FetchError::InvalidUrl(url.to_string()))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(FetchError::InvalidUrl(url.to_string()));
+    }
+
+    let mut url = parsed.clone();
+    let mut redirects = 0;
+    // The slot is held until the body is read
+    let (mut res, _slot) = loop {
+        let crawl_delay = if options.ignore_robots {
+            None
+        } else {
+            check_robots(&url).await?
+        };
+        let slot = politeness::acquire(&url, crawl_delay).await;
+
+        let mut request = page_client()
+            .get(url.clone())
+            .header(ACCEPT, ACCEPT_VALUE)
+            .timeout(options.timeout);
+        if let Some(etag) = &validators.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        let res = request.send().await?;
+
+        let Some(next) = redirect_target(&res) else {
+            break (res, slot);
+        };
+        if redirects == MAX_REDIRECTS {
+            return Err(FetchError::TooManyRedirects(parsed));
+        }
+        redirects += 1;
+        url = next;
+    };
+
+    let final_url = res.url().clone();
+    if res.status() == StatusCode::NOT_MODIFIED {
+        return Err(FetchError::NotModified);
+    }
+    if !res.status().is_success() {
+        return Err(FetchError::Status { status: res.status().as_u16(), url: final_url });
+    }
+
+    let header = |name| res.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let (content_type, charset) = header(CONTENT_TYPE).as_deref().map(parse_content_type).unwrap_or_default();
+    let declared_len = header(CONTENT_LENGTH).and_then(|l| l.parse::<usize>().ok());
+    let validators = Validators {
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+    };
+    if declared_len.is_some_and(|len| len > options.max_bytes) {
+        return Err(FetchError::TooLarge { limit: options.max_bytes });
+    }
+
+    // Content-Length can be absent or wrong, so count as we read
+    let mut bytes = Vec::with_capacity(declared_len.unwrap_or(0));
+    while let Some(chunk) = res.chunk().await? {
+        if bytes.len() + chunk.len() > options.max_bytes {
+            return Err(FetchError::TooLarge { limit: options.max_bytes });
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    let body = match content_type.as_str() {
+        "text/html" | "application/xhtml+xml" => Body::Html(decode(&bytes, charset.as_deref(), true)),
+        "application/pdf" => Body::Pdf(bytes),
+        t if t == "application/json" || t.ends_with("+json") => parse_json(decode(&bytes, charset.as_deref(), false)),
+        t if t.starts_with("text/") => Body::Text(decode(&bytes, charset.as_deref(), false)),
+        // Servers often label downloads generically, so look at the bytes
+        "" | "application/octet-stream" | "binary/octet-stream" => sniff(bytes, &content_type)?,
+        other => return Err(FetchError::UnsupportedType(other.to_string())),
+    };
+
+    Ok(Fetched { final_url, content_type, validators, body })
+}
+
+/// The crawl delay of the URL's site, or `Disallowed` if its robots.txt
+/// excludes the URL.
+async fn check_robots(url: &Url) -> Result<Option<Duration>, FetchError> {
+    let robots = robots::for_url(url).await;
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    if !robots.is_allowed(&path) {
+        return Err(FetchError::Disallowed(url.clone()));
+    }
+    Ok(robots.crawl_delay)
+}
+
+/// Where a redirect response points, if it is one with a usable
+/// `Location`. Anything else, like a 304, is handled as a final response.
+fn redirect_target(res: &reqwest::Response) -> Option<Url> {
+    if !matches!(res.status().as_u16(), 301 | 302 | 303 | 307 | 308) {
+        return None;
+    }
+    let location = res.headers().get(LOCATION)?.to_str().ok()?;
+    let next = res.url().join(location).ok()?;
+    matches!(next.scheme(), "http" | "https").then_some(next)
+}
+
+/// MIME type (lowercased, without parameters) and charset of a
+/// Content-Type header.
+fn parse_content_type(value: &str) -> (String, Option<String>) {
+    let mut parts = value.split(';');
+    let essence = parts.next().unwrap_or("").trim().to_lowercase();
+    let charset = parts.find_map(|p| {
+        let (name, value) = p.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches(['"', '\'']).to_string())
+    });
+    (essence, charset)
+}
+
+fn sniff(bytes: Vec<u8>, content_type: &str) -> Result<Body, FetchError> {
+    if bytes.starts_with(b"%PDF-") {
+        return Ok(Body::Pdf(bytes));
+    }
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]).to_lowercase();
+    if head.contains("<html") || head.contains("<!doctype html") {
+        return Ok(Body::Html(decode(&bytes, None, true)));
+    }
+    if std::str::from_utf8(&bytes).is_ok() {
+        return Ok(Body::Text(decode(&bytes, None, false)));
+    }
+    let content_type = if content_type.is_empty() { "unknown" } else { content_type };
+    Err(FetchError::UnsupportedType(content_type.to_string()))
+}
+
+fn parse_json(text: String) -> Body {
+    // Mislabelled JSON is still readable text
+    serde_json::from_str(&text).map(Body::Json).unwrap_or(Body::Text(text))
+}
+
+/// Decode a body. A byte order mark wins, then the header charset, then
+/// for HTML a `<meta>` charset; otherwise UTF-8, or Windows-1252 when the
+/// bytes aren't valid UTF-8.
+fn decode(bytes: &[u8], header_charset: Option<&str>, is_html: bool) -> String {
+    let declared = header_charset
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+        .or_else(|| if is_html { meta_charset(bytes) } else { None });
+
+    let encoding = match declared {
+        Some(encoding) => encoding,
+        None if std::str::from_utf8(bytes).is_ok() => UTF_8,
+        None => WINDOWS_1252,
+    };
+    // `decode` sniffs the BOM itself and drops it
+    encoding.decode(bytes).0.into_owned()
+}
+
+/// Charset from `<meta charset>` or `<meta http-equiv="Content-Type">` in
+/// the start of a page, where the HTML spec requires it to be.
+fn meta_charset(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(4096)]).to_lowercase();
+    let mut rest = head.as_str();
+    while let Some(start) = rest.find("<meta") {
+        rest = &rest[start + 5..];
+        let tag = &rest[..rest.find('>').unwrap_or(rest.len())];
+        let value = tag
+            .find("charset")
+            .and_then(|at| tag[at + "charset".len()..].trim_start().strip_prefix('='));
+        let Some(value) = value else {
+            continue;
+        };
+        let value = value.trim_start().trim_start_matches(['"', '\'']);
+        let end = value
+            .find(|c: char| c == '"' || c == '\'' || c == ';' || c == '/' || c.is_whitespace())
+            .unwrap_or(value.len());
+        // A page that claims UTF-16 in ASCII markup isn't UTF-16
+        return Encoding::for_label(&value.as_bytes()[..end]).map(|e| e.output_encoding());
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// A raw HTTP/1.1 response that closes the connection.
+    fn response(status: &str, headers: &[(&str, &str)], body: &[u8]) -> Vec<u8> {
+        let mut head = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n", status, body.len());
+        for (name, value) in headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        [head.into_bytes(), body.to_vec()].concat()
+    }
+
+    /// Answer requests on a local port with `handler`, which gets the
+    /// request head. Each server is its own host, so tests don't wait on
+    /// each other's rate limits.
+    async fn serve(handler: impl Fn(&str) -> Vec<u8> + Send + 'static) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let _ = socket.write_all(&handler(&String::from_utf8_lossy(&request))).await;
+            }
+        });
+        base
+    }
+
+    fn path(request: &str) -> &str {
+        request.split_whitespace().nth(1).unwrap_or("")
+    }
+
+    fn no_robots() -> FetchOptions {
+        FetchOptions { ignore_robots: true, ..FetchOptions::default() }
+    }
+
+    fn html(body: Body) -> String {
+        match body {
+            Body::Html(html) => html,
+            other => panic!("expected HTML, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn content_types_are_split_into_type_and_charset() {
+        assert_eq!(parse_content_type("text/html"), ("text/html".to_string(), None));
+        assert_eq!(
+            parse_content_type("Text/HTML; Charset=\"ISO-8859-1\""),
+            ("text/html".to_string(), Some("ISO-8859-1".to_string()))
+        );
+        assert_eq!(
+            parse_content_type("application/json; boundary=x; charset=utf-8"),
+            ("application/json".to_string(), Some("utf-8".to_string()))
+        );
+    }
+
+    #[test]
+    fn bodies_are_decoded_by_bom_header_meta_then_guess() {
+        assert_eq!(decode(b"caf\xe9", Some("iso-8859-1"), false), "café");
+        assert_eq!(decode(b"\xef\xbb\xbfcaf\xc3\xa9", Some("iso-8859-1"), false), "café");
+        assert_eq!(decode("café".as_bytes(), None, false), "café");
+        assert_eq!(decode(b"caf\xe9", None, false), "café");
+
+        let page = b"<html><head><meta charset=\"windows-1251\"></head><body>\xcf\xf0\xe8\xe2\xe5\xf2</body></html>";
+        assert!(decode(page, None, true).contains("Привет"));
+        let http_equiv = b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=koi8-r\"><p>\xf0\xd2\xc9\xd7\xc5\xd4</p>";
+        assert!(decode(http_equiv, None, true).contains("Привет"));
+        // Meta tags only count for HTML, and a claimed UTF-16 is ignored
+        assert!(!decode(page, None, false).contains("Привет"));
+        assert_eq!(meta_charset(b"<meta charset=utf-16>"), Some(UTF_8));
+    }
+
+    #[test]
+    fn unlabelled_bodies_are_sniffed() {
+        assert!(matches!(sniff(b"%PDF-1.7 ...".to_vec(), "").unwrap(), Body::Pdf(_)));
+        assert!(matches!(sniff(b"<!DOCTYPE html><p>hi</p>".to_vec(), "").unwrap(), Body::Html(_)));
+        assert!(matches!(sniff(b"just words".to_vec(), "application/octet-stream").unwrap(), Body::Text(_)));
+        assert!(matches!(
+            sniff(vec![0xff, 0xfe, 0x00, 0x81], "application/octet-stream"),
+            Err(FetchError::UnsupportedType(t)) if t == "application/octet-stream"
+        ));
+        assert!(matches!(parse_json("[1, 2]".to_string()), Body::Json(_)));
+        assert!(matches!(parse_json("not json".to_string()), Body::Text(_)));
+    }
+
+    #[tokio::test]
+    async fn redirects_are_followed_and_the_final_page_decoded() {
+        let page = serve(|request| match path(request) {
+            "/page" => response(
+                "200 OK",
+                &[("Content-Type", "text/html; charset=iso-8859-1"), ("ETag", "\"v1\"")],
+                b"<html><body>caf\xe9</body></html>",
+            ),
+            _ => response("404 Not Found", &[], b""),
+        })
+        .await;
+        let target = page.join("/page").unwrap().to_string();
+        let start = serve(move |_| response("301 Moved Permanently", &[("Location", &target)], b"")).await;
+
+        let fetched = fetch(start.join("/old").unwrap().as_str(), &no_robots()).await.unwrap();
+        assert_eq!(fetched.final_url, page.join("/page").unwrap());
+        assert_eq!(fetched.content_type, "text/html");
+        assert_eq!(fetched.validators.etag.as_deref(), Some("\"v1\""));
+        assert!(html(fetched.body).contains("café"));
+    }
+
+    #[tokio::test]
+    async fn redirect_chains_stop_after_the_limit() {
+        let mut url = serve(|_| response("200 OK", &[("Content-Type", "text/plain")], b"end")).await;
+        for _ in 0..MAX_REDIRECTS {
+            let target = url.to_string();
+            url = serve(move |_| response("302 Found", &[("Location", &target)], b"")).await;
+        }
+        let fetched = fetch(url.as_str(), &no_robots()).await.unwrap();
+        assert!(matches!(fetched.body, Body::Text(text) if text == "end"));
+
+        let target = url.to_string();
+        let too_far = serve(move |_| response("302 Found", &[("Location", &target)], b"")).await;
+        assert!(matches!(
+            fetch(too_far.as_str(), &no_robots()).await,
+            Err(FetchError::TooManyRedirects(start)) if start == too_far
+        ));
+    }
+
+    #[tokio::test]
+    async fn failures_are_reported_by_kind() {
+        let base = serve(|request| match path(request) {
+            "/missing" => response("404 Not Found", &[("Content-Type", "text/html")], b"gone"),
+            "/cached" if request.to_lowercase().contains("if-none-match: \"v1\"") => response("304 Not Modified", &[], b""),
+            "/cached" => response("200 OK", &[("Content-Type", "text/plain")], b"fresh"),
+            "/big" => response("200 OK", &[("Content-Type", "text/plain")], &[b'a'; 64]),
+            "/image" => response("200 OK", &[("Content-Type", "image/png")], b"\x89PNG"),
+            _ => response("500 Internal Server Error", &[], b""),
+        })
+        .await;
+        let url = |path: &str| base.join(path).unwrap().to_string();
+
+        assert!(matches!(
+            fetch(&url("/missing"), &no_robots()).await,
+            Err(FetchError::Status { status: 404, .. })
+        ));
+        assert!(matches!(fetch("ftp://example.com/file", &no_robots()).await, Err(FetchError::InvalidUrl(_))));
+
+        let cached = Validators { etag: Some("\"v1\"".to_string()), last_modified: None };
+        assert!(matches!(
+            fetch_if_modified(&url("/cached"), &no_robots(), &cached).await,
+            Err(FetchError::NotModified)
+        ));
+        assert!(matches!(fetch(&url("/cached"), &no_robots()).await.unwrap().body, Body::Text(t) if t == "fresh"));
+
+        let small = FetchOptions { max_bytes: 16, ..no_robots() };
+        assert!(matches!(fetch(&url("/big"), &small).await, Err(FetchError::TooLarge { limit: 16 })));
+        assert!(matches!(
+            fetch(&url("/image"), &no_robots()).await,
+            Err(FetchError::UnsupportedType(t)) if t == "image/png"
+        ));
+    }
+
+    #[tokio::test]
+    async fn robots_txt_is_obeyed_unless_ignored() {
+        let base = serve(|request| match path(request) {
+            "/robots.txt" => response("200 OK", &[("Content-Type", "text/plain")], b"User-agent: *\nDisallow: /private"),
+            _ => response("200 OK", &[("Content-Type", "text/plain")], b"secret"),
+        })
+        .await;
+        let private = base.join("/private/page").unwrap();
+
+        assert!(matches!(
+            fetch(private.as_str(), &FetchOptions::default()).await,
+            Err(FetchError::Disallowed(url)) if url == private
+        ));
+        assert!(fetch(private.as_str(), &no_robots()).await.is_ok());
+    }
 }
@@ -0,0 +1,348 @@
+use super::{markdown::fence_for, parser, pdf};
+use reqwest::Url;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// How a local file is turned into Markdown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileKind {
+    Markdown,
+    Text,
+    Html,
+    Pdf,
+    /// Source code, with the language used to tag its fence.
+    Code(&'static str),
+}
+
+impl FileKind {
+    /// The kind of file `path` is, by extension. None if it can't be imported.
+    pub fn of(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        let kind = match extension.as_str() {
+            "md" | "markdown" | "mdx" => Self::Markdown,
+            "txt" | "text" | "rst" | "org" => Self::Text,
+            "html" | "htm" | "xhtml" => Self::Html,
+            "pdf" => Self::Pdf,
+            other => Self::Code(code_language(other)?),
+        };
+        Some(kind)
+    }
+
+    /// Name stored in node metadata.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Markdown => "markdown",
+            Self::Text => "text",
+            Self::Html => "html",
+            Self::Pdf => "pdf",
+            Self::Code(_) => "code",
+        }
+    }
+}
+
+fn code_language(extension: &str) -> Option<&'static str> {
+    let language = match extension {
+        "rs" => "rust",
+        "py" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "jsx",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "tsx",
+        "go" => "go",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "scala" => "scala",
+        "swift" => "swift",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "cpp",
+        "cs" => "csharp",
+        "rb" => "ruby",
+        "php" => "php",
+        "lua" => "lua",
+        "r" => "r",
+        "hs" => "haskell",
+        "ex" | "exs" => "elixir",
+        "zig" => "zig",
+        "sh" | "bash" | "zsh" => "bash",
+        "sql" => "sql",
+        "css" | "scss" => "css",
+        "vue" => "vue",
+        "svelte" => "svelte",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// A local file converted to Markdown.
+#[derive(Debug, Clone)]
+pub struct FileDocument {
+    pub kind: FileKind,
+    pub title: String,
+    pub markdown: String,
+    /// YAML front matter of a Markdown file, as JSON.
+    pub front_matter: Option<Value>,
+    /// Number of pages, for PDFs.
+    pub page_count: Option<usize>,
+}
+
+/// Convert the contents of the file at `path` to Markdown. The title is
+/// taken from front matter, the document itself, or else the file name.
+///
+/// PDF parsing can panic on malformed files, so call this where a panic
+/// is contained, e.g. on a blocking task.
+pub fn read_document(path: &Path, bytes: &[u8]) -> Result<FileDocument, String> {
+    let kind = FileKind::of(path).ok_or_else(|| format!("Unsupported file type: {}", path.display()))?;
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    convert(kind, &file_name, bytes, None)
+}
+
+/// Convert `bytes` of the given kind to Markdown, as `read_document`.
+/// `file_name` is the fallback title; `base` resolves relative links in
+/// HTML.
+pub fn convert(kind: FileKind, file_name: &str, bytes: &[u8], base: Option<&Url>) -> Result<FileDocument, String> {
+    let stem = Path::new(file_name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| file_name.to_string());
+
+    let mut document = FileDocument {
+        kind,
+        title: String::new(),
+        markdown: String::new(),
+        front_matter: None,
+        page_count: None,
+    };
+
+    match kind {
+        FileKind::Pdf => {
+            let extracted = pdf::extract(bytes)?;
+            document.title = extracted.title.unwrap_or(stem);
+            document.markdown = pdf::to_markdown(&document.title, &extracted.pages);
+            document.page_count = Some(extracted.pages.len());
+            return Ok(document);
+        }
+        FileKind::Markdown => {
+            let text = decode(bytes);
+            let (front_matter, body) = split_front_matter(&text);
+            document.title = front_matter
+                .as_ref()
+                .and_then(|m| m.get("title")?.as_str())
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .or_else(|| first_heading(body))
+                .unwrap_or(&stem)
+                .to_string();
+            document.markdown = body.trim().to_string();
+            document.front_matter = front_matter;
+        }
+        FileKind::Text => {
+            document.title = stem;
+            document.markdown = decode(bytes).trim().to_string();
+        }
+        FileKind::Html => {
+            let article = parser::extract_article(&decode(bytes), base);
+            document.title = if article.title.is_empty() { stem } else { article.title };
+            document.markdown = article.markdown;
+        }
+        FileKind::Code(language) => {
+            let code = decode(bytes);
+            let code = code.trim_end();
+            let fence = fence_for(code);
+            document.markdown = format!("# {}\n\n{}{}\n{}\n{}", file_name, fence, language, code, fence);
+            document.title = file_name.to_string();
+        }
+    }
+
+    if document.markdown.trim().is_empty() {
+        return Err(format!("No readable content in {}", file_name));
+    }
+    Ok(document)
+}
+
+/// Text of a file, without a byte order mark. Invalid UTF-8 is replaced
+/// rather than rejected.
+fn decode(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Split `---` delimited YAML front matter from the rest of a Markdown
+/// file. Front matter that isn't a valid YAML mapping is left in the body.
+fn split_front_matter(text: &str) -> (Option<Value>, &str) {
+    let Some(rest) = text.strip_prefix("---\n").or_else(|| text.strip_prefix("---\r\n")) else {
+        return (None, text);
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        let end = offset + line.len();
+        if matches!(line.trim_end(), "---" | "...") {
+            let yaml = &rest[..offset];
+            return match serde_yaml::from_str::<Value>(yaml) {
+                Ok(value @ Value::Object(_)) => (Some(value), &rest[end..]),
+                Ok(Value::Null) => (None, &rest[end..]),
+                _ => (None, text),
+            };
+        }
+        offset = end;
+    }
+    (None, text)
+}
+
+/// Text of the first level-one ATX heading.
+pub(crate) fn first_heading(markdown: &str) -> Option<&str> {
+    markdown
+        .lines()
+        .find_map(|line| line.strip_prefix("# "))
+        .map(|title| title.trim().trim_end_matches('#').trim())
+        .filter(|title| !title.is_empty())
+}
+
+/// Importable files under `root`, in path order. Only files matching one
+/// of `include` (every supported file if empty) and none of `exclude` are
+/// returned; globs are matched against paths relative to `root`. Hidden
+/// files and folders are skipped.
+pub fn find_files(root: &Path, include: &[String], exclude: &[String]) -> Result<Vec<PathBuf>, String> {
+    if !root.is_dir() {
+        return Err(format!("Not a folder: {}", root.display()));
+    }
+    let include = glob_set(include)?;
+    let exclude = glob_set(exclude)?;
+
+    let walker = WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'));
+
+    let mut files = Vec::new();
+    // Unreadable folders are skipped rather than failing the whole import
+    for entry in walker.filter_map(Result::ok) {
+        if !entry.file_type().is_file() || FileKind::of(entry.path()).is_none() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+        let included = include.as_ref().is_none_or(|set| set.is_match(relative));
+        let excluded = exclude.as_ref().is_some_and(|set| set.is_match(relative));
+        if included && !excluded {
+            files.push(entry.into_path());
+        }
+    }
+    Ok(files)
+}
+
+pub(crate) fn glob_set(patterns: &[String]) -> Result<Option<GlobSet>, String> {
+    let patterns: Vec<&String> = patterns.iter().filter(|p| !p.trim().is_empty()).collect();
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern.trim()).map_err(|e| format!("Invalid pattern {}: {}", pattern, e))?;
+        builder.add(glob);
+    }
+    builder.build().map(Some).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(name: &str, text: &str) -> FileDocument {
+        read_document(Path::new(name), text.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn kinds_follow_the_extension() {
+        assert_eq!(FileKind::of(Path::new("notes/README.MD")), Some(FileKind::Markdown));
+        assert_eq!(FileKind::of(Path::new("paper.pdf")), Some(FileKind::Pdf));
+        assert_eq!(FileKind::of(Path::new("page.htm")), Some(FileKind::Html));
+        assert_eq!(FileKind::of(Path::new("main.rs")), Some(FileKind::Code("rust")));
+        assert_eq!(FileKind::of(Path::new("photo.png")), None);
+        assert_eq!(FileKind::of(Path::new("Makefile")), None);
+        assert!(read_document(Path::new("photo.png"), b"").unwrap_err().starts_with("Unsupported file type"));
+    }
+
+    #[test]
+    fn markdown_titles_come_from_front_matter_then_heading_then_name() {
+        let document = read("notes.md", "\u{feff}---\ntitle: Ownership\ntags: [rust]\n---\n# Heading\n\nBody\n");
+        assert_eq!(document.title, "Ownership");
+        assert_eq!(document.markdown, "# Heading\n\nBody");
+        assert_eq!(document.front_matter.unwrap()["tags"][0], "rust");
+
+        let document = read("notes.md", "---\ndate: 2024-01-01\n---\n\n# Borrowing #\n\nBody");
+        assert_eq!(document.title, "Borrowing");
+
+        let document = read("notes.md", "Just text");
+        assert_eq!(document.title, "notes");
+        assert_eq!(document.front_matter, None);
+    }
+
+    #[test]
+    fn front_matter_that_is_not_a_mapping_stays_in_the_body() {
+        let document = read("list.md", "---\n- a\n- b\n---\ntext");
+        assert_eq!(document.front_matter, None);
+        assert!(document.markdown.starts_with("---\n- a"));
+
+        let unclosed = read("rule.md", "---\nno closing line");
+        assert!(unclosed.markdown.starts_with("---"));
+    }
+
+    #[test]
+    fn html_text_and_code_are_converted() {
+        let page = read(
+            "page.html",
+            "<html><head><title>Saved page</title></head><body><article><p>Some saved text worth keeping.</p></article></body></html>",
+        );
+        assert_eq!(page.title, "Saved page");
+        assert!(page.markdown.contains("Some saved text worth keeping."));
+
+        let text = read("todo.txt", "  buy milk \n");
+        assert_eq!((text.title.as_str(), text.markdown.as_str()), ("todo", "buy milk"));
+
+        let code = read("lib.rs", "fn main() {}\n\n");
+        assert_eq!(code.title, "lib.rs");
+        assert_eq!(code.markdown, "# lib.rs\n\n```rust\nfn main() {}\n```");
+        let fenced = read("doc.py", "s = '''\n```\n'''");
+        assert!(fenced.markdown.contains("````python\n"));
+
+        assert_eq!(
+            read_document(Path::new("empty.txt"), b" \n").unwrap_err(),
+            "No readable content in empty.txt"
+        );
+    }
+
+    #[test]
+    fn folders_are_walked_with_globs_skipping_hidden_and_unsupported_files() {
+        let root = std::env::temp_dir().join(format!("research-files-{}", uuid::Uuid::new_v4()));
+        for file in ["a.md", "b.png", "docs/c.txt", "docs/draft/d.md", ".git/e.md", "docs/.f.md"] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "text").unwrap();
+        }
+        let relative = |include: &[&str], exclude: &[&str]| -> Vec<String> {
+            let include: Vec<String> = include.iter().map(|s| s.to_string()).collect();
+            let exclude: Vec<String> = exclude.iter().map(|s| s.to_string()).collect();
+            find_files(&root, &include, &exclude)
+                .unwrap()
+                .iter()
+                .map(|p| p.strip_prefix(&root).unwrap().to_string_lossy().replace('\\', "/"))
+                .collect()
+        };
+
+        assert_eq!(relative(&[], &[]), ["a.md", "docs/c.txt", "docs/draft/d.md"]);
+        assert_eq!(relative(&["**/*.md"], &[]), ["a.md", "docs/draft/d.md"]);
+        assert_eq!(relative(&["docs/**", " "], &["**/draft/**"]), ["docs/c.txt"]);
+        assert!(find_files(&root, &["[".to_string()], &[]).unwrap_err().starts_with("Invalid pattern"));
+        assert!(find_files(&root.join("a.md"), &[], &[]).unwrap_err().starts_with("Not a folder"));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
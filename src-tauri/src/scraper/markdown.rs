@@ -0,0 +1,498 @@
+use reqwest::Url;
+use scraper::{ElementRef, Html, Node};
+
+/// Elements laid out as blocks of their own.
+const BLOCK_TAGS: &[&str] = &[
+    "html", "body", "p", "div", "section", "article", "main", "header", "footer", "nav", "aside",
+    "h1", "h2", "h3", "h4", "h5", "h6", "ul", "ol", "li", "dl", "dt", "dd", "pre", "blockquote",
+    "table", "hr", "figure", "figcaption", "address", "details", "summary", "form", "fieldset",
+];
+
+/// Never rendered, whatever the caller skips.
+const HIDDEN_TAGS: &[&str] = &["script", "style", "noscript", "template", "head", "title", "meta", "link"];
+
+/// Converts HTML elements to Markdown, keeping headings, lists, links,
+/// emphasis, code blocks, quotes and tables.
+pub struct Converter<'a> {
+    base: Option<&'a Url>,
+    skip: Option<&'a dyn Fn(ElementRef) -> bool>,
+}
+
+/// Convert an HTML document or fragment to Markdown. Relative links and
+/// images are resolved against `base`.
+pub fn html_to_markdown(html: &str, base: Option<&Url>) -> String {
+    let document = Html::parse_document(html);
+    Converter::new(base).convert([document.root_element()])
+}
+
+impl<'a> Converter<'a> {
+    pub fn new(base: Option<&'a Url>) -> Self {
+        Self { base, skip: None }
+    }
+
+    /// Leave out every element for which `skip` returns true, with its children.
+    pub fn skipping(mut self, skip: &'a dyn Fn(ElementRef) -> bool) -> Self {
+        self.skip = Some(skip);
+        self
+    }
+
+    /// Markdown for `elements`, in order, blocks separated by blank lines.
+    pub fn convert<'e>(&self, elements: impl IntoIterator<Item = ElementRef<'e>>) -> String {
+        let mut blocks = Vec::new();
+        for element in elements {
+            if !self.is_skipped(element) {
+                self.block(element, &mut blocks);
+            }
+        }
+        blocks.join("\n\n")
+    }
+
+    fn is_skipped(&self, element: ElementRef) -> bool {
+        HIDDEN_TAGS.contains(&element.value().name()) || self.skip.is_some_and(|skip| skip(element))
+    }
+
+    /// Render the children of `element` as blocks.
+    fn block(&self, element: ElementRef, blocks: &mut Vec<String>) {
+        let mut inline = String::new();
+        self.children(element, blocks, &mut inline);
+        flush(&mut inline, blocks);
+    }
+
+    fn children(&self, element: ElementRef, blocks: &mut Vec<String>, inline: &mut String) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => push_text(inline, &escape(text)),
+                Node::Element(_) => {
+                    let Some(child) = ElementRef::wrap(child) else { continue };
+                    if self.is_skipped(child) {
+                        continue;
+                    }
+                    if BLOCK_TAGS.contains(&child.value().name()) {
+                        flush(inline, blocks);
+                        self.block_element(child, blocks);
+                    } else if !is_formatting(child) && has_block_descendant(child) {
+                        // Wrappers like <span> around whole sections
+                        self.children(child, blocks, inline);
+                    } else {
+                        self.inline_element(child, inline);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn block_element(&self, element: ElementRef, blocks: &mut Vec<String>) {
+        let tag = element.value().name();
+        match tag {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = tag[1..].parse::<usize>().unwrap_or(1);
+                let text = self.inline_text(element);
+                if !text.is_empty() {
+                    blocks.push(format!("{} {}", "#".repeat(level), text));
+                }
+            }
+            "pre" => {
+                let code = element.text().collect::<String>();
+                let code = code.trim_matches('\n').trim_end();
+                if !code.is_empty() {
+                    let fence = fence_for(code);
+                    blocks.push(format!("{}{}\n{}\n{}", fence, code_language(element), code, fence));
+                }
+            }
+            "blockquote" => {
+                let mut inner = Vec::new();
+                self.block(element, &mut inner);
+                if !inner.is_empty() {
+                    blocks.push(prefix_lines(&inner.join("\n\n"), "> ", ">"));
+                }
+            }
+            "ul" | "ol" => {
+                let list = self.list(element, tag == "ol");
+                if !list.is_empty() {
+                    blocks.push(list);
+                }
+            }
+            "table" => self.table(element, blocks),
+            "hr" => blocks.push("---".to_string()),
+            "dt" => {
+                let text = self.inline_text(element);
+                if !text.is_empty() {
+                    blocks.push(format!("**{}**", text));
+                }
+            }
+            _ => self.block(element, blocks),
+        }
+    }
+
+    /// A tight list; nested content is indented under its item.
+    fn list(&self, element: ElementRef, ordered: bool) -> String {
+        let mut number: usize = element
+            .value()
+            .attr("start")
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(1);
+
+        let mut items = Vec::new();
+        for child in element.children().filter_map(ElementRef::wrap) {
+            if self.is_skipped(child) {
+                continue;
+            }
+            let mut content = Vec::new();
+            if child.value().name() == "li" {
+                self.block(child, &mut content);
+            } else {
+                self.block_element(child, &mut content);
+            }
+            if content.is_empty() {
+                continue;
+            }
+
+            let marker = if ordered {
+                let marker = format!("{}. ", number);
+                number += 1;
+                marker
+            } else {
+                "- ".to_string()
+            };
+            let body = content.join("\n");
+            let indent = " ".repeat(marker.len());
+            items.push(format!("{}{}", marker, prefix_continuation(&body, &indent)));
+        }
+        items.join("\n")
+    }
+
+    /// A GFM table, the first row as header. Single-column tables are
+    /// layout, not data, so their cells are rendered as blocks.
+    fn table(&self, element: ElementRef, blocks: &mut Vec<String>) {
+        let rows: Vec<ElementRef> = element
+            .descendants()
+            .filter_map(ElementRef::wrap)
+            .filter(|e| e.value().name() == "tr")
+            .collect();
+        let cells: Vec<Vec<ElementRef>> = rows
+            .iter()
+            .map(|row| {
+                row.children()
+                    .filter_map(ElementRef::wrap)
+                    .filter(|c| matches!(c.value().name(), "th" | "td"))
+                    .collect()
+            })
+            .collect();
+        let columns = cells.iter().map(Vec::len).max().unwrap_or(0);
+
+        if columns <= 1 {
+            for cell in cells.into_iter().flatten() {
+                self.block(cell, blocks);
+            }
+            return;
+        }
+
+        let mut lines = Vec::new();
+        for (i, row) in cells.iter().enumerate() {
+            let mut texts: Vec<String> = row.iter().map(|c| self.inline_text(*c).replace('|', "\\|")).collect();
+            texts.resize(columns, String::new());
+            lines.push(format!("| {} |", texts.join(" | ")));
+            if i == 0 {
+                lines.push(format!("|{}", " --- |".repeat(columns)));
+            }
+        }
+        blocks.push(lines.join("\n"));
+    }
+
+    /// Inline Markdown of `element` on one line, for headings and cells.
+    fn inline_text(&self, element: ElementRef) -> String {
+        let mut out = String::new();
+        self.inline_children(element, &mut out);
+        out.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    fn inline_children(&self, element: ElementRef, out: &mut String) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => push_text(out, &escape(text)),
+                Node::Element(_) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        if !self.is_skipped(child) {
+                            self.inline_element(child, out);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn inline_element(&self, element: ElementRef, out: &mut String) {
+        let value = element.value();
+        match value.name() {
+            "br" => out.push('\n'),
+            "strong" | "b" => self.wrapped(element, "**", out),
+            "em" | "i" | "cite" => self.wrapped(element, "*", out),
+            "del" | "s" | "strike" => self.wrapped(element, "~~", out),
+            "code" | "kbd" | "samp" | "tt" => {
+                let code = element.text().collect::<String>();
+                let code = code.split_whitespace().collect::<Vec<_>>().join(" ");
+                if !code.is_empty() {
+                    push_text(out, &inline_code(&code));
+                }
+            }
+            "img" => {
+                let alt = value.attr("alt").unwrap_or("").trim();
+                if let Some(src) = value.attr("src").and_then(|s| self.resolve(s)) {
+                    push_text(out, &format!("![{}]({})", escape(alt), src));
+                }
+            }
+            "a" => {
+                let mut text = String::new();
+                self.inline_children(element, &mut text);
+                let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                if text.is_empty() {
+                    return;
+                }
+                match value.attr("href").and_then(|h| self.resolve(h)) {
+                    Some(href) => push_text(out, &format!("[{}]({})", text, href)),
+                    None => push_text(out, &text),
+                }
+            }
+            _ => self.inline_children(element, out),
+        }
+    }
+
+    /// Surround the content of `element` with `marker`, keeping the
+    /// spaces around it outside the markers.
+    fn wrapped(&self, element: ElementRef, marker: &str, out: &mut String) {
+        let mut inner = String::new();
+        self.inline_children(element, &mut inner);
+        let trimmed = inner.trim();
+        if trimmed.is_empty() {
+            push_text(out, &inner);
+            return;
+        }
+        if inner.starts_with(char::is_whitespace) {
+            push_text(out, " ");
+        }
+        push_text(out, &format!("{}{}{}", marker, trimmed, marker));
+        if inner.ends_with(char::is_whitespace) {
+            out.push(' ');
+        }
+    }
+
+    /// Absolute URL for a link or image, None for in-page and script links.
+    fn resolve(&self, href: &str) -> Option<String> {
+        let href = href.trim();
+        if href.is_empty() || href.starts_with('#') || href.starts_with("javascript:") || href.starts_with("data:") {
+            return None;
+        }
+        let url = match self.base {
+            Some(base) => base.join(href).ok()?.to_string(),
+            None => href.to_string(),
+        };
+        Some(url.replace(' ', "%20").replace('(', "%28").replace(')', "%29"))
+    }
+}
+
+fn is_formatting(element: ElementRef) -> bool {
+    matches!(
+        element.value().name(),
+        "a" | "strong" | "b" | "em" | "i" | "cite" | "del" | "s" | "strike" | "code" | "kbd" | "samp" | "tt"
+    )
+}
+
+fn has_block_descendant(element: ElementRef) -> bool {
+    element
+        .descendants()
+        .skip(1)
+        .filter_map(ElementRef::wrap)
+        .any(|e| BLOCK_TAGS.contains(&e.value().name()))
+}
+
+/// Escape characters Markdown would read as formatting. Underscores
+/// inside words are left alone, they never start emphasis.
+fn escape(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        let escape = match c {
+            '\\' | '*' | '`' | '[' | ']' | '<' => true,
+            '_' => {
+                let before = i > 0 && chars[i - 1].is_alphanumeric();
+                let after = chars.get(i + 1).is_some_and(|c| c.is_alphanumeric());
+                !(before && after)
+            }
+            _ => false,
+        };
+        if escape {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Append text with HTML whitespace rules: runs collapse to one space.
+fn push_text(out: &mut String, text: &str) {
+    if text.starts_with(char::is_whitespace) && !out.is_empty() && !out.ends_with([' ', '\n']) {
+        out.push(' ');
+    }
+    out.push_str(&text.split_whitespace().collect::<Vec<_>>().join(" "));
+    if text.ends_with(char::is_whitespace) && !text.trim().is_empty() {
+        out.push(' ');
+    }
+}
+
+/// Close the current paragraph, if any. Line breaks become hard breaks.
+fn flush(inline: &mut String, blocks: &mut Vec<String>) {
+    let lines: Vec<&str> = inline.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    if !lines.is_empty() {
+        blocks.push(escape_block_start(&lines.join("\\\n")));
+    }
+    inline.clear();
+}
+
+/// Keep a paragraph that happens to start like a heading, quote or list
+/// item from being read as one.
+pub(crate) fn escape_block_start(paragraph: &str) -> String {
+    let digits = paragraph.chars().take_while(char::is_ascii_digit).count();
+    let rest = &paragraph[digits..];
+    let is_marker = paragraph.starts_with('#')
+        || paragraph.starts_with('>')
+        || paragraph.starts_with("- ")
+        || paragraph.starts_with("+ ")
+        || (digits > 0 && (rest.starts_with(". ") || rest.starts_with(") ")));
+
+    if !is_marker {
+        paragraph.to_string()
+    } else if digits > 0 {
+        format!("{}\\{}", &paragraph[..digits], rest)
+    } else {
+        format!("\\{}", paragraph)
+    }
+}
+
+/// Backticks around `code`, more than any run inside it.
+fn inline_code(code: &str) -> String {
+    let ticks = "`".repeat(longest_run(code, '`') + 1);
+    let pad = if code.starts_with('`') || code.ends_with('`') { " " } else { "" };
+    format!("{}{}{}{}{}", ticks, pad, code, pad, ticks)
+}
+
+pub(crate) fn fence_for(code: &str) -> String {
+    "`".repeat(longest_run(code, '`').max(2) + 1)
+}
+
+fn longest_run(text: &str, c: char) -> usize {
+    let mut longest = 0;
+    let mut current = 0;
+    for ch in text.chars() {
+        current = if ch == c { current + 1 } else { 0 };
+        longest = longest.max(current);
+    }
+    longest
+}
+
+/// Language of a code block from `language-*` or `lang-*` classes on the
+/// `pre` or its `code`.
+fn code_language(pre: ElementRef) -> String {
+    let code = pre.children().filter_map(ElementRef::wrap).find(|e| e.value().name() == "code");
+    [Some(pre), code]
+        .into_iter()
+        .flatten()
+        .flat_map(|e| e.value().classes())
+        .find_map(|class| class.strip_prefix("language-").or_else(|| class.strip_prefix("lang-")))
+        .unwrap_or("")
+        .to_string()
+}
+
+fn prefix_lines(text: &str, prefix: &str, empty: &str) -> String {
+    text.lines()
+        .map(|line| if line.is_empty() { empty.to_string() } else { format!("{}{}", prefix, line) })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Indent every line but the first.
+fn prefix_continuation(text: &str, indent: &str) -> String {
+    let mut lines = text.lines();
+    let mut out = lines.next().unwrap_or("").to_string();
+    for line in lines {
+        out.push('\n');
+        if !line.is_empty() {
+            out.push_str(indent);
+            out.push_str(line);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markdown(html: &str) -> String {
+        html_to_markdown(html, None)
+    }
+
+    #[test]
+    fn headings_paragraphs_and_emphasis() {
+        let html = "<h2>Title  <em>here</em></h2><p>Some <strong>bold</strong> and <i>italic</i>\n text, <del>gone</del>.</p>";
+        assert_eq!(markdown(html), "## Title *here*\n\nSome **bold** and *italic* text, ~~gone~~.");
+    }
+
+    #[test]
+    fn links_and_images_resolve_against_the_base() {
+        let base = Url::parse("https://example.com/docs/").unwrap();
+        let html = r##"<p><a href="guide (v2)">Guide</a> <a href="#top">Top</a> <img src="/logo.png" alt="Logo"></p>"##;
+        assert_eq!(
+            html_to_markdown(html, Some(&base)),
+            "[Guide](https://example.com/docs/guide%20%28v2%29) Top ![Logo](https://example.com/logo.png)"
+        );
+    }
+
+    #[test]
+    fn code_blocks_and_inline_code() {
+        let html = r#"<pre><code class="language-rust">fn main() {}
+</code></pre><p>Run <code>cargo `build`</code> now.</p>"#;
+        assert_eq!(markdown(html), "```rust\nfn main() {}\n```\n\nRun `` cargo `build` `` now.");
+        // A fence longer than any run of backticks in the code
+        assert_eq!(markdown("<pre>a ``` b</pre>"), "````\na ``` b\n````");
+    }
+
+    #[test]
+    fn nested_and_ordered_lists() {
+        let html = r#"<ol start="3"><li>Three</li><li>Four<ul><li>Nested</li></ul></li></ol>"#;
+        assert_eq!(markdown(html), "3. Three\n4. Four\n   - Nested");
+    }
+
+    #[test]
+    fn blockquotes_are_prefixed() {
+        assert_eq!(markdown("<blockquote><p>One</p><p>Two</p></blockquote>"), "> One\n>\n> Two");
+    }
+
+    #[test]
+    fn tables_have_a_header_row() {
+        let html = "<table><tr><th>Name</th><th>Value</th></tr><tr><td>a|b</td><td>1</td></tr><tr><td>c</td></tr></table>";
+        assert_eq!(markdown(html), "| Name | Value |\n| --- | --- |\n| a\\|b | 1 |\n| c |  |");
+        // Single-column tables are layout
+        assert_eq!(markdown("<table><tr><td>Only</td></tr><tr><td>Cells</td></tr></table>"), "Only\n\nCells");
+    }
+
+    #[test]
+    fn formatting_characters_are_escaped() {
+        assert_eq!(markdown("<p>2 * 3 [x] snake_case _lead</p>"), "2 \\* 3 \\[x\\] snake_case \\_lead");
+        assert_eq!(markdown("<p># not a heading</p><p>1. not a list</p>"), "\\# not a heading\n\n1\\. not a list");
+    }
+
+    #[test]
+    fn line_breaks_and_hidden_elements() {
+        assert_eq!(markdown("<p>One<br>Two</p><script>alert(1)</script><style>p{}</style>"), "One\\\nTwo");
+    }
+
+    #[test]
+    fn skipped_elements_are_left_out() {
+        let document = Html::parse_document(r#"<p>Keep</p><div class="ad">Drop</div><p>Also</p>"#);
+        let skip = |e: ElementRef| e.value().has_class("ad", scraper::CaseSensitivity::CaseSensitive);
+        let converted = Converter::new(None).skipping(&skip).convert([document.root_element()]);
+        assert_eq!(converted, "Keep\n\nAlso");
+    }
+}
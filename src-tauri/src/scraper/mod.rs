@@ -1,3 +1,9 @@
+pub mod canonical;
 pub mod fetcher;
+pub mod markdown;
 pub mod parser;
 pub mod chunker;
+pub mod pdf;
+pub mod files;
+pub mod politeness;
+pub mod robots;
@@ -1,26 +1,411 @@
-use scraper::{Html, Selector};
+use super::markdown::Converter;
+use reqwest::Url;
+use scraper::{ElementRef, Html, Selector};
+use std::collections::{HashMap, HashSet};
 
-/// Parse HTML and extract readable text
+/// The readable part of a web page.
+#[derive(Debug, Clone, Default)]
+pub struct Article {
+    /// Empty if the page has none.
+    pub title: String,
+    /// Main content as Markdown.
+    pub markdown: String,
+}
+
+/// Elements that never hold article content.
+const SKIP_TAGS: &[&str] = &[
+    "script", "style", "noscript", "template", "nav", "header", "footer", "aside", "iframe",
+    "svg", "canvas", "button", "input", "select", "textarea", "dialog",
+];
+
+/// ARIA roles of page furniture.
+const SKIP_ROLES: &[&str] = &["navigation", "banner", "contentinfo", "complementary", "dialog", "alert"];
+
+/// Class or id words of page furniture.
+const NEGATIVE_HINTS: &[&str] = &[
+    "nav", "menu", "footer", "header", "cookie", "consent", "gdpr", "banner", "comment",
+    "comments", "sidebar", "share", "sharing", "social", "related", "recommend", "promo",
+    "ad", "ads", "advert", "advertisement", "sponsor", "subscribe", "newsletter", "signup",
+    "popup", "modal", "breadcrumb", "pagination", "widget", "masthead", "skip", "hidden",
+    "disqus",
+];
+
+/// Class or id words of the main content.
+const POSITIVE_HINTS: &[&str] = &[
+    "article", "content", "main", "post", "entry", "body", "text", "story", "blog", "prose",
+];
+
+/// Elements whose text is scored to find the content container.
+const SCORED: &str = "p, pre, td, blockquote";
+
+/// Containers dropped from the output when they are mostly links. Only
+/// blocks: an inline link is part of its sentence.
+const LINK_LIST_TAGS: &[&str] = &["div", "section", "ul", "ol", "dl", "table"];
+
+/// Extract the title and main content of an HTML page as Markdown,
+/// leaving out navigation, banners, footers, comments and similar
+/// boilerplate. Relative links are resolved against `page_url`.
+pub fn extract_article(html: &str, page_url: Option<&Url>) -> Article {
+    let document = Html::parse_document(html);
+    let title = extract_title(&document);
+    let base = base_url(&document, page_url);
+    let converter = Converter::new(base.as_ref()).skipping(&is_noise);
+
+    let markdown = match top_candidate(&document) {
+        Some((top, score)) => converter.convert(content_roots(top, score)),
+        // Nothing scored, e.g. a page without paragraphs: keep what is left of the body
+        None => {
+            let body_selector = Selector::parse("body").unwrap();
+            converter.convert(document.select(&body_selector).next())
+        }
+    };
+
+    Article { title, markdown }
+}
+
+/// Parse HTML and extract the readable content as Markdown
 pub fn parse_html(html: &str) -> String {
+    extract_article(html, None).markdown
+}
+
+/// The page's `<link rel="canonical">` target, resolved against
+/// `page_url`. Only http(s) URLs are accepted.
+pub fn canonical_link(html: &str, page_url: &Url) -> Option<Url> {
+    let document = Html::parse_document(html);
+    let selector = Selector::parse("link[rel][href]").unwrap();
+    let href = document
+        .select(&selector)
+        .find(|link| {
+            link.value()
+                .attr("rel")
+                .is_some_and(|rel| rel.split_whitespace().any(|r| r.eq_ignore_ascii_case("canonical")))
+        })?
+        .value()
+        .attr("href")?;
+    let base = base_url(&document, Some(page_url))?;
+    base.join(href.trim())
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+/// Absolute http(s) targets of the page's links, without fragments, in
+/// page order and without repeats. Links marked `rel="nofollow"` are left
+/// out.
+pub fn extract_links(html: &str, page_url: &Url) -> Vec<Url> {
     let document = Html::parse_document(html);
-    let selector = Selector::parse("body p, body h1, body h2, body h3, body h4, body h5, body h6, body li, body article").unwrap();
+    let Some(base) = base_url(&document, Some(page_url)) else {
+        return Vec::new();
+    };
+
+    let selector = Selector::parse("a[href]").unwrap();
+    let mut links = Vec::new();
+    let mut seen = HashSet::new();
+    for anchor in document.select(&selector) {
+        let value = anchor.value();
+        if value.attr("rel").is_some_and(|rel| rel.split_whitespace().any(|r| r.eq_ignore_ascii_case("nofollow"))) {
+            continue;
+        }
+        let Some(mut link) = value.attr("href").and_then(|href| base.join(href.trim()).ok()) else {
+            continue;
+        };
+        if !matches!(link.scheme(), "http" | "https") {
+            continue;
+        }
+        link.set_fragment(None);
+        if seen.insert(link.to_string()) {
+            links.push(link);
+        }
+    }
+    links
+}
+
+fn extract_title(document: &Html) -> String {
+    let og_selector = Selector::parse(r#"meta[property="og:title"]"#).unwrap();
+    let og_title = document
+        .select(&og_selector)
+        .next()
+        .and_then(|e| e.value().attr("content"))
+        .map(collapse_whitespace);
+    if let Some(title) = og_title.filter(|t| !t.is_empty()) {
+        return title;
+    }
+
+    let selector = Selector::parse("title, h1").unwrap();
+    document
+        .select(&selector)
+        .map(|e| collapse_whitespace(&e.text().collect::<String>()))
+        .find(|t| !t.is_empty())
+        .unwrap_or_default()
+}
+
+/// The element holding most of the prose, with its score. Each paragraph
+/// credits its parent fully and its grandparent by half, so the container
+/// of many substantial paragraphs wins; link-heavy containers are marked
+/// down.
+fn top_candidate(document: &Html) -> Option<(ElementRef<'_>, f64)> {
+    let selector = Selector::parse(SCORED).unwrap();
+    let mut scores = HashMap::new();
+    let mut candidates = Vec::new();
+
+    for paragraph in document.select(&selector) {
+        if is_excluded(paragraph) || has_excluded_ancestor(paragraph) {
+            continue;
+        }
+        let text = collapse_whitespace(&paragraph.text().collect::<String>());
+        if text.len() < 25 {
+            continue;
+        }
+        let score = 1.0 + text.matches(',').count() as f64 + (text.len() as f64 / 100.0).min(3.0);
+
+        let ancestors = paragraph.ancestors().filter_map(ElementRef::wrap).take(2);
+        for (level, ancestor) in ancestors.enumerate() {
+            let entry = scores.entry(ancestor.id()).or_insert_with(|| {
+                candidates.push(ancestor);
+                initial_score(ancestor)
+            });
+            *entry += if level == 0 { score } else { score / 2.0 };
+        }
+    }
+
+    candidates
+        .into_iter()
+        .map(|c| (c, scores[&c.id()] * (1.0 - link_density(c))))
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+}
+
+fn initial_score(element: ElementRef) -> f64 {
+    let tag_score = match element.value().name() {
+        "article" | "main" => 10.0,
+        "div" | "section" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "ol" | "ul" | "dl" | "form" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    };
+    tag_score + class_weight(element)
+}
+
+fn class_weight(element: ElementRef) -> f64 {
+    let hints = hint_words(element);
+    let mut weight = 0.0;
+    if has_hint(&hints, NEGATIVE_HINTS) {
+        weight -= 25.0;
+    }
+    if has_hint(&hints, POSITIVE_HINTS) {
+        weight += 25.0;
+    }
+    weight
+}
+
+/// The words of an element's class and id, split on whitespace, `-`
+/// and `_` so that e.g. `navbar` or `canvas` don't match `nav`.
+fn hint_words(element: ElementRef) -> Vec<String> {
+    let value = element.value();
+    [value.attr("class"), value.attr("id")]
+        .into_iter()
+        .flatten()
+        .flat_map(|v| v.split(|c: char| c.is_whitespace() || c == '-' || c == '_'))
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn has_hint(words: &[String], hints: &[&str]) -> bool {
+    words.iter().any(|w| hints.contains(&w.as_str()))
+}
+
+/// Whether an element is boilerplate by its tag, role or class names.
+fn is_excluded(element: ElementRef) -> bool {
+    let value = element.value();
+    let tag = value.name();
+    if SKIP_TAGS.contains(&tag) {
+        return true;
+    }
+    if value.attr("hidden").is_some() || value.attr("aria-hidden") == Some("true") {
+        return true;
+    }
+    if value.attr("role").is_some_and(|r| SKIP_ROLES.contains(&r)) {
+        return true;
+    }
+    if matches!(tag, "html" | "body" | "article" | "main") {
+        return false;
+    }
+    let hints = hint_words(element);
+    has_hint(&hints, NEGATIVE_HINTS) && !has_hint(&hints, POSITIVE_HINTS)
+}
+
+fn has_excluded_ancestor(element: ElementRef) -> bool {
+    element.ancestors().filter_map(ElementRef::wrap).any(is_excluded)
+}
+
+/// Share of an element's text that sits inside links.
+fn link_density(element: ElementRef) -> f64 {
+    let total = text_len(element);
+    if total == 0 {
+        return 0.0;
+    }
+    let link_selector = Selector::parse("a").unwrap();
+    let linked: usize = element.select(&link_selector).map(text_len).sum();
+    linked as f64 / total as f64
+}
 
-    let mut text = String::new();
-    for element in document.select(&selector) {
-        let element_text = element.text().collect::<Vec<_>>().join(" ");
-        if !element_text.trim().is_empty() {
-            text.push_str(&element_text);
-            text.push('\n');
+fn text_len(element: ElementRef) -> usize {
+    element.text().map(|t| t.trim().len()).sum()
+}
+
+/// The top candidate plus the siblings that look like part of the same
+/// article, e.g. when a post is split across several divs.
+fn content_roots(top: ElementRef, top_score: f64) -> Vec<ElementRef> {
+    let Some(parent) = top.parent().and_then(ElementRef::wrap) else {
+        return vec![top];
+    };
+
+    let threshold = (top_score * 0.2).max(10.0);
+    let sibling_selector = Selector::parse(SCORED).unwrap();
+    let mut roots = Vec::new();
+    for sibling in parent.children().filter_map(ElementRef::wrap) {
+        if sibling.id() == top.id() {
+            roots.push(sibling);
+            continue;
+        }
+        if is_excluded(sibling) {
+            continue;
+        }
+
+        let density = link_density(sibling);
+        let len = text_len(sibling);
+        let is_paragraph = sibling.value().name() == "p";
+        let paragraphs = sibling.select(&sibling_selector).count() as f64;
+        let score = (initial_score(sibling) + paragraphs) * (1.0 - density);
+        if score >= threshold || (is_paragraph && len > 80 && density < 0.25) {
+            roots.push(sibling);
         }
     }
-    
-    // Fallback: If no structured content found, try body text directly (but avoid script/style)
-    if text.trim().is_empty() {
-         let body_selector = Selector::parse("body").unwrap();
-         if let Some(body) = document.select(&body_selector).next() {
-             text = body.text().collect::<Vec<_>>().join(" ");
-         }
+    roots
+}
+
+/// Boilerplate inside the content: page furniture and link lists.
+fn is_noise(element: ElementRef) -> bool {
+    is_excluded(element)
+        || (LINK_LIST_TAGS.contains(&element.value().name()) && link_density(element) > 0.5)
+}
+
+/// Base for relative links: the page's `<base href>`, resolved against
+/// the URL it was fetched from.
+fn base_url(document: &Html, page_url: Option<&Url>) -> Option<Url> {
+    let selector = Selector::parse("base[href]").unwrap();
+    let href = document.select(&selector).next().and_then(|e| e.value().attr("href"));
+    match (href, page_url) {
+        (Some(href), Some(page)) => page.join(href).ok().or_else(|| Some(page.clone())),
+        (Some(href), None) => Url::parse(href).ok(),
+        (None, page) => page.cloned(),
     }
+}
 
-    text
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROSE: &str = "This paragraph is long enough to count as content, with commas, clauses, and detail.";
+
+    fn page(body: &str) -> String {
+        format!("<html><head><title>Page title</title></head><body>{}</body></html>", body)
+    }
+
+    #[test]
+    fn keeps_the_article_and_drops_page_furniture() {
+        let html = page(&format!(
+            r#"<nav><a href="/">Home</a></nav>
+            <div class="cookie-banner">We use cookies to improve your experience on this site.</div>
+            <article><h1>Heading</h1><p>{p}</p><p>{p}</p></article>
+            <aside>Related reading that is not part of the article at all.</aside>
+            <footer>Copyright and contact details for the whole site.</footer>"#,
+            p = PROSE
+        ));
+        let article = extract_article(&html, None);
+        assert_eq!(article.title, "Page title");
+        assert!(article.markdown.contains("# Heading"));
+        assert!(article.markdown.contains("enough to count as content"));
+        for furniture in ["Home", "cookies", "Related reading", "Copyright"] {
+            assert!(!article.markdown.contains(furniture), "{} kept:\n{}", furniture, article.markdown);
+        }
+    }
+
+    #[test]
+    fn prefers_the_og_title() {
+        let html = r#"<html><head><meta property="og:title" content=" Shared  title "><title>Tab</title></head></html>"#;
+        assert_eq!(extract_article(html, None).title, "Shared title");
+    }
+
+    #[test]
+    fn hints_match_whole_class_words() {
+        let html = page(&format!(
+            r#"<div class="canvas-wrapper"><p>{p}</p><p>{p}</p></div>
+            <div class="site_nav"><p>Navigation paragraph that must not be kept in the output.</p></div>"#,
+            p = PROSE
+        ));
+        let markdown = extract_article(&html, None).markdown;
+        assert!(markdown.contains("enough to count as content"));
+        assert!(!markdown.contains("Navigation paragraph"));
+    }
+
+    #[test]
+    fn content_inside_a_form_is_kept() {
+        let html = page(&format!(r#"<form action="/post"><div class="content"><p>{p}</p><p>{p}</p></div></form>"#, p = PROSE));
+        assert!(extract_article(&html, None).markdown.contains("enough to count as content"));
+    }
+
+    #[test]
+    fn links_inside_sentences_are_kept() {
+        let html = page(&format!(
+            r#"<article><p>{p}</p><p>See <span><a href="/docs">the docs</a></span> for details about the setup.</p></article>"#,
+            p = PROSE
+        ));
+        let base = Url::parse("https://example.com/guide/").unwrap();
+        let markdown = extract_article(&html, Some(&base)).markdown;
+        assert!(markdown.contains("See [the docs](https://example.com/docs) for details"), "{}", markdown);
+    }
+
+    #[test]
+    fn link_lists_are_dropped() {
+        let html = page(&format!(
+            r#"<article><p>{p}</p><p>{p}</p>
+            <ul><li><a href="/a">First related link</a></li><li><a href="/b">Second related link</a></li></ul></article>"#,
+            p = PROSE
+        ));
+        let markdown = extract_article(&html, None).markdown;
+        assert!(markdown.contains("enough to count as content"));
+        assert!(!markdown.contains("related link"), "{}", markdown);
+    }
+
+    #[test]
+    fn canonical_link_is_resolved() {
+        let url = Url::parse("https://example.com/a/page?utm_source=x").unwrap();
+        let html = r#"<head><link rel="alternate" href="/feed"><link rel="Canonical" href="/a/page"></head>"#;
+        assert_eq!(canonical_link(html, &url).unwrap().as_str(), "https://example.com/a/page");
+        assert_eq!(canonical_link(r#"<link rel="canonical" href="ftp://example.com/x">"#, &url), None);
+        assert_eq!(canonical_link("<p>none</p>", &url), None);
+    }
+
+    #[test]
+    fn links_are_absolute_unique_and_followable() {
+        let url = Url::parse("https://example.com/docs/").unwrap();
+        let html = r#"<a href="intro#top">Intro</a> <a href="intro">Again</a>
+            <a href="https://other.org/x">Other</a> <a href="/private" rel="nofollow">Hidden</a>
+            <a href="mailto:me@example.com">Mail</a> <a href="javascript:void(0)">Script</a>"#;
+        let links: Vec<String> = extract_links(html, &url).into_iter().map(String::from).collect();
+        assert_eq!(links, ["https://example.com/docs/intro", "https://other.org/x"]);
+    }
+
+    #[test]
+    fn base_href_is_honoured() {
+        let url = Url::parse("https://example.com/docs/page").unwrap();
+        let html = r#"<head><base href="/static/"></head><body><a href="file">File</a></body>"#;
+        let links = extract_links(html, &url);
+        assert_eq!(links[0].as_str(), "https://example.com/static/file");
+    }
 }
@@ -0,0 +1,187 @@
+use super::markdown::escape_block_start;
+use pdf_extract::{decode_text_string, Document};
+
+/// Text extracted from a PDF.
+#[derive(Debug, Clone)]
+pub struct PdfText {
+    /// From the document info, if it has one.
+    pub title: Option<String>,
+    /// Text of each page, in order.
+    pub pages: Vec<String>,
+}
+
+/// Extract the title and per-page text of a PDF.
+///
+/// The underlying parser panics on some malformed files, so call this
+/// where a panic is contained, e.g. on a blocking task.
+pub fn extract(bytes: &[u8]) -> Result<PdfText, String> {
+    let document = Document::load_mem(bytes).map_err(|e| format!("Not a readable PDF: {}", e))?;
+    let title = info_title(&document);
+    drop(document);
+
+    let pages = pdf_extract::extract_text_from_mem_by_pages(bytes).map_err(|e| e.to_string())?;
+    if pages.iter().all(|p| p.trim().is_empty()) {
+        return Err("The PDF has no extractable text (is it a scan?)".to_string());
+    }
+    Ok(PdfText { title, pages })
+}
+
+fn info_title(document: &Document) -> Option<String> {
+    let info = document.trailer.get(b"Info").ok()?;
+    let info = match info.as_reference() {
+        Ok(id) => document.get_object(id).ok()?,
+        Err(_) => info,
+    };
+    let title = decode_text_string(info.as_dict().ok()?.get(b"Title").ok()?).ok()?;
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+/// Marker line put before the text of each page. Invisible when the
+/// Markdown is rendered; the chunker reads it to tag chunks with pages.
+pub fn page_marker(page: usize) -> String {
+    format!("<!-- page {} -->", page)
+}
+
+/// Page number of a marker line.
+pub fn parse_page_marker(line: &str) -> Option<u32> {
+    line.trim()
+        .strip_prefix("<!-- page ")?
+        .strip_suffix("-->")?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Markdown for the document: the title as a heading, then each page
+/// behind its marker, with hard-wrapped lines joined into paragraphs.
+pub fn to_markdown(title: &str, pages: &[String]) -> String {
+    let mut out = format!("# {}\n", title);
+    for (i, page) in pages.iter().enumerate() {
+        let paragraphs = reflow(page);
+        if paragraphs.is_empty() {
+            continue;
+        }
+        out.push('\n');
+        out.push_str(&page_marker(i + 1));
+        out.push_str("\n\n");
+        out.push_str(&paragraphs.join("\n\n"));
+        out.push('\n');
+    }
+    out
+}
+
+/// Join the lines of each blank-line separated paragraph, undoing
+/// hyphenation at line ends.
+fn reflow(page: &str) -> Vec<String> {
+    let mut paragraphs = Vec::new();
+    let mut current = String::new();
+
+    for line in page.lines().map(str::trim) {
+        if line.is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(escape_block_start(&current));
+                current.clear();
+            }
+            continue;
+        }
+        if current.ends_with('-') && line.starts_with(char::is_lowercase) {
+            current.pop();
+        } else if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(&line.split_whitespace().collect::<Vec<_>>().join(" "));
+    }
+    if !current.is_empty() {
+        paragraphs.push(escape_block_start(&current));
+    }
+    paragraphs
+}
+
+/// Helpers for tests that need a PDF.
+#[cfg(test)]
+pub(crate) mod testing {
+    /// A minimal PDF with one line of Helvetica text per page and, if
+    /// given, a title in its document info.
+    pub fn build(title: Option<&str>, pages: &[&str]) -> Vec<u8> {
+        let page_ids: Vec<usize> = (0..pages.len()).map(|i| 5 + 2 * i).collect();
+        let kids = page_ids.iter().map(|id| format!("{} 0 R", id)).collect::<Vec<_>>().join(" ");
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids, pages.len()),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+            format!("<< /Title ({}) >>", title.unwrap_or("")),
+        ];
+        for (i, text) in pages.iter().enumerate() {
+            let stream = format!("BT /F1 12 Tf 72 720 Td ({}) Tj ET", text);
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+                page_ids[i] + 1
+            ));
+            objects.push(format!("<< /Length {} >>\nstream\n{}\nendstream", stream.len(), stream));
+        }
+
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+        }
+        let xref = pdf.len();
+        pdf.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+        for offset in offsets {
+            pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        let info = if title.is_some() { " /Info 4 0 R" } else { "" };
+        pdf.extend_from_slice(
+            format!("trailer\n<< /Size {} /Root 1 0 R{} >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, info, xref).as_bytes(),
+        );
+        pdf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_and_title_are_extracted_per_page() {
+        let pdf = testing::build(Some("  Ownership   in Rust "), &["Every value has an owner.", "Borrowing lends it."]);
+        let extracted = extract(&pdf).unwrap();
+        assert_eq!(extracted.title.as_deref(), Some("Ownership in Rust"));
+        assert_eq!(extracted.pages.len(), 2);
+        assert!(extracted.pages[0].contains("Every value has an owner."));
+        assert!(extracted.pages[1].contains("Borrowing lends it."));
+
+        let untitled = extract(&testing::build(None, &["Text"])).unwrap();
+        assert_eq!(untitled.title, None);
+    }
+
+    #[test]
+    fn unreadable_and_textless_pdfs_are_errors() {
+        assert!(extract(b"not a pdf").unwrap_err().starts_with("Not a readable PDF"));
+        assert!(extract(&testing::build(None, &[""])).unwrap_err().contains("no extractable text"));
+    }
+
+    #[test]
+    fn page_markers_round_trip() {
+        assert_eq!(parse_page_marker(&page_marker(12)), Some(12));
+        assert_eq!(parse_page_marker("  <!-- page 3 -->  "), Some(3));
+        assert_eq!(parse_page_marker("<!-- pages 3 -->"), None);
+        assert_eq!(parse_page_marker("<!-- page x -->"), None);
+    }
+
+    #[test]
+    fn pages_are_reflowed_behind_their_markers() {
+        let pages = vec![
+            "A line broken\nacross two, with hyphen-\nation.\n\n# not a heading".to_string(),
+            "   \n".to_string(),
+            "Last   page.".to_string(),
+        ];
+        let markdown = to_markdown("Doc", &pages);
+        assert_eq!(
+            markdown,
+            "# Doc\n\n<!-- page 1 -->\n\nA line broken across two, with hyphenation.\n\n\\# not a heading\n\n<!-- page 3 -->\n\nLast page.\n"
+        );
+    }
+}
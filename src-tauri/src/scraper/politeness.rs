@@ -0,0 +1,62 @@
+use reqwest::Url;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+/// Least time between the starts of two requests to one host, unless
+/// its robots.txt asks for more.
+pub const MIN_HOST_INTERVAL: Duration = Duration::from_secs(1);
+/// Requests in flight to one host at a time.
+pub const MAX_HOST_CONCURRENCY: usize = 2;
+
+struct Host {
+    permits: Arc<Semaphore>,
+    /// Earliest start of the next request.
+    next_start: tokio::sync::Mutex<Instant>,
+}
+
+fn hosts() -> &'static Mutex<HashMap<String, Arc<Host>>> {
+    static HOSTS: OnceLock<Mutex<HashMap<String, Arc<Host>>>> = OnceLock::new();
+    HOSTS.get_or_init(Default::default)
+}
+
+/// Permission to send one request to a host. Keep it until the response
+/// body is read; dropping it frees the slot.
+pub struct HostSlot {
+    _permit: OwnedSemaphorePermit,
+}
+
+/// Wait for a slot to request `url`: at most `MAX_HOST_CONCURRENCY`
+/// requests run per host, and each starts at least `MIN_HOST_INTERVAL`
+/// (or the site's `crawl_delay`, if longer) after the one before.
+pub async fn acquire(url: &Url, crawl_delay: Option<Duration>) -> HostSlot {
+    let key = format!("{}:{}", url.host_str().unwrap_or(""), url.port_or_known_default().unwrap_or(0));
+    // The map is valid even if a holder panicked
+    let host = hosts()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(key)
+        .or_insert_with(|| {
+            Arc::new(Host {
+                permits: Arc::new(Semaphore::new(MAX_HOST_CONCURRENCY)),
+                next_start: tokio::sync::Mutex::new(Instant::now()),
+            })
+        })
+        .clone();
+
+    // The semaphore is never closed, so acquiring cannot fail
+    let permit = host.permits.clone().acquire_owned().await.expect("host semaphore is open");
+
+    let interval = crawl_delay.map_or(MIN_HOST_INTERVAL, |d| d.max(MIN_HOST_INTERVAL));
+    let start = {
+        let mut next_start = host.next_start.lock().await;
+        let start = (*next_start).max(Instant::now());
+        *next_start = start + interval;
+        start
+    };
+    tokio::time::sleep_until(start).await;
+
+    HostSlot { _permit: permit }
+}
@@ -0,0 +1,284 @@
+use super::fetcher;
+use reqwest::Url;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Product token matched against `User-agent` lines.
+pub const AGENT_TOKEN: &str = "re_research";
+
+/// How long a fetched robots.txt is trusted.
+const CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long an unreachable robots.txt blocks the site before retrying.
+const UNREACHABLE_TTL: Duration = Duration::from_secs(5 * 60);
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+/// Larger files are cut off here, as RFC 9309 allows.
+const MAX_BYTES: usize = 500 * 1024;
+/// Crawl-delays beyond this are treated as this.
+const MAX_CRAWL_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+/// The rules of a robots.txt that apply to this app.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Robots {
+    rules: Vec<Rule>,
+    /// From the `Crawl-delay` of the matching group.
+    pub crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    pub fn disallow_all() -> Self {
+        Self {
+            rules: vec![Rule { allow: false, pattern: "/".to_string() }],
+            crawl_delay: None,
+        }
+    }
+
+    /// Parse a robots.txt, keeping the groups for `agent`, or the `*`
+    /// groups if none name it.
+    pub fn parse(text: &str, agent: &str) -> Self {
+        struct Group {
+            agents: Vec<String>,
+            rules: Vec<Rule>,
+            crawl_delay: Option<Duration>,
+        }
+
+        let mut groups: Vec<Group> = Vec::new();
+        // Consecutive User-agent lines share one group
+        let mut in_agents = false;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let (key, value) = (key.trim().to_lowercase(), value.trim());
+
+            if key == "user-agent" {
+                if !in_agents {
+                    groups.push(Group { agents: Vec::new(), rules: Vec::new(), crawl_delay: None });
+                    in_agents = true;
+                }
+                if let Some(group) = groups.last_mut() {
+                    group.agents.push(value.to_lowercase());
+                }
+                continue;
+            }
+            in_agents = false;
+            // Rules before any User-agent line belong to no group
+            let Some(group) = groups.last_mut() else {
+                continue;
+            };
+            match key.as_str() {
+                "allow" | "disallow" if !value.is_empty() => group.rules.push(Rule {
+                    allow: key == "allow",
+                    pattern: value.to_string(),
+                }),
+                "crawl-delay" => {
+                    group.crawl_delay = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|d| d.is_finite() && *d >= 0.0)
+                        .map(|d| Duration::from_secs_f64(d).min(MAX_CRAWL_DELAY));
+                }
+                _ => {}
+            }
+        }
+
+        let agent = agent.to_lowercase();
+        let named = groups.iter().any(|g| g.agents.contains(&agent));
+        let wanted = if named { agent } else { "*".to_string() };
+
+        let mut robots = Self::default();
+        for group in groups.into_iter().filter(|g| g.agents.contains(&wanted)) {
+            robots.rules.extend(group.rules);
+            robots.crawl_delay = robots.crawl_delay.max(group.crawl_delay);
+        }
+        robots
+    }
+
+    /// Whether a URL path (with its query) may be fetched. The longest
+    /// matching rule decides; on a tie, allowing wins.
+    pub fn is_allowed(&self, path: &str) -> bool {
+        if path == "/robots.txt" {
+            return true;
+        }
+        self.rules
+            .iter()
+            .filter(|rule| matches(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+}
+
+/// Whether a robots.txt pattern matches the start of `path`. `*` matches
+/// any run of characters and a trailing `$` anchors the end.
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(p) => (p, true),
+        None => (pattern, false),
+    };
+
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let Some(mut rest) = path.strip_prefix(parts[0]) else {
+        return false;
+    };
+    if parts.len() == 1 {
+        return !anchored || rest.is_empty();
+    }
+    for (i, part) in parts.iter().enumerate().skip(1) {
+        if anchored && i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+struct Cached {
+    robots: Arc<Robots>,
+    expires: Instant,
+}
+
+/// Per site, so concurrent requests to a new site fetch its robots.txt once.
+type Entry = Arc<tokio::sync::Mutex<Option<Cached>>>;
+
+fn cache() -> &'static Mutex<HashMap<String, Entry>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, Entry>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+/// The robots.txt rules for the site of `url`, fetched once a day per
+/// site. A missing file allows everything; an unreachable one blocks the
+/// site for a few minutes.
+pub async fn for_url(url: &Url) -> Arc<Robots> {
+    let origin = url.origin().ascii_serialization();
+    // The map is valid even if a holder panicked
+    let entry = cache().lock().unwrap_or_else(PoisonError::into_inner).entry(origin.clone()).or_default().clone();
+
+    let mut cached = entry.lock().await;
+    if let Some(cached) = cached.as_ref().filter(|c| c.expires > Instant::now()) {
+        return cached.robots.clone();
+    }
+
+    let (robots, ttl) = match fetch(&origin).await {
+        Some(robots) => (robots, CACHE_TTL),
+        None => (Robots::disallow_all(), UNREACHABLE_TTL),
+    };
+    let robots = Arc::new(robots);
+    *cached = Some(Cached { robots: robots.clone(), expires: Instant::now() + ttl });
+    robots
+}
+
+/// None when the site could not be asked, i.e. on network errors and
+/// server errors.
+async fn fetch(origin: &str) -> Option<Robots> {
+    let url = Url::parse(&format!("{}/robots.txt", origin)).ok()?;
+    let _slot = super::politeness::acquire(&url, None).await;
+
+    let mut res = fetcher::client().get(url).timeout(FETCH_TIMEOUT).send().await.ok()?;
+    let status = res.status();
+    if status.is_client_error() {
+        return Some(Robots::allow_all());
+    }
+    if !status.is_success() {
+        return None;
+    }
+
+    let mut bytes = Vec::new();
+    while let Some(chunk) = res.chunk().await.ok()? {
+        bytes.extend_from_slice(&chunk);
+        if bytes.len() >= MAX_BYTES {
+            bytes.truncate(MAX_BYTES);
+            break;
+        }
+    }
+    Some(Robots::parse(&String::from_utf8_lossy(&bytes), AGENT_TOKEN))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_prefixes() {
+        assert!(matches("/", "/anything"));
+        assert!(matches("/private", "/private/page"));
+        assert!(matches("/private", "/private-notes"));
+        assert!(!matches("/private", "/public"));
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(matches("/*.pdf", "/docs/report.pdf"));
+        assert!(matches("/*/edit", "/wiki/page/edit?x=1"));
+        assert!(matches("/a*b*c", "/a-b-c"));
+        assert!(!matches("/a*b*c", "/a-c-b"));
+        assert!(matches("*", "/"));
+    }
+
+    #[test]
+    fn dollar_anchors_the_end() {
+        assert!(matches("/*.pdf$", "/docs/report.pdf"));
+        assert!(!matches("/*.pdf$", "/docs/report.pdf?download=1"));
+        assert!(matches("/exact$", "/exact"));
+        assert!(!matches("/exact$", "/exact/more"));
+    }
+
+    #[test]
+    fn parse_picks_the_named_group_over_star() {
+        let text = "User-agent: *\nDisallow: /\n\nUser-agent: RE_ReSearch\nUser-agent: re_research\nDisallow: /private\nCrawl-delay: 2";
+        let robots = Robots::parse(text, AGENT_TOKEN);
+        assert!(robots.is_allowed("/public"));
+        assert!(!robots.is_allowed("/private/x"));
+        assert_eq!(robots.crawl_delay, Some(Duration::from_secs(2)));
+
+        let other = Robots::parse(text, "someone_else");
+        assert!(!other.is_allowed("/public"));
+        assert_eq!(other.crawl_delay, None);
+    }
+
+    #[test]
+    fn parse_ignores_comments_empty_rules_and_stray_lines() {
+        let text = "Disallow: /before-any-group\n# comment\nUser-agent: * # everyone\nDisallow:\nDisallow: /tmp # scratch\nCrawl-delay: 1000";
+        let robots = Robots::parse(text, AGENT_TOKEN);
+        assert!(robots.is_allowed("/before-any-group"));
+        assert!(robots.is_allowed("/"));
+        assert!(!robots.is_allowed("/tmp/file"));
+        assert_eq!(robots.crawl_delay, Some(MAX_CRAWL_DELAY));
+    }
+
+    #[test]
+    fn longest_match_wins() {
+        let robots = Robots::parse("User-agent: *\nDisallow: /docs\nAllow: /docs/public\nDisallow: /docs/public/drafts", AGENT_TOKEN);
+        assert!(!robots.is_allowed("/docs/secret"));
+        assert!(robots.is_allowed("/docs/public/page"));
+        assert!(!robots.is_allowed("/docs/public/drafts/1"));
+    }
+
+    #[test]
+    fn tie_allows() {
+        let robots = Robots::parse("User-agent: *\nDisallow: /page\nAllow: /page", AGENT_TOKEN);
+        assert!(robots.is_allowed("/page"));
+        let robots = Robots::parse("User-agent: *\nAllow: /*.html\nDisallow: /page.*", AGENT_TOKEN);
+        assert!(robots.is_allowed("/page.html"));
+    }
+
+    #[test]
+    fn robots_txt_itself_is_always_allowed() {
+        assert!(Robots::disallow_all().is_allowed("/robots.txt"));
+        assert!(!Robots::disallow_all().is_allowed("/"));
+        assert!(Robots::allow_all().is_allowed("/anything"));
+    }
+}
@@ -0,0 +1,295 @@
+use crate::ai::embeddings::{gemini, ollama, openai};
+use crate::scraper::chunker::{self, ChunkOptions};
+use reqwest::Url;
+use rusqlite::{params, Connection, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+pub const LLM_PROVIDERS: &[&str] = &["ollama", "openai", "gemini", "mock"];
+pub const EMBEDDING_PROVIDERS: &[&str] = &["ollama", "gemini", "openai", "hash"];
+pub const DEFAULT_LLM_MODEL: &str = "ministral-3:8b";
+/// A year.
+pub const MAX_SOURCE_REFRESH_HOURS: u32 = 24 * 365;
+
+/// Provider, model and endpoint configuration shared by every AI call site.
+///
+/// Stored one key per row in the `settings` table (values as JSON), so
+/// fields added later simply fall back to their default on older databases.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppSettings {
+    pub llm_provider: String,
+    pub llm_model: String,
+    pub embedding_provider: String,
+    pub embedding_model: String,
+    pub embedding_dimension: usize,
+    pub ollama_url: String,
+    pub openai_base_url: String,
+    pub openai_api_key: Option<String>,
+    pub gemini_base_url: String,
+    pub gemini_api_key: Option<String>,
+    /// Largest chunk, in tokens. Changing it only affects nodes indexed afterwards.
+    pub chunk_max_tokens: usize,
+    pub chunk_overlap_tokens: usize,
+    /// Hours between automatic refreshes of web sources; 0 turns them off.
+    pub source_refresh_hours: u32,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            llm_provider: "ollama".to_string(),
+            llm_model: DEFAULT_LLM_MODEL.to_string(),
+            embedding_provider: "ollama".to_string(),
+            embedding_model: ollama::DEFAULT_MODEL.to_string(),
+            embedding_dimension: ollama::DEFAULT_DIMENSION,
+            ollama_url: ollama::DEFAULT_BASE_URL.to_string(),
+            openai_base_url: openai::DEFAULT_BASE_URL.to_string(),
+            openai_api_key: None,
+            gemini_base_url: gemini::DEFAULT_BASE_URL.to_string(),
+            gemini_api_key: None,
+            chunk_max_tokens: chunker::DEFAULT_MAX_TOKENS,
+            chunk_overlap_tokens: chunker::DEFAULT_OVERLAP_TOKENS,
+            source_refresh_hours: 0,
+        }
+    }
+}
+
+impl AppSettings {
+    /// Check the settings are usable before they are persisted.
+    pub fn validate(&self) -> Result<(), String> {
+        if !LLM_PROVIDERS.contains(&self.llm_provider.as_str()) {
+            return Err(format!("Unknown LLM provider: {}", self.llm_provider));
+        }
+        if !EMBEDDING_PROVIDERS.contains(&self.embedding_provider.as_str()) {
+            return Err(format!("Unknown embedding provider: {}", self.embedding_provider));
+        }
+        if self.llm_model.trim().is_empty() {
+            return Err("LLM model must not be empty".to_string());
+        }
+        if self.embedding_model.trim().is_empty() {
+            return Err("Embedding model must not be empty".to_string());
+        }
+        if self.embedding_dimension == 0 || self.embedding_dimension > 8192 {
+            return Err(format!(
+                "Embedding dimension must be between 1 and 8192, got {}",
+                self.embedding_dimension
+            ));
+        }
+
+        if self.chunk_max_tokens < 32 || self.chunk_max_tokens > 8192 {
+            return Err(format!(
+                "Chunk size must be between 32 and 8192 tokens, got {}",
+                self.chunk_max_tokens
+            ));
+        }
+        if self.chunk_overlap_tokens * 2 > self.chunk_max_tokens {
+            return Err("Chunk overlap must be at most half the chunk size".to_string());
+        }
+        if self.source_refresh_hours > MAX_SOURCE_REFRESH_HOURS {
+            return Err(format!(
+                "Source refresh interval must be at most {} hours, got {}",
+                MAX_SOURCE_REFRESH_HOURS, self.source_refresh_hours
+            ));
+        }
+
+        validate_url("Ollama URL", &self.ollama_url)?;
+        validate_url("OpenAI base URL", &self.openai_base_url)?;
+        validate_url("Gemini base URL", &self.gemini_base_url)?;
+
+        let uses_gemini = self.llm_provider == "gemini" || self.embedding_provider == "gemini";
+        if uses_gemini && is_blank(&self.gemini_api_key) {
+            return Err("API key required for Gemini".to_string());
+        }
+
+        Ok(())
+    }
+
+    pub fn chunk_options(&self) -> ChunkOptions {
+        ChunkOptions {
+            max_tokens: self.chunk_max_tokens,
+            overlap_tokens: self.chunk_overlap_tokens,
+        }
+    }
+}
+
+fn is_blank(value: &Option<String>) -> bool {
+    value.as_deref().is_none_or(|v| v.trim().is_empty())
+}
+
+fn validate_url(name: &str, value: &str) -> Result<(), String> {
+    let url = Url::parse(value).map_err(|e| format!("{} is invalid: {}", name, e))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(format!("{} must use http or https", name));
+    }
+    Ok(())
+}
+
+pub fn init_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at TEXT DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Read the stored settings, filling anything missing with defaults.
+pub fn load(conn: &Connection) -> Result<AppSettings> {
+    let mut merged = match serde_json::to_value(AppSettings::default()) {
+        Ok(Value::Object(map)) => map,
+        _ => Map::new(),
+    };
+
+    let mut stmt = conn.prepare("SELECT key, value FROM settings")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+    for row in rows {
+        let (key, value) = row?;
+        // Ignore keys of removed settings and values that no longer parse
+        if merged.contains_key(&key) {
+            if let Ok(value) = serde_json::from_str(&value) {
+                merged.insert(key, value);
+            }
+        }
+    }
+
+    // A stored value of the wrong type falls back to all defaults rather
+    // than making every AI command fail
+    Ok(serde_json::from_value(Value::Object(merged)).unwrap_or_default())
+}
+
+/// Validate and persist every field of `settings`.
+pub fn save(conn: &Connection, settings: &AppSettings) -> Result<(), String> {
+    settings.validate()?;
+
+    let map = match serde_json::to_value(settings).map_err(|e| e.to_string())? {
+        Value::Object(map) => map,
+        _ => return Err("Settings must serialize to an object".to_string()),
+    };
+    let now = chrono::Utc::now().to_rfc3339();
+    for (key, value) in map {
+        conn.execute(
+            "INSERT INTO settings (key, value, updated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+            params![key, value.to_string(), now],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Apply a partial update (a JSON object with any subset of the fields)
+/// on top of `current`.
+pub fn merge(current: &AppSettings, patch: Value) -> Result<AppSettings, String> {
+    let Value::Object(patch) = patch else {
+        return Err("Settings update must be an object".to_string());
+    };
+    let mut merged = match serde_json::to_value(current).map_err(|e| e.to_string())? {
+        Value::Object(map) => map,
+        _ => Map::new(),
+    };
+    for (key, value) in patch {
+        if !merged.contains_key(&key) {
+            return Err(format!("Unknown setting: {}", key));
+        }
+        merged.insert(key, value);
+    }
+    serde_json::from_value(Value::Object(merged)).map_err(|e| format!("Invalid settings: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing::open_db;
+    use serde_json::json;
+
+    fn invalid(change: impl FnOnce(&mut AppSettings)) -> String {
+        let mut settings = AppSettings::default();
+        change(&mut settings);
+        settings.validate().unwrap_err()
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        AppSettings::default().validate().unwrap();
+    }
+
+    #[test]
+    fn unusable_settings_are_rejected() {
+        assert!(invalid(|s| s.llm_provider = "claude".into()).contains("Unknown LLM provider"));
+        assert!(invalid(|s| s.embedding_provider = "mock".into()).contains("Unknown embedding provider"));
+        assert!(invalid(|s| s.llm_model = "  ".into()).contains("LLM model"));
+        assert!(invalid(|s| s.embedding_model = String::new()).contains("Embedding model"));
+        assert!(invalid(|s| s.embedding_dimension = 0).contains("Embedding dimension"));
+        assert!(invalid(|s| s.embedding_dimension = 8193).contains("Embedding dimension"));
+        assert!(invalid(|s| s.chunk_max_tokens = 31).contains("Chunk size"));
+        assert!(invalid(|s| s.chunk_overlap_tokens = s.chunk_max_tokens / 2 + 1).contains("Chunk overlap"));
+        assert!(invalid(|s| s.source_refresh_hours = MAX_SOURCE_REFRESH_HOURS + 1).contains("refresh interval"));
+        assert!(invalid(|s| s.ollama_url = "localhost:11434".into()).contains("Ollama URL"));
+        assert!(invalid(|s| s.openai_base_url = "ftp://example.com".into()).contains("http or https"));
+        assert!(invalid(|s| s.gemini_base_url = "not a url".into()).contains("Gemini base URL"));
+    }
+
+    #[test]
+    fn gemini_needs_an_api_key() {
+        let mut settings = AppSettings {
+            embedding_provider: "gemini".to_string(),
+            gemini_api_key: Some(" ".to_string()),
+            ..AppSettings::default()
+        };
+        assert_eq!(settings.validate().unwrap_err(), "API key required for Gemini");
+
+        settings.gemini_api_key = Some("key".to_string());
+        settings.validate().unwrap();
+    }
+
+    #[test]
+    fn saved_settings_load_back_and_invalid_ones_are_not_saved() {
+        let conn = open_db();
+        assert_eq!(load(&conn).unwrap(), AppSettings::default());
+
+        let saved = AppSettings {
+            llm_provider: "openai".to_string(),
+            llm_model: "gpt-4o-mini".to_string(),
+            source_refresh_hours: 24,
+            ..AppSettings::default()
+        };
+        save(&conn, &saved).unwrap();
+        assert_eq!(load(&conn).unwrap(), saved);
+
+        let broken = AppSettings { llm_model: String::new(), ..saved.clone() };
+        assert!(save(&conn, &broken).is_err());
+        assert_eq!(load(&conn).unwrap(), saved);
+    }
+
+    #[test]
+    fn stored_values_that_no_longer_fit_fall_back_to_defaults() {
+        let conn = open_db();
+        conn.execute_batch(
+            "INSERT INTO settings (key, value) VALUES ('removedSetting', '1');
+             INSERT INTO settings (key, value) VALUES ('llmModel', 'not json');",
+        )
+        .unwrap();
+        assert_eq!(load(&conn).unwrap(), AppSettings::default());
+
+        conn.execute("INSERT INTO settings (key, value) VALUES ('embeddingDimension', '\"big\"')", [])
+            .unwrap();
+        assert_eq!(load(&conn).unwrap(), AppSettings::default());
+    }
+
+    #[test]
+    fn merge_applies_known_fields_only() {
+        let current = AppSettings::default();
+        let merged = merge(&current, json!({ "llmModel": "llama3", "sourceRefreshHours": 6 })).unwrap();
+        assert_eq!(merged.llm_model, "llama3");
+        assert_eq!(merged.source_refresh_hours, 6);
+        assert_eq!(merged.embedding_model, current.embedding_model);
+
+        assert_eq!(merge(&current, json!({ "colour": "red" })).unwrap_err(), "Unknown setting: colour");
+        assert!(merge(&current, json!(["llmModel"])).is_err());
+        assert!(merge(&current, json!({ "embeddingDimension": "big" })).unwrap_err().starts_with("Invalid settings"));
+    }
+}
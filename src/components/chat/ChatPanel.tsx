@@ -1,10 +1,39 @@
 import { useState, useRef, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
-import { Send, X, Bot, User, RefreshCw } from 'lucide-react';
+import { listen, UnlistenFn } from '@tauri-apps/api/event';
+import { Send, X, Bot, User, RefreshCw, Square, FileText, History, Plus, Pencil, Trash2, FilePlus } from 'lucide-react';
+import { ChatSession, ChatSource } from '../../types';
+import { useEditorStore } from '../../stores/useEditorStore';
+import {
+  listChatSessions,
+  createChatSession,
+  openChatSession,
+  renameChatSession,
+  deleteChatSession,
+  saveChatSessionAsNode,
+} from '../../lib/tauri';
 
 interface ChatMessage {
   role: 'user' | 'assistant' | 'system';
   content: string;
+  sources?: ChatSource[];
+}
+
+interface ChatDeltaEvent {
+  requestId: string;
+  delta: string;
+}
+
+interface ChatDoneEvent {
+  requestId: string;
+  content: string;
+  sources: ChatSource[];
+}
+
+interface ChatErrorEvent {
+  requestId: string;
+  error: string;
+  cancelled: boolean;
 }
 
 interface ChatPanelProps {
@@ -17,6 +46,11 @@ export function ChatPanel({ isOpen, onClose }: ChatPanelProps) {
   const [messages, setMessages] = useState<ChatMessage[]>([]);
   const [loading, setLoading] = useState(false);
   const scrollRef = useRef<HTMLDivElement>(null);
+  const requestIdRef = useRef<string | null>(null);
+  const openArtifact = useEditorStore(state => state.openArtifact);
+  const [sessionId, setSessionId] = useState<string | null>(null);
+  const [sessions, setSessions] = useState<ChatSession[]>([]);
+  const [showSessions, setShowSessions] = useState(false);
 
   useEffect(() => {
     if (scrollRef.current) {
@@ -24,6 +58,69 @@ export function ChatPanel({ isOpen, onClose }: ChatPanelProps) {
     }
   }, [messages]);
 
+  const refreshSessions = async () => {
+    try {
+      setSessions(await listChatSessions());
+    } catch (error) {
+      console.error('Failed to load chat sessions:', error);
+    }
+  };
+
+  useEffect(() => {
+    if (isOpen) refreshSessions();
+  }, [isOpen]);
+
+  const handleNewChat = () => {
+    if (loading) return;
+    setSessionId(null);
+    setMessages([]);
+    setShowSessions(false);
+  };
+
+  const handleOpenSession = async (id: string) => {
+    if (loading) return;
+    try {
+      const { messages: stored } = await openChatSession(id);
+      setMessages(stored.map(({ role, content, sources }) => ({ role, content, sources })));
+      setSessionId(id);
+      setShowSessions(false);
+    } catch (error) {
+      console.error('Failed to open chat session:', error);
+    }
+  };
+
+  const handleRenameSession = async (session: ChatSession) => {
+    const title = window.prompt('Rename conversation', session.title);
+    if (!title || !title.trim()) return;
+    try {
+      await renameChatSession(session.id, title);
+      await refreshSessions();
+    } catch (error) {
+      console.error('Failed to rename chat session:', error);
+    }
+  };
+
+  const handleDeleteSession = async (session: ChatSession) => {
+    if (!window.confirm(`Delete "${session.title}"?`)) return;
+    try {
+      await deleteChatSession(session.id);
+      if (session.id === sessionId) handleNewChat();
+      await refreshSessions();
+    } catch (error) {
+      console.error('Failed to delete chat session:', error);
+    }
+  };
+
+  const handleSaveAsNode = async () => {
+    if (!sessionId) return;
+    try {
+      const node = await saveChatSessionAsNode(sessionId);
+      openArtifact(node.id);
+    } catch (error) {
+      console.error('Failed to save conversation:', error);
+    }
+  };
+
   const handleSubmit = async (e: React.FormEvent) => {
     e.preventDefault();
     if (!input.trim() || loading) return;
@@ -33,22 +130,74 @@ export function ChatPanel({ isOpen, onClose }: ChatPanelProps) {
     setInput('');
     setLoading(true);
 
+    const requestId = crypto.randomUUID();
+    requestIdRef.current = requestId;
+
+    // Placeholder the streamed deltas are appended to
+    setMessages(prev => [...prev, { role: 'assistant', content: '' }]);
+    const updateAnswer = (update: (content: string) => string, sources?: ChatSource[]) => {
+      setMessages(prev => {
+        const next = [...prev];
+        const last = next[next.length - 1];
+        next[next.length - 1] = { ...last, content: update(last.content), sources: sources ?? last.sources };
+        return next;
+      });
+    };
+
+    const unlisteners: UnlistenFn[] = [];
+    const finish = () => {
+      unlisteners.forEach(unlisten => unlisten());
+      requestIdRef.current = null;
+      setLoading(false);
+      refreshSessions();
+    };
+
     try {
-      // Prepare history for context (exclude system messages if any, keep last 10)
-      const history = messages.slice(-10);
-      
-      const response = await invoke<string>('chat', { 
+      // The backend keeps the history of the session
+      let activeSessionId = sessionId;
+      if (!activeSessionId) {
+        activeSessionId = (await createChatSession()).id;
+        setSessionId(activeSessionId);
+      }
+
+      unlisteners.push(
+        await listen<ChatDeltaEvent>('chat-delta', ({ payload }) => {
+          if (payload.requestId !== requestId) return;
+          updateAnswer(content => content + payload.delta);
+        }),
+        await listen<ChatDoneEvent>('chat-done', ({ payload }) => {
+          if (payload.requestId !== requestId) return;
+          updateAnswer(() => payload.content, payload.sources);
+          finish();
+        }),
+        await listen<ChatErrorEvent>('chat-error', ({ payload }) => {
+          if (payload.requestId !== requestId) return;
+          if (!payload.cancelled) {
+            console.error('Chat failed:', payload.error);
+            updateAnswer(content => content || 'Error: Failed to get response.');
+          }
+          finish();
+        }),
+      );
+
+      await invoke<string>('chat_stream', {
         message: userMsg.content,
-        history: history 
+        sessionId: activeSessionId,
+        requestId,
       });
-
-      const botMsg: ChatMessage = { role: 'assistant', content: response };
-      setMessages(prev => [...prev, botMsg]);
     } catch (error) {
       console.error('Chat failed:', error);
-      setMessages(prev => [...prev, { role: 'assistant', content: 'Error: Failed to get response.' }]);
-    } finally {
-      setLoading(false);
+      updateAnswer(() => 'Error: Failed to get response.');
+      finish();
+    }
+  };
+
+  const handleStop = async () => {
+    if (!requestIdRef.current) return;
+    try {
+      await invoke('cancel_chat', { requestId: requestIdRef.current });
+    } catch (error) {
+      console.error('Cancel failed:', error);
     }
   };
 
@@ -61,11 +210,60 @@ export function ChatPanel({ isOpen, onClose }: ChatPanelProps) {
           <Bot className="w-5 h-5 text-purple-500" />
           <span>Research Assistant</span>
         </div>
-        <button onClick={onClose} className="p-1 hover:bg-zinc-200 dark:hover:bg-zinc-800 rounded">
-          <X className="w-4 h-4" />
-        </button>
+        <div className="flex items-center gap-1">
+          <button onClick={() => setShowSessions(!showSessions)} className="p-1 hover:bg-zinc-200 dark:hover:bg-zinc-800 rounded" title="Conversations">
+            <History className="w-4 h-4" />
+          </button>
+          <button onClick={handleNewChat} disabled={loading} className="p-1 hover:bg-zinc-200 dark:hover:bg-zinc-800 rounded disabled:opacity-50" title="New conversation">
+            <Plus className="w-4 h-4" />
+          </button>
+          <button onClick={handleSaveAsNode} disabled={!sessionId || loading} className="p-1 hover:bg-zinc-200 dark:hover:bg-zinc-800 rounded disabled:opacity-50" title="Save conversation as node">
+            <FilePlus className="w-4 h-4" />
+          </button>
+          <button onClick={onClose} className="p-1 hover:bg-zinc-200 dark:hover:bg-zinc-800 rounded">
+            <X className="w-4 h-4" />
+          </button>
+        </div>
       </div>
 
+      {showSessions && (
+        <div className="max-h-64 overflow-y-auto border-b border-zinc-200 dark:border-zinc-800 bg-zinc-50 dark:bg-zinc-950/50">
+          {sessions.length === 0 && (
+            <p className="p-4 text-xs text-zinc-500 text-center">No saved conversations yet.</p>
+          )}
+          {sessions.map(session => (
+            <div
+              key={session.id}
+              className={`group flex items-center gap-2 px-4 py-2 text-sm cursor-pointer hover:bg-zinc-100 dark:hover:bg-zinc-800 ${
+                session.id === sessionId ? 'bg-zinc-100 dark:bg-zinc-800' : ''
+              }`}
+              onClick={() => handleOpenSession(session.id)}
+            >
+              <div className="flex-1 min-w-0">
+                <div className="truncate">{session.title}</div>
+                <div className="text-xs text-zinc-400">
+                  {session.messageCount} messages · {new Date(session.updatedAt).toLocaleString()}
+                </div>
+              </div>
+              <button
+                onClick={(e) => { e.stopPropagation(); handleRenameSession(session); }}
+                className="p-1 opacity-0 group-hover:opacity-100 hover:text-blue-600"
+                title="Rename"
+              >
+                <Pencil className="w-3 h-3" />
+              </button>
+              <button
+                onClick={(e) => { e.stopPropagation(); handleDeleteSession(session); }}
+                className="p-1 opacity-0 group-hover:opacity-100 hover:text-red-600"
+                title="Delete"
+              >
+                <Trash2 className="w-3 h-3" />
+              </button>
+            </div>
+          ))}
+        </div>
+      )}
+
       <div className="flex-1 overflow-y-auto p-4 space-y-4" ref={scrollRef}>
         {messages.length === 0 && (
           <div className="text-center text-zinc-500 mt-10 text-sm">
@@ -74,7 +272,7 @@ export function ChatPanel({ isOpen, onClose }: ChatPanelProps) {
           </div>
         )}
         
-        {messages.map((msg, i) => (
+        {messages.filter(msg => msg.content || msg.role !== 'assistant').map((msg, i) => (
           <div key={i} className={`flex gap-3 ${msg.role === 'user' ? 'justify-end' : 'justify-start'}`}>
             {msg.role === 'assistant' && (
               <div className="w-8 h-8 rounded-full bg-purple-100 dark:bg-purple-900/30 flex items-center justify-center shrink-0">
@@ -88,6 +286,29 @@ export function ChatPanel({ isOpen, onClose }: ChatPanelProps) {
                 : 'bg-zinc-100 dark:bg-zinc-800 text-zinc-900 dark:text-zinc-100 border border-zinc-200 dark:border-zinc-700'
             }`}>
               {msg.content}
+              {msg.sources && msg.sources.length > 0 && (
+                <div className="mt-2 pt-2 border-t border-zinc-200 dark:border-zinc-700 space-y-1">
+                  {msg.sources.map(source => (
+                    <button
+                      key={source.number}
+                      onClick={() => openArtifact(source.nodeId)}
+                      title={source.chunk.slice(0, 300)}
+                      className="flex items-center gap-1.5 w-full text-left text-xs text-zinc-500 hover:text-blue-600 dark:hover:text-blue-400"
+                    >
+                      <span className="font-mono">[{source.number}]</span>
+                      <FileText className="w-3 h-3 shrink-0" />
+                      <span className="truncate">{source.title}</span>
+                      {source.pageStart != null && (
+                        <span className="shrink-0">
+                          p. {source.pageEnd != null && source.pageEnd > source.pageStart
+                            ? `${source.pageStart}-${source.pageEnd}`
+                            : source.pageStart}
+                        </span>
+                      )}
+                    </button>
+                  ))}
+                </div>
+              )}
             </div>
 
             {msg.role === 'user' && (
@@ -98,7 +319,7 @@ export function ChatPanel({ isOpen, onClose }: ChatPanelProps) {
           </div>
         ))}
         
-        {loading && (
+        {loading && !messages[messages.length - 1]?.content && (
           <div className="flex gap-3 justify-start">
              <div className="w-8 h-8 rounded-full bg-purple-100 dark:bg-purple-900/30 flex items-center justify-center shrink-0">
                 <Bot className="w-4 h-4 text-purple-600 dark:text-purple-400" />
@@ -120,13 +341,23 @@ export function ChatPanel({ isOpen, onClose }: ChatPanelProps) {
             onChange={(e) => setInput(e.target.value)}
             disabled={loading}
           />
-          <button 
-            type="submit" 
-            disabled={!input.trim() || loading}
-            className="absolute right-2 top-2 p-1.5 bg-blue-600 hover:bg-blue-700 text-white rounded-md disabled:opacity-50 disabled:cursor-not-allowed transition-colors"
-          >
-            <Send className="w-4 h-4" />
-          </button>
+          {loading ? (
+            <button
+              type="button"
+              onClick={handleStop}
+              className="absolute right-2 top-2 p-1.5 bg-zinc-600 hover:bg-zinc-700 text-white rounded-md transition-colors"
+            >
+              <Square className="w-4 h-4" />
+            </button>
+          ) : (
+            <button 
+              type="submit" 
+              disabled={!input.trim()}
+              className="absolute right-2 top-2 p-1.5 bg-blue-600 hover:bg-blue-700 text-white rounded-md disabled:opacity-50 disabled:cursor-not-allowed transition-colors"
+            >
+              <Send className="w-4 h-4" />
+            </button>
+          )}
         </div>
       </form>
     </div>
@@ -4,6 +4,7 @@ import { Plus, Loader2, Link as LinkIcon, AlertCircle, CheckCircle } from 'lucid
 
 export function IngestUrlDialog({ isOpen, onClose }: { isOpen: boolean; onClose: () => void }) {
   const [url, setUrl] = useState('');
+  const [ignoreRobots, setIgnoreRobots] = useState(false);
   const [loading, setLoading] = useState(false);
   const [status, setStatus] = useState<'idle' | 'success' | 'error'>('idle');
   const [message, setMessage] = useState('');
@@ -17,11 +18,8 @@ export function IngestUrlDialog({ isOpen, onClose }: { isOpen: boolean; onClose:
     setMessage('');
 
     try {
-      await invoke('ingest_url', { 
-        url, 
-        provider: 'ollama', // Default for now
-        apiKey: null 
-      });
+      // Embedding provider and keys come from the backend settings
+      await invoke('ingest_url', { url, ignoreRobots });
       setStatus('success');
       setMessage('Successfully ingested URL!');
       setTimeout(() => {
@@ -72,6 +70,16 @@ export function IngestUrlDialog({ isOpen, onClose }: { isOpen: boolean; onClose:
             />
           </div>
 
+          <label className="flex items-center gap-2 text-sm text-zinc-600 dark:text-zinc-400">
+            <input
+              type="checkbox"
+              checked={ignoreRobots}
+              onChange={(e) => setIgnoreRobots(e.target.checked)}
+              disabled={loading}
+            />
+            Fetch even if the site's robots.txt disallows it
+          </label>
+
           {status === 'error' && (
             <div className="flex items-center gap-2 text-sm text-red-600 bg-red-50 dark:bg-red-900/20 p-2 rounded">
               <AlertCircle className="w-4 h-4" />
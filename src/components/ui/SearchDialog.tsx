@@ -11,10 +11,16 @@ interface SearchResult {
   snippet: string;
 }
 
+interface SearchResponse {
+  results: SearchResult[];
+  semanticError: string | null;
+}
+
 export function SearchDialog() {
   const [open, setOpen] = useState(false);
   const [query, setQuery] = useState('');
   const [results, setResults] = useState<SearchResult[]>([]);
+  const [semanticError, setSemanticError] = useState<string | null>(null);
   const [loading, setLoading] = useState(false);
   const openArtifact = useEditorStore(state => state.openArtifact);
   const inputRef = useRef<HTMLInputElement>(null);
@@ -46,13 +52,15 @@ export function SearchDialog() {
     const timer = setTimeout(async () => {
       if (!query.trim()) {
         setResults([]);
+        setSemanticError(null);
         return;
       }
       
       setLoading(true);
       try {
-        const res = await invoke<SearchResult[]>('search_nodes', { query, mode: 'hybrid' });
-        setResults(res);
+        const res = await invoke<SearchResponse>('search_nodes', { query, mode: 'hybrid' });
+        setResults(res.results);
+        setSemanticError(res.semanticError);
       } catch (error) {
         console.error('Search failed:', error);
       } finally {
@@ -127,7 +135,13 @@ export function SearchDialog() {
         </div>
         
         <div className="px-4 py-2 bg-zinc-50 dark:bg-zinc-950/50 border-t border-zinc-200 dark:border-zinc-800 text-xs text-zinc-500 flex justify-between">
-          <span>Search mode: Hybrid (Fuzzy + Semantic)</span>
+          {semanticError ? (
+            <span className="text-amber-600 dark:text-amber-400" title={semanticError}>
+              Semantic search unavailable, showing keyword matches only
+            </span>
+          ) : (
+            <span>Search mode: Hybrid (Fuzzy + Semantic)</span>
+          )}
           <div className="flex gap-2">
             <span className="bg-zinc-200 dark:bg-zinc-800 px-1.5 py-0.5 rounded">Esc</span> to close
             <span className="bg-zinc-200 dark:bg-zinc-800 px-1.5 py-0.5 rounded">Cmd K</span> to open
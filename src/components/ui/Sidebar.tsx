@@ -79,7 +79,7 @@ const Sidebar = () => {
                     
                     try {
                       target.disabled = true;
-                      await invoke('ingest_url', { url, provider: 'ollama', apiKey: null });
+                      await invoke('ingest_url', { url });
                       setShowIngest(false);
                       // Simple notification (ideally use a toast)
                       alert('Successfully ingested URL!');
@@ -1,5 +1,5 @@
 import { invoke as tauriInvoke } from '@tauri-apps/api/core';
-import { Node, Edge, NodeType } from '../types';
+import { Node, Edge, NodeType, AppSettings, ChatSession, ChatSessionMessage, TrashedNode, FileIngestResult, CrawlOptions, RefreshResult, SourceChanges } from '../types';
 
 // Wrapper to prevent crashes in non-Tauri environments
 const invoke = async <T>(cmd: string, args?: any): Promise<T> => {
@@ -24,6 +24,27 @@ export const saveNodeContent = async (id: string, content: string): Promise<void
   await invoke('save_node_content', { id, content });
 };
 
+// Moves the node to the trash unless `permanent` is set
+export const deleteNode = async (id: string, permanent?: boolean): Promise<void> => {
+  await invoke('delete_node', { id, permanent });
+};
+
+export const restoreNode = async (id: string): Promise<void> => {
+  await invoke('restore_node', { id });
+};
+
+export const listTrash = async (): Promise<TrashedNode[]> => {
+  return await invoke<TrashedNode[]>('list_trash');
+};
+
+export const purgeNode = async (id: string): Promise<void> => {
+  await invoke('purge_node', { id });
+};
+
+export const emptyTrash = async (): Promise<number> => {
+  return await invoke<number>('empty_trash');
+};
+
 export const connectNodes = async (sourceId: string, targetId: string, label?: string): Promise<void> => {
   await invoke('connect_nodes', { sourceId, targetId, label });
 };
@@ -39,3 +60,86 @@ export const getGraphData = async (): Promise<{ nodes: Node[], edges: Edge[] }>
 export const updateNodePosition = async (id: string, x: number, y: number): Promise<void> => {
   await invoke('update_node_position', { id, x, y });
 };
+
+export const getSettings = async (): Promise<AppSettings> => {
+  return await invoke<AppSettings>('get_settings');
+};
+
+export const updateSettings = async (settings: Partial<AppSettings>): Promise<AppSettings> => {
+  return await invoke<AppSettings>('update_settings', { settings });
+};
+
+export const listChatSessions = async (): Promise<ChatSession[]> => {
+  return await invoke<ChatSession[]>('list_chat_sessions');
+};
+
+export const createChatSession = async (title?: string): Promise<ChatSession> => {
+  return await invoke<ChatSession>('create_chat_session', { title });
+};
+
+export const openChatSession = async (id: string): Promise<{ session: ChatSession, messages: ChatSessionMessage[] }> => {
+  return await invoke('open_chat_session', { id });
+};
+
+export const renameChatSession = async (id: string, title: string): Promise<void> => {
+  await invoke('rename_chat_session', { id, title });
+};
+
+export const deleteChatSession = async (id: string): Promise<void> => {
+  await invoke('delete_chat_session', { id });
+};
+
+export const saveChatSessionAsNode = async (id: string): Promise<Node> => {
+  return await invoke<Node>('save_chat_session_as_node', { id });
+};
+
+// Imports a local file as a source node; `path` is an absolute file path.
+// Re-importing the same contents returns the existing node.
+export const ingestFile = async (path: string): Promise<Node> => {
+  return await invoke<Node>('ingest_file', { path });
+};
+
+// Imports every supported file under a folder; globs are relative to it.
+// Progress arrives as 'folder-ingest-progress' events.
+export const ingestFolder = async (
+  path: string,
+  include?: string[],
+  exclude?: string[],
+): Promise<FileIngestResult[]> => {
+  return await invoke<FileIngestResult[]>('ingest_folder', { path, include, exclude });
+};
+
+// Starts a background crawl and returns its job id.
+// Progress arrives as 'crawl-progress' events.
+export const startCrawl = async (url: string, options?: CrawlOptions): Promise<string> => {
+  return await invoke<string>('start_crawl', { url, options });
+};
+
+export const pauseCrawl = async (jobId: string): Promise<void> => {
+  await invoke('pause_crawl', { jobId });
+};
+
+export const resumeCrawl = async (jobId: string): Promise<void> => {
+  await invoke('resume_crawl', { jobId });
+};
+
+export const cancelCrawl = async (jobId: string): Promise<void> => {
+  await invoke('cancel_crawl', { jobId });
+};
+
+// Re-fetches a web source and re-indexes it if its text changed.
+export const refreshSource = async (id: string): Promise<RefreshResult> => {
+  return await invoke<RefreshResult>('refresh_source', { id });
+};
+
+// Refreshes every web source. Progress arrives as 'source-refresh-progress' events;
+// the background scheduler reports failures as 'source-refresh-error' events.
+export const refreshSources = async (): Promise<RefreshResult[]> => {
+  return await invoke<RefreshResult[]>('refresh_sources');
+};
+
+// What changed in a source's text since a kept earlier version (by default
+// the one before the last update).
+export const getSourceChanges = async (id: string, version?: number): Promise<SourceChanges> => {
+  return await invoke<SourceChanges>('get_source_changes', { id, version });
+};
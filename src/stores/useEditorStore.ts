@@ -55,7 +55,8 @@ export const useEditorStore = create<EditorState>((set, get) => ({
     set({ isLoading: true, error: null });
     try {
       await saveNodeContent(activeNode.id, content);
-      set({ isDirty: false, isLoading: false });
+      // Re-indexed in the background; `node-index-status` events report progress
+      set({ activeNode: { ...activeNode, indexStatus: 'stale' }, isDirty: false, isLoading: false });
     } catch (err: any) {
       set({ error: err.message || 'Failed to save content', isLoading: false });
     }
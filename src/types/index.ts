@@ -4,6 +4,9 @@ export interface NodeMetadata {
   [key: string]: any;
 }
 
+// Whether search and chat see a node's latest content
+export type IndexStatus = 'indexed' | 'stale' | 'indexing' | 'failed';
+
 export interface Node {
   id: string;
   type: NodeType;
@@ -12,6 +15,19 @@ export interface Node {
   metadata: NodeMetadata;
   createdAt: string;
   updatedAt: string;
+  indexStatus: IndexStatus;
+}
+
+// Payload of the `node-index-status` event
+export interface NodeIndexEvent {
+  // Null for failures not about one node, e.g. listing stale nodes
+  nodeId: string | null;
+  status: IndexStatus;
+  error?: string | null;
+}
+
+export interface TrashedNode extends Node {
+  deletedAt: string;
 }
 
 export interface Edge {
@@ -20,3 +36,119 @@ export interface Edge {
   target: string;
   label?: string;
 }
+
+export type LlmProvider = 'ollama' | 'openai' | 'gemini' | 'mock';
+export type EmbeddingProvider = 'ollama' | 'gemini' | 'openai' | 'hash';
+
+export interface AppSettings {
+  llmProvider: LlmProvider;
+  llmModel: string;
+  embeddingProvider: EmbeddingProvider;
+  embeddingModel: string;
+  embeddingDimension: number;
+  ollamaUrl: string;
+  openaiBaseUrl: string;
+  openaiApiKey: string | null;
+  geminiBaseUrl: string;
+  geminiApiKey: string | null;
+  chunkMaxTokens: number;
+  chunkOverlapTokens: number;
+  // Hours between automatic refreshes of web sources; 0 turns them off
+  sourceRefreshHours: number;
+}
+
+export interface FileIngestResult {
+  path: string;
+  status: 'imported' | 'duplicate' | 'failed';
+  // The new node, or the existing one for a duplicate
+  nodeId: string | null;
+  error: string | null;
+}
+
+export interface FolderIngestProgress {
+  processed: number;
+  total: number;
+  result: FileIngestResult;
+}
+
+export interface CrawlOptions {
+  // Link hops from the seed; 0 ingests only the seed
+  maxDepth?: number;
+  maxPages?: number;
+  // Globs over full URLs; without any, links on the seed's host are followed
+  include?: string[];
+  exclude?: string[];
+}
+
+export interface CrawlProgress {
+  jobId: string;
+  status: 'running' | 'paused' | 'done' | 'cancelled' | 'error';
+  seedUrl: string;
+  // Pages fetched so far, including failed ones
+  processed: number;
+  ingested: number;
+  failedCount: number;
+  queued: number;
+  currentUrl: string | null;
+  failed: string[];
+  message: string | null;
+}
+
+export interface RefreshResult {
+  nodeId: string;
+  title: string;
+  // 'edited': changed in the app since it was fetched, so left alone
+  status: 'updated' | 'unchanged' | 'not_modified' | 'edited' | 'failed';
+  // Lines of text added and removed, for an update
+  addedLines: number;
+  removedLines: number;
+  error: string | null;
+}
+
+export interface SourceRefreshProgress {
+  processed: number;
+  total: number;
+  result: RefreshResult;
+}
+
+export interface SourceChanges {
+  nodeId: string;
+  previousVersion: number;
+  previousUpdatedAt: string;
+  updatedAt: string;
+  // Unified diff from the previous text to the current one
+  diff: string;
+  addedLines: number;
+  removedLines: number;
+}
+
+export interface ChatSource {
+  number: number;
+  nodeId: string;
+  title: string;
+  // Headings the passage sits under, outermost first
+  section: string[];
+  // Pages the passage covers, for PDFs
+  pageStart?: number | null;
+  pageEnd?: number | null;
+  chunk: string;
+  distance: number;
+}
+
+export interface ChatSession {
+  id: string;
+  title: string;
+  messageCount: number;
+  createdAt: string;
+  updatedAt: string;
+}
+
+export interface ChatSessionMessage {
+  id: number;
+  sessionId: string;
+  role: 'user' | 'assistant' | 'system';
+  content: string;
+  model: string | null;
+  sources: ChatSource[];
+  createdAt: string;
+}